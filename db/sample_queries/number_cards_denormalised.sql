@@ -0,0 +1,8 @@
+-- Active cards per user, read from the denormalised accounts.num_active_cards column.
+SELECT 
+    users.id AS user_id,
+    COALESCE(SUM(accounts.num_active_cards), 0) AS cards_total
+FROM users
+LEFT JOIN accounts
+ON users.id = accounts.user_id
+GROUP BY users.id;
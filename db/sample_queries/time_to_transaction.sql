@@ -6,7 +6,8 @@ FROM
 JOIN (
     SELECT created_at, account_id FROM transactions
     UNION ALL
-    SELECT created_at, account_id FROM transfers
+    -- A transfer is the sending account's action; transfers have no account_id column.
+    SELECT created_at, sender_account_id FROM transfers
     UNION ALL
     SELECT created_at, account_id FROM payments
 ) AS action 
@@ -33,6 +33,12 @@ CREATE TABLE audit_logs (
             'account deleted'
         )
     ),
-    details TEXT,
+    details JSONB,
     created_at TIMESTAMP DEFAULT NOW()
 );
+
+-- Containment searches over payload fields, e.g. details @> '{"after": {"loan_id": 1}}'.
+CREATE INDEX idx_audit_logs_details ON audit_logs USING GIN (details jsonb_path_ops);
+
+-- A subject's history, in time order.
+CREATE INDEX idx_audit_logs_subject ON audit_logs (subject_table, subject_id, created_at);
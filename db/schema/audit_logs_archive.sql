@@ -0,0 +1,10 @@
+-- Audit rows moved out of audit_logs by the retention job, with their original ids.
+CREATE TABLE audit_logs_archive (
+    id INT PRIMARY KEY,
+    subject_table VARCHAR(50),
+    subject_id INT,
+    action VARCHAR(50),
+    details JSONB,
+    created_at TIMESTAMP,
+    archived_at TIMESTAMPTZ DEFAULT NOW()
+);
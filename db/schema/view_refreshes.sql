@@ -0,0 +1,7 @@
+CREATE TABLE view_refreshes (
+    view_name VARCHAR(64) PRIMARY KEY,
+    refreshed_at TIMESTAMPTZ NOT NULL,
+    -- Latest created_at in the view's source table when the refresh started.
+    covered_until TIMESTAMP,
+    duration_ms DOUBLE PRECISION NOT NULL
+);
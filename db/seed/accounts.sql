@@ -0,0 +1,18 @@
+-- One account of each type per user, in the same id order as BankSystemManager::insert_accounts.
+INSERT INTO public.accounts
+(user_id, account_type, balance, created_at, num_active_cards)
+SELECT
+    users.id,
+    account_types.account_type,
+    FLOOR(random() * 1000001),
+    NOW() - INTERVAL '9 weeks' + random() * INTERVAL '1 week',
+    account_types.num_active_cards
+FROM public.users
+CROSS JOIN (
+    VALUES
+        (1, 'checking', 1),
+        (2, 'savings', 0),
+        (3, 'credit', 1),
+        (4, 'business', 2)
+) AS account_types(position, account_type, num_active_cards)
+ORDER BY users.id, account_types.position;
@@ -0,0 +1,16 @@
+-- A 'created' audit log for every seeded row, with the row as the payload's after value, as the
+-- row by row generator writes. Card numbers are left out, as they are from generated payloads.
+INSERT INTO public.audit_logs (subject_table, subject_id, action, details, created_at)
+SELECT 'users', id, 'user created', jsonb_build_object('actor', 'seed', 'after', to_jsonb(t) - 'id'), created_at FROM public.users t;
+INSERT INTO public.audit_logs (subject_table, subject_id, action, details, created_at)
+SELECT 'accounts', id, 'account created', jsonb_build_object('actor', 'seed', 'after', to_jsonb(t) - 'id'), created_at FROM public.accounts t;
+INSERT INTO public.audit_logs (subject_table, subject_id, action, details, created_at)
+SELECT 'cards', id, 'card created', jsonb_build_object('actor', 'seed', 'after', to_jsonb(t) - 'id' - 'card_number'), expiration_date FROM public.cards t;
+INSERT INTO public.audit_logs (subject_table, subject_id, action, details, created_at)
+SELECT 'transfers', id, 'transfer created', jsonb_build_object('actor', 'seed', 'after', to_jsonb(t) - 'id'), created_at FROM public.transfers t;
+INSERT INTO public.audit_logs (subject_table, subject_id, action, details, created_at)
+SELECT 'transactions', id, 'transaction created', jsonb_build_object('actor', 'seed', 'after', to_jsonb(t) - 'id'), created_at FROM public.transactions t;
+INSERT INTO public.audit_logs (subject_table, subject_id, action, details, created_at)
+SELECT 'loans', id, 'loan created', jsonb_build_object('actor', 'seed', 'after', to_jsonb(t) - 'id'), created_at FROM public.loans t;
+INSERT INTO public.audit_logs (subject_table, subject_id, action, details, created_at)
+SELECT 'payments', id, 'payment created', jsonb_build_object('actor', 'seed', 'after', to_jsonb(t) - 'id'), created_at FROM public.payments t;
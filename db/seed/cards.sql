@@ -0,0 +1,19 @@
+-- A debit card for checking, a credit card for credit, and one of each for business accounts.
+INSERT INTO public.cards
+(account_id, card_number, card_type, expiration_date, status)
+SELECT
+    accounts.id,
+    LPAD((ROW_NUMBER() OVER (ORDER BY accounts.id, card_types.position))::TEXT, 16, '0'),
+    card_types.card_type,
+    NOW() + INTERVAL '6 weeks' + random() * INTERVAL '6 weeks',
+    'active'
+FROM public.accounts
+JOIN (
+    VALUES
+        (1, 'checking', 'debit'),
+        (2, 'credit', 'credit'),
+        (3, 'business', 'debit'),
+        (4, 'business', 'credit')
+) AS card_types(position, account_type, card_type)
+ON accounts.account_type = card_types.account_type
+ORDER BY accounts.id, card_types.position;
@@ -0,0 +1,12 @@
+-- One active loan per user.
+INSERT INTO public.loans
+(user_id, term_months, interest_rate, amount, status, created_at)
+SELECT
+    users.id,
+    24,
+    4.50,
+    FLOOR(random() * 100000) + 1,
+    'active',
+    NOW() - INTERVAL '6 weeks' + random() * INTERVAL '1 week'
+FROM public.users
+ORDER BY users.id;
@@ -0,0 +1,13 @@
+-- Three payments per loan, made from the borrower's checking account.
+INSERT INTO public.payments
+(account_id, loan_id, amount, status, created_at)
+SELECT
+    accounts.id,
+    loans.id,
+    FLOOR(random() * 1000) + 1,
+    'completed',
+    NOW() - INTERVAL '6 weeks' + random() * INTERVAL '1 week'
+FROM public.loans
+JOIN public.accounts ON accounts.user_id = loans.user_id AND accounts.account_type = 'checking'
+CROSS JOIN generate_series(1, 3)
+ORDER BY loans.id;
@@ -0,0 +1,13 @@
+-- A deposit and a withdrawal for each checking and business account.
+INSERT INTO public.transactions
+(account_id, transaction_type, amount, status, created_at)
+SELECT
+    accounts.id,
+    CASE WHEN g = 0 THEN 'deposit' ELSE 'withdrawal' END,
+    FLOOR(random() * 100000) + 1,
+    'pending',
+    NOW() - INTERVAL '6 weeks' + random() * INTERVAL '1 week'
+FROM public.accounts
+CROSS JOIN generate_series(0, 1) AS g
+WHERE accounts.account_type IN ('checking', 'business')
+ORDER BY accounts.id, g;
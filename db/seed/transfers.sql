@@ -0,0 +1,14 @@
+-- Five transfers from each checking and business account to the account half the table away.
+INSERT INTO public.transfers
+(sender_account_id, receiver_account_id, amount, status, created_at)
+SELECT
+    accounts.id,
+    (accounts.id + account_count.total / 2) % account_count.total + 1,
+    FLOOR(random() * 1000) + 1,
+    'completed',
+    NOW() - INTERVAL '6 weeks' + random() * INTERVAL '1 week'
+FROM public.accounts
+CROSS JOIN (SELECT COUNT(*)::INT AS total FROM public.accounts) AS account_count
+CROSS JOIN generate_series(1, 5)
+WHERE accounts.account_type IN ('checking', 'business')
+ORDER BY accounts.id;
@@ -0,0 +1,12 @@
+-- $1 users, mirroring BankSystemManager::insert_users with generated rather than fake names.
+INSERT INTO public.users
+(given_name, family_name, username, email, phone, created_at)
+SELECT
+    'given_' || g,
+    'family_' || g,
+    'user_' || g,
+    'user_' || g || '@example.com',
+    '07' || LPAD(g::TEXT, 9, '0'),
+    NOW() - INTERVAL '10 weeks' + random() * INTERVAL '1 week'
+FROM generate_series(1, $1) AS g
+ORDER BY g;
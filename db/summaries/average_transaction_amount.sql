@@ -0,0 +1,48 @@
+-- Trigger maintained alternative to the average_transaction_amount materialized view.
+-- Keeps a running sum and count per account so the average is always current.
+CREATE TABLE average_transaction_amount_summary (
+    account_id INT PRIMARY KEY,
+    transaction_sum DECIMAL(14,2) NOT NULL,
+    transaction_count BIGINT NOT NULL
+);
+
+INSERT INTO average_transaction_amount_summary (account_id, transaction_sum, transaction_count)
+SELECT account_id, SUM(amount), COUNT(*)
+FROM transactions
+WHERE account_id IS NOT NULL
+GROUP BY account_id;
+
+CREATE FUNCTION apply_average_transaction_amount_delta(p_account_id INT, p_amount DECIMAL, p_count INT)
+RETURNS VOID AS $$
+BEGIN
+    IF p_account_id IS NULL THEN
+        RETURN;
+    END IF;
+
+    INSERT INTO average_transaction_amount_summary AS s (account_id, transaction_sum, transaction_count)
+    VALUES (p_account_id, p_amount, p_count)
+    ON CONFLICT (account_id) DO UPDATE
+    SET transaction_sum = s.transaction_sum + EXCLUDED.transaction_sum,
+        transaction_count = s.transaction_count + EXCLUDED.transaction_count;
+
+    DELETE FROM average_transaction_amount_summary
+    WHERE account_id = p_account_id AND transaction_count <= 0;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE FUNCTION maintain_average_transaction_amount_summary()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP IN ('UPDATE', 'DELETE') THEN
+        PERFORM apply_average_transaction_amount_delta(OLD.account_id, -OLD.amount, -1);
+    END IF;
+    IF TG_OP IN ('INSERT', 'UPDATE') THEN
+        PERFORM apply_average_transaction_amount_delta(NEW.account_id, NEW.amount, 1);
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trg_average_transaction_amount_summary
+AFTER INSERT OR UPDATE OF account_id, amount OR DELETE ON transactions
+FOR EACH ROW EXECUTE FUNCTION maintain_average_transaction_amount_summary();
@@ -0,0 +1,48 @@
+-- Trigger maintained alternative to the loans_outstanding materialized view.
+-- Only active loans count, so status changes move a loan in or out of its user's total.
+CREATE TABLE loans_outstanding_summary (
+    user_id INT PRIMARY KEY,
+    sum_loans_outstanding DECIMAL(14,2) NOT NULL,
+    active_loans INT NOT NULL
+);
+
+INSERT INTO loans_outstanding_summary (user_id, sum_loans_outstanding, active_loans)
+SELECT user_id, SUM(amount), COUNT(*)
+FROM loans
+WHERE user_id IS NOT NULL AND status = 'active'
+GROUP BY user_id;
+
+CREATE FUNCTION apply_loans_outstanding_delta(p_user_id INT, p_amount DECIMAL, p_count INT)
+RETURNS VOID AS $$
+BEGIN
+    IF p_user_id IS NULL THEN
+        RETURN;
+    END IF;
+
+    INSERT INTO loans_outstanding_summary AS s (user_id, sum_loans_outstanding, active_loans)
+    VALUES (p_user_id, p_amount, p_count)
+    ON CONFLICT (user_id) DO UPDATE
+    SET sum_loans_outstanding = s.sum_loans_outstanding + EXCLUDED.sum_loans_outstanding,
+        active_loans = s.active_loans + EXCLUDED.active_loans;
+
+    DELETE FROM loans_outstanding_summary
+    WHERE user_id = p_user_id AND active_loans <= 0;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE FUNCTION maintain_loans_outstanding_summary()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP IN ('UPDATE', 'DELETE') AND OLD.status = 'active' THEN
+        PERFORM apply_loans_outstanding_delta(OLD.user_id, -OLD.amount, -1);
+    END IF;
+    IF TG_OP IN ('INSERT', 'UPDATE') AND NEW.status = 'active' THEN
+        PERFORM apply_loans_outstanding_delta(NEW.user_id, NEW.amount, 1);
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trg_loans_outstanding_summary
+AFTER INSERT OR UPDATE OF user_id, amount, status OR DELETE ON loans
+FOR EACH ROW EXECUTE FUNCTION maintain_loans_outstanding_summary();
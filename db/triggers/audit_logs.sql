@@ -0,0 +1,84 @@
+-- Writes an audit log for every insert, update and delete on the audited tables, whatever the
+-- write path. Each table's trigger only fires for the operations audit_logs has an action for,
+-- so transactions are never 'updated' and transfers, loans and payments are never 'deleted'.
+-- The actor and correlation id come from the audit.actor and audit.correlation_id settings, when
+-- set. Card numbers are left out of the payload, as they are from application written payloads.
+CREATE FUNCTION write_audit_log()
+RETURNS TRIGGER AS $$
+DECLARE
+    v_before JSONB;
+    v_after JSONB;
+    v_subject_id INT;
+    v_created_at TIMESTAMP := LOCALTIMESTAMP;
+    v_details JSONB;
+BEGIN
+    IF TG_OP IN ('UPDATE', 'DELETE') THEN
+        v_before := to_jsonb(OLD) - 'id' - 'card_number';
+        v_subject_id := OLD.id;
+    END IF;
+    IF TG_OP IN ('INSERT', 'UPDATE') THEN
+        v_after := to_jsonb(NEW) - 'id' - 'card_number';
+        v_subject_id := NEW.id;
+    END IF;
+    IF TG_OP = 'UPDATE' AND v_before = v_after THEN
+        RETURN NULL;
+    END IF;
+    -- Like the generator, date a created row's audit log by the row's own created_at.
+    IF TG_OP = 'INSERT' THEN
+        v_created_at := COALESCE((v_after->>'created_at')::TIMESTAMP, v_created_at);
+    END IF;
+
+    v_details := jsonb_strip_nulls(jsonb_build_object(
+        'actor', NULLIF(current_setting('audit.actor', true), ''),
+        'correlation_id', NULLIF(current_setting('audit.correlation_id', true), '')
+    ));
+    IF v_before IS NOT NULL THEN
+        v_details := v_details || jsonb_build_object('before', v_before);
+    END IF;
+    IF v_after IS NOT NULL THEN
+        v_details := v_details || jsonb_build_object('after', v_after);
+    END IF;
+
+    INSERT INTO audit_logs (subject_table, subject_id, action, details, created_at)
+    VALUES (
+        TG_TABLE_NAME,
+        v_subject_id,
+        left(TG_TABLE_NAME, -1) || ' ' || CASE TG_OP
+            WHEN 'INSERT' THEN 'created'
+            WHEN 'UPDATE' THEN 'updated'
+            ELSE 'deleted'
+        END,
+        v_details,
+        v_created_at
+    );
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trg_users_audit
+AFTER INSERT OR UPDATE OR DELETE ON users
+FOR EACH ROW EXECUTE FUNCTION write_audit_log();
+
+CREATE TRIGGER trg_accounts_audit
+AFTER INSERT OR UPDATE OR DELETE ON accounts
+FOR EACH ROW EXECUTE FUNCTION write_audit_log();
+
+CREATE TRIGGER trg_cards_audit
+AFTER INSERT OR UPDATE OR DELETE ON cards
+FOR EACH ROW EXECUTE FUNCTION write_audit_log();
+
+CREATE TRIGGER trg_transfers_audit
+AFTER INSERT OR UPDATE ON transfers
+FOR EACH ROW EXECUTE FUNCTION write_audit_log();
+
+CREATE TRIGGER trg_transactions_audit
+AFTER INSERT OR DELETE ON transactions
+FOR EACH ROW EXECUTE FUNCTION write_audit_log();
+
+CREATE TRIGGER trg_loans_audit
+AFTER INSERT OR UPDATE ON loans
+FOR EACH ROW EXECUTE FUNCTION write_audit_log();
+
+CREATE TRIGGER trg_payments_audit
+AFTER INSERT OR UPDATE ON payments
+FOR EACH ROW EXECUTE FUNCTION write_audit_log();
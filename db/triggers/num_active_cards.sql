@@ -0,0 +1,45 @@
+-- Keeps accounts.num_active_cards equal to the number of active cards on each account.
+-- Recounts rather than incrementing, so it also corrects any drift on the accounts it touches.
+CREATE FUNCTION recount_num_active_cards(p_account_id INT)
+RETURNS VOID AS $$
+BEGIN
+    IF p_account_id IS NULL THEN
+        RETURN;
+    END IF;
+
+    -- Lock the account first so the count below runs with a snapshot that sees cards committed
+    -- by any concurrent writer that held the lock before us.
+    PERFORM 1 FROM accounts WHERE id = p_account_id FOR UPDATE;
+    UPDATE accounts
+    SET num_active_cards = (
+        SELECT COUNT(*) FROM cards WHERE account_id = p_account_id AND status = 'active'
+    )
+    WHERE id = p_account_id;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE FUNCTION maintain_num_active_cards()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP IN ('UPDATE', 'DELETE') THEN
+        PERFORM recount_num_active_cards(OLD.account_id);
+    END IF;
+    IF TG_OP = 'INSERT' OR (TG_OP = 'UPDATE' AND NEW.account_id IS DISTINCT FROM OLD.account_id) THEN
+        PERFORM recount_num_active_cards(NEW.account_id);
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trg_num_active_cards
+AFTER INSERT OR UPDATE OF account_id, status OR DELETE ON cards
+FOR EACH ROW EXECUTE FUNCTION maintain_num_active_cards();
+
+-- Bring existing rows in line, as they may have been written before the trigger existed.
+UPDATE accounts
+SET num_active_cards = COALESCE(active_cards.count, 0)
+FROM accounts AS a
+LEFT JOIN (
+    SELECT account_id, COUNT(*) AS count FROM cards WHERE status = 'active' GROUP BY account_id
+) AS active_cards ON active_cards.account_id = a.id
+WHERE accounts.id = a.id AND accounts.num_active_cards IS DISTINCT FROM COALESCE(active_cards.count, 0);
@@ -0,0 +1,17 @@
+-- Announces new users and accounts on the row_created channel as '<table>:<id>', so caches can
+-- drop negative entries for the id. Postgres only delivers the notification once the insert commits.
+CREATE OR REPLACE FUNCTION notify_row_created()
+RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM pg_notify('row_created', TG_TABLE_NAME || ':' || NEW.id);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE TRIGGER trg_users_row_created
+AFTER INSERT ON users
+FOR EACH ROW EXECUTE FUNCTION notify_row_created();
+
+CREATE OR REPLACE TRIGGER trg_accounts_row_created
+AFTER INSERT ON accounts
+FOR EACH ROW EXECUTE FUNCTION notify_row_created();
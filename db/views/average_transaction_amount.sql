@@ -8,3 +8,6 @@ FROM
 JOIN 
     transactions ON accounts.id = transactions.account_id
 GROUP BY accounts.id;
+
+-- Required for REFRESH MATERIALIZED VIEW CONCURRENTLY.
+CREATE UNIQUE INDEX uidx_average_transaction_amount_account_id ON average_transaction_amount (account_id);
@@ -9,3 +9,6 @@ JOIN
     loans ON users.id = loans.user_id AND loans.status = 'active'
 GROUP BY 
     users.id;
+
+-- Required for REFRESH MATERIALIZED VIEW CONCURRENTLY.
+CREATE UNIQUE INDEX uidx_loans_outstanding_user_id ON loans_outstanding (user_id);
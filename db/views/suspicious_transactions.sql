@@ -28,3 +28,6 @@ JOIN average_transaction_amount ata
 WHERE 
     (tc.amount > (ata.average_transaction * 1.5) OR
     (tc.amount < 10 AND tc.transaction_count_last_10_minutes > 5));
+
+-- Required for REFRESH MATERIALIZED VIEW CONCURRENTLY.
+CREATE UNIQUE INDEX uidx_suspicious_transactions_transaction_id ON suspicious_transactions (transaction_id);
@@ -0,0 +1,5 @@
+pub mod payload;
+pub mod repository;
+pub mod retention;
+pub mod search;
+pub mod triggers;
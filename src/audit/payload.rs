@@ -0,0 +1,131 @@
+use crate::enums::audit_change::AuditChange;
+use crate::enums::audit_log_action::AuditLogAction;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::types::Json;
+use sqlx::{Executor, Postgres, Row};
+use uuid::Uuid;
+
+/// The JSONB `details` of an audit log.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AuditPayload {
+    /// Who made the change.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    /// Shared by the audit logs of one request or job.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<Uuid>,
+    /// The subject row before the change.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<Value>,
+    /// The subject row after the change.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<Value>,
+}
+
+impl AuditPayload {
+    /// Serializes the before and after snapshots of the subject row, which have to match the
+    /// kind of change `action` is: only `after` when created, both when updated and only
+    /// `before` when deleted.
+    pub fn new<T: Serialize>(
+        action: AuditLogAction,
+        before: Option<&T>,
+        after: Option<&T>,
+    ) -> Result<Self, String> {
+        let expected = match action.change() {
+            AuditChange::Created => (false, true),
+            AuditChange::Updated => (true, true),
+            AuditChange::Deleted => (true, false),
+        };
+        if (before.is_some(), after.is_some()) != expected {
+            return Err(format!(
+                "Error: audit payload does not match action - <action={}> - <before={}> - <after={}>",
+                action.to_string(),
+                before.is_some(),
+                after.is_some()
+            ));
+        }
+
+        Ok(Self {
+            actor: None,
+            correlation_id: None,
+            before: before.map(snapshot).transpose()?,
+            after: after.map(snapshot).transpose()?,
+        })
+    }
+}
+
+fn snapshot<T: Serialize>(row: &T) -> Result<Value, String> {
+    serde_json::to_value(row).map_err(|e| {
+        format!(
+            "Error: failed to serialize audit snapshot - <error={:?}>",
+            e
+        )
+    })
+}
+
+/// Writes an audit log for `action` on the subject row `subject_id`, returning its id.
+pub async fn insert_audit_log<'c, E>(
+    executor: E,
+    action: AuditLogAction,
+    subject_id: i32,
+    payload: &AuditPayload,
+    created_at: DateTime<Utc>,
+) -> Result<i32, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    Ok(sqlx::query(
+        "
+        INSERT INTO public.audit_logs
+        (subject_table, subject_id, action, details, created_at)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id;
+        ",
+    )
+    .bind(action.subject_table().to_string())
+    .bind(subject_id)
+    .bind(action.to_string())
+    .bind(Json(payload))
+    .bind(created_at)
+    .fetch_one(executor)
+    .await?
+    .get("id"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_payload_matches_action() {
+        let row = json!({"amount": 10.0});
+
+        let created = AuditPayload::new(AuditLogAction::PaymentCreated, None, Some(&row)).unwrap();
+        assert_eq!(created.before, None);
+        assert_eq!(created.after, Some(row.clone()));
+        assert!(AuditPayload::new(AuditLogAction::PaymentUpdated, Some(&row), Some(&row)).is_ok());
+        assert!(AuditPayload::new(AuditLogAction::CardDeleted, Some(&row), None).is_ok());
+
+        assert!(AuditPayload::new(AuditLogAction::PaymentCreated, Some(&row), Some(&row)).is_err());
+        assert!(AuditPayload::new(AuditLogAction::LoanUpdated, None, Some(&row)).is_err());
+        assert!(AuditPayload::new::<Value>(AuditLogAction::UserDeleted, None, None).is_err());
+    }
+
+    #[test]
+    fn test_payload_leaves_out_missing_fields() {
+        let mut payload =
+            AuditPayload::new(AuditLogAction::UserCreated, None, Some(&json!({"id": 1}))).unwrap();
+        assert_eq!(
+            serde_json::to_value(&payload).unwrap(),
+            json!({"after": {"id": 1}})
+        );
+
+        payload.actor = Some("teller".to_string());
+        let round_trip: AuditPayload =
+            serde_json::from_value(serde_json::to_value(&payload).unwrap()).unwrap();
+        assert_eq!(round_trip, payload);
+    }
+}
@@ -0,0 +1,272 @@
+use crate::enums::audit_log_action::AuditLogAction;
+use crate::enums::audit_log_subject_table::AuditLogSubjectTable;
+use crate::models::audit::AuditLogs;
+use chrono::{DateTime, Utc};
+use sqlx::{Pool, Postgres};
+
+/// Narrows a subject's timeline. Empty `actions` means every action.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimelineFilter {
+    pub actions: Vec<AuditLogAction>,
+    /// Inclusive.
+    pub from: Option<DateTime<Utc>>,
+    /// Exclusive.
+    pub to: Option<DateTime<Utc>>,
+    pub page_size: i64,
+}
+
+impl TimelineFilter {
+    pub fn new() -> Self {
+        Self {
+            actions: Vec::new(),
+            from: None,
+            to: None,
+            page_size: 100,
+        }
+    }
+}
+
+impl Default for TimelineFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Position after the last audit log of a page. Pages continue from the cursor rather than an
+/// offset, so each one is a range scan of the subject index however deep into the timeline it is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimelineCursor {
+    pub timestamp: DateTime<Utc>,
+    pub id: i32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimelinePage {
+    pub logs: Vec<AuditLogs>,
+    /// Where the next page starts, if there is one.
+    pub next: Option<TimelineCursor>,
+}
+
+/// Reads the audit history of single rows.
+pub struct AuditLogRepository {
+    db: Pool<Postgres>,
+}
+
+impl AuditLogRepository {
+    pub fn new(db: Pool<Postgres>) -> Self {
+        Self { db }
+    }
+
+    /// One page of the subject's audit logs matching `filter`, oldest first, starting after
+    /// `cursor`.
+    pub async fn timeline(
+        &self,
+        subject_table: AuditLogSubjectTable,
+        subject_id: i32,
+        filter: &TimelineFilter,
+        cursor: Option<TimelineCursor>,
+    ) -> Result<TimelinePage, sqlx::Error> {
+        let actions: Option<Vec<&str>> = if filter.actions.is_empty() {
+            None
+        } else {
+            Some(
+                filter
+                    .actions
+                    .iter()
+                    .map(|action| action.to_string())
+                    .collect(),
+            )
+        };
+        let page_size = filter.page_size.max(1);
+
+        let mut logs = sqlx::query(
+            "
+            SELECT * FROM public.audit_logs
+            WHERE subject_table = $1
+                AND subject_id = $2
+                AND ($3::TEXT[] IS NULL OR action = ANY($3))
+                AND ($4::TIMESTAMP IS NULL OR created_at >= $4)
+                AND ($5::TIMESTAMP IS NULL OR created_at < $5)
+                AND ($6::TIMESTAMP IS NULL OR (created_at, id) > ($6, $7))
+            ORDER BY created_at, id
+            LIMIT $8;
+            ",
+        )
+        .bind(subject_table.to_string())
+        .bind(subject_id)
+        .bind(actions)
+        .bind(filter.from.map(|from| from.naive_utc()))
+        .bind(filter.to.map(|to| to.naive_utc()))
+        .bind(cursor.map(|cursor| cursor.timestamp.naive_utc()))
+        .bind(cursor.map(|cursor| cursor.id))
+        // One extra row tells whether there is another page.
+        .bind(page_size + 1)
+        .fetch_all(&self.db)
+        .await?
+        .iter()
+        .map(AuditLogs::from_row)
+        .collect::<Result<Vec<_>, _>>()?;
+
+        let next = if logs.len() as i64 > page_size {
+            logs.truncate(page_size as usize);
+            logs.last().map(|log| TimelineCursor {
+                timestamp: log.timestamp,
+                id: log.id,
+            })
+        } else {
+            None
+        };
+
+        Ok(TimelinePage { logs, next })
+    }
+
+    /// Every audit log of the subject matching `filter`, oldest first.
+    pub async fn full_timeline(
+        &self,
+        subject_table: AuditLogSubjectTable,
+        subject_id: i32,
+        filter: &TimelineFilter,
+    ) -> Result<Vec<AuditLogs>, sqlx::Error> {
+        let mut logs = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = self
+                .timeline(subject_table, subject_id, filter, cursor)
+                .await?;
+            logs.extend(page.logs);
+            match page.next {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+
+        Ok(logs)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::audit::payload::{insert_audit_log, AuditPayload};
+    use crate::benchmarking::explain::explain_estimate;
+    use crate::optimisations::BankSystemManager;
+    use chrono::Duration;
+    use serde_json::json;
+    use sqlx::PgPool;
+
+    #[sqlx::test(fixtures("../../db/schema/audit_logs.sql"))]
+    async fn test_it_pages_through_a_subject_timeline(pool: PgPool) -> sqlx::Result<()> {
+        let start = Utc::now() - Duration::days(10);
+        let before = json!({"balance": 10.0});
+        let after = json!({"balance": 20.0});
+        let created =
+            AuditPayload::new(AuditLogAction::AccountCreated, None, Some(&after)).unwrap();
+        let updated =
+            AuditPayload::new(AuditLogAction::AccountUpdated, Some(&before), Some(&after)).unwrap();
+        insert_audit_log(&pool, AuditLogAction::AccountCreated, 1, &created, start).await?;
+        for day in 1..=5 {
+            insert_audit_log(
+                &pool,
+                AuditLogAction::AccountUpdated,
+                1,
+                &updated,
+                start + Duration::days(day),
+            )
+            .await?;
+        }
+        // Same time as the last update, so only the id tells them apart.
+        insert_audit_log(
+            &pool,
+            AuditLogAction::AccountUpdated,
+            1,
+            &updated,
+            start + Duration::days(5),
+        )
+        .await?;
+        insert_audit_log(&pool, AuditLogAction::AccountCreated, 2, &created, start).await?;
+        let repository = AuditLogRepository::new(pool.clone());
+
+        let mut filter = TimelineFilter::new();
+        filter.page_size = 2;
+        let first = repository
+            .timeline(AuditLogSubjectTable::Accounts, 1, &filter, None)
+            .await?;
+        assert_eq!(first.logs.len(), 2);
+        assert_eq!(first.logs[0].action, AuditLogAction::AccountCreated);
+        assert_eq!(first.logs[0].details, created);
+        let second = repository
+            .timeline(AuditLogSubjectTable::Accounts, 1, &filter, first.next)
+            .await?;
+        assert!(second.logs[0].timestamp > first.logs[1].timestamp);
+
+        let timeline = repository
+            .full_timeline(AuditLogSubjectTable::Accounts, 1, &filter)
+            .await?;
+        assert_eq!(timeline.len(), 7);
+        assert!(timeline.iter().all(|log| log.subject_id == 1));
+        assert!(timeline
+            .windows(2)
+            .all(|pair| (pair[0].timestamp, pair[0].id) < (pair[1].timestamp, pair[1].id)));
+
+        filter.actions = vec![AuditLogAction::AccountUpdated];
+        filter.from = Some(start + Duration::days(2));
+        filter.to = Some(start + Duration::days(5));
+        let updates = repository
+            .full_timeline(AuditLogSubjectTable::Accounts, 1, &filter)
+            .await?;
+        assert_eq!(updates.len(), 3);
+        assert!(updates
+            .iter()
+            .all(|log| log.action == AuditLogAction::AccountUpdated));
+
+        let other = repository
+            .full_timeline(AuditLogSubjectTable::Users, 1, &TimelineFilter::new())
+            .await?;
+        assert!(other.is_empty());
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/audit_logs.sql",
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/cards.sql",
+        "../../db/schema/transfers.sql",
+        "../../db/schema/transactions.sql",
+        "../../db/schema/loans.sql",
+        "../../db/schema/payments.sql",
+    ))]
+    async fn test_timelines_use_the_subject_index(pool: PgPool) -> sqlx::Result<()> {
+        BankSystemManager::with_num_users(pool.clone(), 500)
+            .bulk_insert_data()
+            .await?;
+        sqlx::query("ANALYZE public.audit_logs")
+            .execute(&pool)
+            .await?;
+
+        let plan = explain_estimate(
+            &pool,
+            "
+            SELECT * FROM public.audit_logs
+            WHERE subject_table = 'accounts' AND subject_id = 42
+            ORDER BY created_at, id
+            LIMIT 100;
+            ",
+        )
+        .await?;
+
+        assert!(plan
+            .root
+            .nodes()
+            .iter()
+            .any(|node| node.index_name.as_deref() == Some("idx_audit_logs_subject")));
+        let timeline = AuditLogRepository::new(pool.clone())
+            .full_timeline(AuditLogSubjectTable::Accounts, 42, &TimelineFilter::new())
+            .await?;
+        assert_eq!(timeline.len(), 1);
+        assert_eq!(timeline[0].action, AuditLogAction::AccountCreated);
+
+        Ok(())
+    }
+}
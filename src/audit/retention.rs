@@ -0,0 +1,353 @@
+use chrono::NaiveDateTime;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{Pool, Postgres, Row};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Where the retention job puts audit rows it removes from `audit_logs`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArchiveDestination {
+    /// `audit_logs_archive`, written in the same transaction as the delete.
+    Table,
+    /// Gzipped JSON lines appended to this file, one gzip member per batch. Each batch is synced
+    /// to disk before its delete commits, so a failure can repeat rows in the file but never
+    /// lose them.
+    GzipFile(PathBuf),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetentionConfig {
+    /// Rows older than this are moved.
+    pub max_age: Duration,
+    pub destination: ArchiveDestination,
+    /// Rows moved per transaction, keeping each delete's row locks short lived.
+    pub batch_size: i64,
+    /// Pause between batches, to leave room for other writers.
+    pub pause: Duration,
+    /// Vacuum `audit_logs` afterwards so the freed space can be reused.
+    pub vacuum: bool,
+}
+
+impl RetentionConfig {
+    pub fn new(max_age: Duration, destination: ArchiveDestination) -> Self {
+        Self {
+            max_age,
+            destination,
+            batch_size: 1_000,
+            pause: Duration::ZERO,
+            vacuum: true,
+        }
+    }
+}
+
+/// An `audit_logs` row as written to an export file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedAuditLog {
+    pub id: i32,
+    pub subject_table: Option<String>,
+    pub subject_id: Option<i32>,
+    pub action: Option<String>,
+    pub details: Option<Value>,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetentionReport {
+    pub rows_moved: u64,
+    pub batches: usize,
+    /// Size of the moved rows, which becomes free for reuse once the table is vacuumed.
+    pub bytes_moved: i64,
+    pub table_bytes_before: i64,
+    pub table_bytes_after: i64,
+    pub elapsed: Duration,
+}
+
+/// Picks the next batch of expired rows, skipping any another transaction has locked.
+const EXPIRED_BATCH: &str = "
+    SELECT id FROM public.audit_logs
+    WHERE created_at < LOCALTIMESTAMP - $1 * INTERVAL '1 second'
+    ORDER BY id
+    LIMIT $2
+    FOR UPDATE SKIP LOCKED
+";
+
+pub struct RetentionJob {
+    db: Pool<Postgres>,
+    config: RetentionConfig,
+}
+
+impl RetentionJob {
+    pub fn new(db: Pool<Postgres>, config: RetentionConfig) -> Self {
+        Self { db, config }
+    }
+
+    pub async fn run(&self) -> Result<RetentionReport, String> {
+        let start = Instant::now();
+        let table_bytes_before = self.table_bytes().await?;
+
+        let mut rows_moved = 0;
+        let mut bytes_moved = 0;
+        let mut batches = 0;
+        loop {
+            let (rows, bytes) = match &self.config.destination {
+                ArchiveDestination::Table => self.move_batch_to_table().await,
+                ArchiveDestination::GzipFile(path) => self.move_batch_to_file(path).await,
+            }?;
+            if rows == 0 {
+                break;
+            }
+            rows_moved += rows;
+            bytes_moved += bytes;
+            batches += 1;
+
+            if !self.config.pause.is_zero() {
+                tokio::time::sleep(self.config.pause).await;
+            }
+        }
+
+        if self.config.vacuum {
+            sqlx::raw_sql("VACUUM (ANALYZE) public.audit_logs")
+                .execute(&self.db)
+                .await
+                .map_err(|e| format!("Error: failed to vacuum audit_logs - <error={:?}>", e))?;
+        }
+
+        Ok(RetentionReport {
+            rows_moved,
+            batches,
+            bytes_moved,
+            table_bytes_before,
+            table_bytes_after: self.table_bytes().await?,
+            elapsed: start.elapsed(),
+        })
+    }
+
+    async fn move_batch_to_table(&self) -> Result<(u64, i64), String> {
+        let row = sqlx::query(&format!(
+            "
+            WITH batch AS ({batch}),
+            moved AS (
+                DELETE FROM public.audit_logs a
+                USING batch
+                WHERE a.id = batch.id
+                RETURNING a.*, pg_column_size(a.*) AS size
+            ),
+            archived AS (
+                INSERT INTO public.audit_logs_archive
+                (id, subject_table, subject_id, action, details, created_at)
+                SELECT id, subject_table, subject_id, action, details, created_at FROM moved
+            )
+            SELECT COUNT(*) AS rows, COALESCE(SUM(size), 0)::BIGINT AS bytes FROM moved;
+            ",
+            batch = EXPIRED_BATCH
+        ))
+        .bind(self.config.max_age.as_secs_f64())
+        .bind(self.config.batch_size)
+        .fetch_one(&self.db)
+        .await
+        .map_err(|e| {
+            format!(
+                "Error: failed to move audit logs to archive table - <error={:?}>",
+                e
+            )
+        })?;
+
+        Ok((row.get::<i64, _>("rows") as u64, row.get("bytes")))
+    }
+
+    async fn move_batch_to_file(&self, path: &PathBuf) -> Result<(u64, i64), String> {
+        let mut tx = self
+            .db
+            .begin()
+            .await
+            .map_err(|e| format!("Error: failed to begin retention batch - <error={:?}>", e))?;
+        let rows = sqlx::query(&format!(
+            "
+            WITH batch AS ({batch})
+            DELETE FROM public.audit_logs a
+            USING batch
+            WHERE a.id = batch.id
+            RETURNING a.id, a.subject_table, a.subject_id, a.action, a.details, a.created_at,
+                pg_column_size(a.*) AS size;
+            ",
+            batch = EXPIRED_BATCH
+        ))
+        .bind(self.config.max_age.as_secs_f64())
+        .bind(self.config.batch_size)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| {
+            format!(
+                "Error: failed to delete expired audit logs - <error={:?}>",
+                e
+            )
+        })?;
+        if rows.is_empty() {
+            return Ok((0, 0));
+        }
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        let mut bytes = 0;
+        for row in &rows {
+            bytes += row.get::<i32, _>("size") as i64;
+            let log = ArchivedAuditLog {
+                id: row.get("id"),
+                subject_table: row.get("subject_table"),
+                subject_id: row.get("subject_id"),
+                action: row.get("action"),
+                details: row.get("details"),
+                created_at: row.get("created_at"),
+            };
+            serde_json::to_writer(&mut encoder, &log)
+                .map_err(|e| e.to_string())
+                .and_then(|_| encoder.write_all(b"\n").map_err(|e| e.to_string()))
+                .map_err(|e| {
+                    format!(
+                        "Error: failed to encode archived audit log - <id={}> - <error={}>",
+                        log.id, e
+                    )
+                })?;
+        }
+        let compressed = encoder.finish().map_err(|e| {
+            format!(
+                "Error: failed to compress audit log batch - <error={:?}>",
+                e
+            )
+        })?;
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| {
+                file.write_all(&compressed)?;
+                file.sync_all()
+            })
+            .map_err(|e| {
+                format!(
+                    "Error: failed to write audit log archive - <path={}> - <error={:?}>",
+                    path.display(),
+                    e
+                )
+            })?;
+
+        tx.commit()
+            .await
+            .map_err(|e| format!("Error: failed to commit retention batch - <error={:?}>", e))?;
+
+        Ok((rows.len() as u64, bytes))
+    }
+
+    async fn table_bytes(&self) -> Result<i64, String> {
+        Ok(
+            sqlx::query("SELECT pg_total_relation_size('public.audit_logs') AS size")
+                .fetch_one(&self.db)
+                .await
+                .map_err(|e| format!("Error: failed to size audit_logs - <error={:?}>", e))?
+                .get("size"),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use flate2::read::MultiGzDecoder;
+    use sqlx::PgPool;
+    use std::fs;
+    use std::io::{BufRead, BufReader};
+    use uuid::Uuid;
+
+    async fn insert_audit_logs(pool: &PgPool) -> sqlx::Result<()> {
+        sqlx::raw_sql(
+            "
+            INSERT INTO public.audit_logs (subject_table, subject_id, action, details, created_at)
+            SELECT 'users', n, 'user created', jsonb_build_object('after', jsonb_build_object('id', n)),
+                LOCALTIMESTAMP - CASE WHEN n <= 5 THEN INTERVAL '2 days' ELSE INTERVAL '1 hour' END
+            FROM generate_series(1, 8) AS n;
+            ",
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn count(pool: &PgPool, table: &str) -> sqlx::Result<i64> {
+        Ok(
+            sqlx::query(&format!("SELECT COUNT(*) AS count FROM public.{}", table))
+                .fetch_one(pool)
+                .await?
+                .get("count"),
+        )
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/audit_logs.sql",
+        "../../db/schema/audit_logs_archive.sql"
+    ))]
+    async fn test_it_moves_expired_rows_to_the_archive_table(pool: PgPool) -> sqlx::Result<()> {
+        insert_audit_logs(&pool).await?;
+        let mut config =
+            RetentionConfig::new(Duration::from_secs(24 * 60 * 60), ArchiveDestination::Table);
+        config.batch_size = 2;
+        config.pause = Duration::from_millis(10);
+
+        let report = RetentionJob::new(pool.clone(), config).run().await.unwrap();
+
+        assert_eq!(report.rows_moved, 5);
+        assert_eq!(report.batches, 3);
+        assert!(report.bytes_moved > 0);
+        assert_eq!(count(&pool, "audit_logs").await?, 3);
+        assert_eq!(count(&pool, "audit_logs_archive").await?, 5);
+        let archived_ids: Vec<i32> =
+            sqlx::query("SELECT id FROM public.audit_logs_archive ORDER BY id")
+                .fetch_all(&pool)
+                .await?
+                .iter()
+                .map(|row| row.get("id"))
+                .collect();
+        assert_eq!(archived_ids, vec![1, 2, 3, 4, 5]);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("../../db/schema/audit_logs.sql"))]
+    async fn test_it_appends_expired_rows_to_a_gzip_file(pool: PgPool) -> sqlx::Result<()> {
+        let path = std::env::temp_dir().join(format!("audit-archive-{}.jsonl.gz", Uuid::new_v4()));
+        let config = RetentionConfig::new(
+            Duration::from_secs(24 * 60 * 60),
+            ArchiveDestination::GzipFile(path.clone()),
+        );
+        let job = RetentionJob::new(pool.clone(), config);
+
+        insert_audit_logs(&pool).await?;
+        let first = job.run().await;
+        insert_audit_logs(&pool).await?;
+        let second = job.run().await;
+        let lines: Vec<ArchivedAuditLog> = fs::File::open(&path)
+            .map(|file| {
+                BufReader::new(MultiGzDecoder::new(file))
+                    .lines()
+                    .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(first.unwrap().rows_moved, 5);
+        assert_eq!(second.unwrap().rows_moved, 5);
+        assert_eq!(lines.len(), 10);
+        assert_eq!(lines[0].action.as_deref(), Some("user created"));
+        assert_eq!(
+            lines[0].details,
+            Some(serde_json::json!({"after": {"id": 1}}))
+        );
+        assert_eq!(count(&pool, "audit_logs").await?, 6);
+
+        Ok(())
+    }
+}
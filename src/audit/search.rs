@@ -0,0 +1,126 @@
+use crate::models::audit::AuditLogs;
+use serde_json::{json, Value};
+use sqlx::types::Json;
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+
+/// Audit logs whose payload contains `filter`, oldest first. Containment is what the
+/// `jsonb_path_ops` GIN index on `details` can answer.
+pub async fn find_by_payload(
+    db: &Pool<Postgres>,
+    filter: &Value,
+) -> Result<Vec<AuditLogs>, sqlx::Error> {
+    sqlx::query(
+        "
+        SELECT * FROM public.audit_logs
+        WHERE details @> $1
+        ORDER BY created_at, id;
+        ",
+    )
+    .bind(Json(filter))
+    .fetch_all(db)
+    .await?
+    .iter()
+    .map(AuditLogs::from_row)
+    .collect()
+}
+
+pub async fn find_by_actor(
+    db: &Pool<Postgres>,
+    actor: &str,
+) -> Result<Vec<AuditLogs>, sqlx::Error> {
+    find_by_payload(db, &json!({ "actor": actor })).await
+}
+
+pub async fn find_by_correlation_id(
+    db: &Pool<Postgres>,
+    correlation_id: Uuid,
+) -> Result<Vec<AuditLogs>, sqlx::Error> {
+    find_by_payload(db, &json!({ "correlation_id": correlation_id })).await
+}
+
+/// Audit logs where `field` of the subject row had `value`, either before or after the change.
+pub async fn find_by_field(
+    db: &Pool<Postgres>,
+    field: &str,
+    value: Value,
+) -> Result<Vec<AuditLogs>, sqlx::Error> {
+    sqlx::query(
+        "
+        SELECT * FROM public.audit_logs
+        WHERE details @> jsonb_build_object('before', jsonb_build_object($1::TEXT, $2::JSONB))
+            OR details @> jsonb_build_object('after', jsonb_build_object($1::TEXT, $2::JSONB))
+        ORDER BY created_at, id;
+        ",
+    )
+    .bind(field)
+    .bind(Json(value))
+    .fetch_all(db)
+    .await?
+    .iter()
+    .map(AuditLogs::from_row)
+    .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::audit::payload::{insert_audit_log, AuditPayload};
+    use crate::enums::audit_log_action::AuditLogAction;
+    use crate::enums::audit_log_subject_table::AuditLogSubjectTable;
+    use chrono::Utc;
+    use sqlx::PgPool;
+
+    #[sqlx::test(fixtures("../../db/schema/audit_logs.sql"))]
+    async fn test_it_finds_audit_logs_by_payload_fields(pool: PgPool) -> sqlx::Result<()> {
+        let correlation_id = Uuid::new_v4();
+        let before = json!({"loan_id": 1, "status": "pending"});
+        let after = json!({"loan_id": 1, "status": "completed"});
+        let mut created =
+            AuditPayload::new(AuditLogAction::PaymentCreated, None, Some(&before)).unwrap();
+        created.actor = Some("teller".to_string());
+        created.correlation_id = Some(correlation_id);
+        let mut updated =
+            AuditPayload::new(AuditLogAction::PaymentUpdated, Some(&before), Some(&after)).unwrap();
+        updated.actor = Some("settlement".to_string());
+        updated.correlation_id = Some(correlation_id);
+        let other = AuditPayload::new(
+            AuditLogAction::PaymentCreated,
+            None,
+            Some(&json!({"loan_id": 2, "status": "pending"})),
+        )
+        .unwrap();
+        let now = Utc::now();
+        let created_id =
+            insert_audit_log(&pool, AuditLogAction::PaymentCreated, 7, &created, now).await?;
+        let updated_id =
+            insert_audit_log(&pool, AuditLogAction::PaymentUpdated, 7, &updated, now).await?;
+        insert_audit_log(&pool, AuditLogAction::PaymentCreated, 8, &other, now).await?;
+
+        let ids = |logs: Vec<AuditLogs>| logs.iter().map(|log| log.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(find_by_field(&pool, "loan_id", json!(1)).await?),
+            vec![created_id, updated_id]
+        );
+        assert_eq!(
+            ids(find_by_field(&pool, "status", json!("completed")).await?),
+            vec![updated_id]
+        );
+        assert_eq!(
+            ids(find_by_correlation_id(&pool, correlation_id).await?),
+            vec![created_id, updated_id]
+        );
+        let by_actor = find_by_actor(&pool, "settlement").await?;
+        assert_eq!(by_actor.len(), 1);
+        assert_eq!(by_actor[0].subject_table, AuditLogSubjectTable::Payments);
+        assert_eq!(by_actor[0].subject_id, 7);
+        assert_eq!(by_actor[0].action, AuditLogAction::PaymentUpdated);
+        assert_eq!(by_actor[0].details, updated);
+        assert_eq!(
+            ids(find_by_payload(&pool, &json!({"after": {"status": "pending"}})).await?).len(),
+            2
+        );
+
+        Ok(())
+    }
+}
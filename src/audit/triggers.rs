@@ -0,0 +1,493 @@
+use crate::audit::payload::{insert_audit_log, AuditPayload};
+use crate::enums::audit_log_action::AuditLogAction;
+use crate::enums::audit_log_subject_table::AuditLogSubjectTable;
+use chrono::Utc;
+use serde_json::Value;
+use sqlx::{Executor, PgConnection, Pool, Postgres, Row};
+use std::time::Instant;
+use uuid::Uuid;
+
+const AUDIT_TRIGGERS_SQL: &str = include_str!("../../db/triggers/audit_logs.sql");
+const EXPERIMENT_ACTOR: &str = "audit trigger experiment";
+
+pub async fn is_installed<'c, E>(executor: E) -> Result<bool, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    Ok(sqlx::query(
+        "
+        SELECT 1 FROM pg_proc p
+        JOIN pg_namespace n ON n.oid = p.pronamespace
+        WHERE n.nspname = 'public' AND p.proname = 'write_audit_log';
+        ",
+    )
+    .fetch_optional(executor)
+    .await?
+    .is_some())
+}
+
+/// Installs the triggers that audit every write to the audited tables. The generator stops
+/// writing its own audit logs once they are installed, but any other writer that calls
+/// `insert_audit_log` itself would log each change twice.
+pub async fn install_triggers<'c, E>(executor: E) -> Result<(), sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::raw_sql(AUDIT_TRIGGERS_SQL).execute(executor).await?;
+    Ok(())
+}
+
+pub async fn uninstall_triggers<'c, E>(executor: E) -> Result<(), sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::raw_sql(
+        "
+        DROP TRIGGER IF EXISTS trg_users_audit ON public.users;
+        DROP TRIGGER IF EXISTS trg_accounts_audit ON public.accounts;
+        DROP TRIGGER IF EXISTS trg_cards_audit ON public.cards;
+        DROP TRIGGER IF EXISTS trg_transfers_audit ON public.transfers;
+        DROP TRIGGER IF EXISTS trg_transactions_audit ON public.transactions;
+        DROP TRIGGER IF EXISTS trg_loans_audit ON public.loans;
+        DROP TRIGGER IF EXISTS trg_payments_audit ON public.payments;
+        DROP FUNCTION IF EXISTS write_audit_log();
+        ",
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Sets the actor and correlation id the triggers put in audit payloads, for the rest of the
+/// current transaction.
+pub async fn set_audit_context(
+    conn: &mut PgConnection,
+    actor: &str,
+    correlation_id: Option<Uuid>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "SELECT set_config('audit.actor', $1, true), set_config('audit.correlation_id', $2, true)",
+    )
+    .bind(actor)
+    .bind(correlation_id.map(|id| id.to_string()).unwrap_or_default())
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Auditing {
+    None,
+    Application,
+    Trigger,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditTriggerReport {
+    pub table: AuditLogSubjectTable,
+    pub rows: usize,
+    pub inserts_per_sec_unaudited: f64,
+    /// Each insert followed by an `insert_audit_log` call, as the generator does.
+    pub inserts_per_sec_application: f64,
+    pub inserts_per_sec_trigger: f64,
+    /// Audit logs written by each approach, which should both equal `rows`.
+    pub audit_logs_application: i64,
+    pub audit_logs_trigger: i64,
+}
+
+/// Compares single row insert throughput with no auditing, application level auditing and the
+/// audit triggers, by copying existing rows of each table.
+///
+/// Every measurement runs in a rolled back transaction that also installs or drops the triggers
+/// as needed, so neither the tables nor the installed triggers change. Installing or dropping
+/// them locks all seven audited tables until that transaction ends, blocking every other writer
+/// and reader, so only run this against an idle database.
+pub struct AuditTriggerExperiment {
+    db: Pool<Postgres>,
+    rows: usize,
+}
+
+impl AuditTriggerExperiment {
+    pub fn new(db: Pool<Postgres>, rows: usize) -> Self {
+        Self { db, rows }
+    }
+
+    /// Measures each table that has rows to copy.
+    pub async fn run(
+        &self,
+        tables: &[AuditLogSubjectTable],
+    ) -> Result<Vec<AuditTriggerReport>, sqlx::Error> {
+        let installed = is_installed(&self.db).await?;
+        let mut reports = Vec::with_capacity(tables.len());
+        for table in tables {
+            let source_ids: Vec<i32> = sqlx::query(&format!(
+                "SELECT id FROM public.{} ORDER BY id LIMIT $1",
+                table.to_string()
+            ))
+            .bind(self.rows as i64)
+            .fetch_all(&self.db)
+            .await?
+            .iter()
+            .map(|row| row.get("id"))
+            .collect();
+            if source_ids.is_empty() {
+                continue;
+            }
+
+            let insert = self.copy_row_sql(*table).await?;
+            let (inserts_per_sec_unaudited, _) = self
+                .time_inserts(*table, &insert, &source_ids, Auditing::None, installed)
+                .await?;
+            let (inserts_per_sec_application, audit_logs_application) = self
+                .time_inserts(
+                    *table,
+                    &insert,
+                    &source_ids,
+                    Auditing::Application,
+                    installed,
+                )
+                .await?;
+            let (inserts_per_sec_trigger, audit_logs_trigger) = self
+                .time_inserts(*table, &insert, &source_ids, Auditing::Trigger, installed)
+                .await?;
+
+            reports.push(AuditTriggerReport {
+                table: *table,
+                rows: self.rows,
+                inserts_per_sec_unaudited,
+                inserts_per_sec_application,
+                inserts_per_sec_trigger,
+                audit_logs_application,
+                audit_logs_trigger,
+            });
+        }
+
+        Ok(reports)
+    }
+
+    /// Inserts a copy of the row with id `$1`, returning the copy's id and its audit snapshot.
+    /// Unique columns get fresh values so copies do not collide.
+    async fn copy_row_sql(&self, table: AuditLogSubjectTable) -> Result<String, sqlx::Error> {
+        let columns: Vec<String> = sqlx::query(
+            "
+            SELECT column_name
+            FROM information_schema.columns
+            WHERE table_schema = 'public' AND table_name = $1 AND column_name <> 'id'
+            ORDER BY ordinal_position;
+            ",
+        )
+        .bind(table.to_string())
+        .fetch_all(&self.db)
+        .await?
+        .iter()
+        .map(|row| row.get("column_name"))
+        .collect();
+        let values: Vec<&str> = columns
+            .iter()
+            .map(|column| match (table, column.as_str()) {
+                (AuditLogSubjectTable::Users, "public_id") => "gen_random_uuid()",
+                (AuditLogSubjectTable::Users, "phone")
+                | (AuditLogSubjectTable::Cards, "card_number") => "left(md5(random()::TEXT), 16)",
+                _ => column.as_str(),
+            })
+            .collect();
+
+        Ok(format!(
+            "
+            INSERT INTO public.{table} AS inserted ({columns})
+            SELECT {values} FROM public.{table} WHERE id = $1
+            RETURNING inserted.id, to_jsonb(inserted) - 'id' - 'card_number' AS after;
+            ",
+            table = table.to_string(),
+            columns = columns.join(", "),
+            values = values.join(", ")
+        ))
+    }
+
+    /// Returns inserts per second and how many audit logs the inserts wrote.
+    async fn time_inserts(
+        &self,
+        table: AuditLogSubjectTable,
+        insert: &str,
+        source_ids: &[i32],
+        auditing: Auditing,
+        installed: bool,
+    ) -> Result<(f64, i64), sqlx::Error> {
+        let mut tx = self.db.begin().await?;
+        match auditing {
+            Auditing::None | Auditing::Application if installed => {
+                uninstall_triggers(&mut *tx).await?
+            }
+            Auditing::Trigger if !installed => install_triggers(&mut *tx).await?,
+            _ => {}
+        }
+        set_audit_context(&mut tx, EXPERIMENT_ACTOR, None).await?;
+        let audit_logs_before = count_audit_logs(&mut tx).await?;
+        let action = created_action(table);
+
+        let start = Instant::now();
+        for i in 0..self.rows {
+            let row = sqlx::query(insert)
+                .bind(source_ids[i % source_ids.len()])
+                .fetch_one(&mut *tx)
+                .await?;
+            if auditing == Auditing::Application {
+                let after: Value = row.get("after");
+                let mut payload =
+                    AuditPayload::new(action, None, Some(&after)).map_err(sqlx::Error::Protocol)?;
+                payload.actor = Some(EXPERIMENT_ACTOR.to_string());
+                insert_audit_log(&mut *tx, action, row.get("id"), &payload, Utc::now()).await?;
+            }
+        }
+        let elapsed = start.elapsed();
+
+        let audit_logs = count_audit_logs(&mut tx).await? - audit_logs_before;
+        tx.rollback().await?;
+
+        Ok((self.rows as f64 / elapsed.as_secs_f64(), audit_logs))
+    }
+}
+
+fn created_action(table: AuditLogSubjectTable) -> AuditLogAction {
+    match table {
+        AuditLogSubjectTable::Users => AuditLogAction::UserCreated,
+        AuditLogSubjectTable::Accounts => AuditLogAction::AccountCreated,
+        AuditLogSubjectTable::Cards => AuditLogAction::CardCreated,
+        AuditLogSubjectTable::Transfers => AuditLogAction::TransferCreated,
+        AuditLogSubjectTable::Transactions => AuditLogAction::TransactionCreated,
+        AuditLogSubjectTable::Loans => AuditLogAction::LoanCreated,
+        AuditLogSubjectTable::Payments => AuditLogAction::PaymentCreated,
+    }
+}
+
+async fn count_audit_logs(conn: &mut PgConnection) -> Result<i64, sqlx::Error> {
+    Ok(
+        sqlx::query("SELECT COUNT(*) AS count FROM public.audit_logs")
+            .fetch_one(conn)
+            .await?
+            .get("count"),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::audit::repository::{AuditLogRepository, TimelineFilter};
+    use crate::optimisations::BankSystemManager;
+    use sqlx::PgPool;
+
+    async fn assert_one_audit_log_per_row(pool: &PgPool) -> sqlx::Result<()> {
+        for table in AuditLogSubjectTable::all() {
+            let row = sqlx::query(&format!(
+                "
+                SELECT
+                    (SELECT COUNT(*) FROM public.{table}) AS rows,
+                    (SELECT COUNT(*) FROM public.audit_logs WHERE subject_table = '{table}') AS audit_logs;
+                ",
+                table = table.to_string()
+            ))
+            .fetch_one(pool)
+            .await?;
+            let rows: i64 = row.get("rows");
+            assert!(rows > 0);
+            assert_eq!(
+                row.get::<i64, _>("audit_logs"),
+                rows,
+                "{}",
+                table.to_string()
+            );
+        }
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/audit_logs.sql",
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/cards.sql",
+        "../../db/schema/transfers.sql",
+        "../../db/schema/transactions.sql",
+        "../../db/schema/loans.sql",
+        "../../db/schema/payments.sql",
+    ))]
+    async fn test_generator_does_not_duplicate_trigger_audit_logs(
+        pool: PgPool,
+    ) -> sqlx::Result<()> {
+        install_triggers(&pool).await?;
+
+        BankSystemManager::with_num_users(pool.clone(), 5)
+            .insert_data()
+            .await;
+
+        assert_one_audit_log_per_row(&pool).await
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/audit_logs.sql",
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/cards.sql",
+        "../../db/schema/transfers.sql",
+        "../../db/schema/transactions.sql",
+        "../../db/schema/loans.sql",
+        "../../db/schema/payments.sql",
+    ))]
+    async fn test_bulk_seeding_does_not_duplicate_trigger_audit_logs(
+        pool: PgPool,
+    ) -> sqlx::Result<()> {
+        install_triggers(&pool).await?;
+
+        BankSystemManager::with_num_users(pool.clone(), 5)
+            .bulk_insert_data()
+            .await?;
+
+        assert_one_audit_log_per_row(&pool).await
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/audit_logs.sql",
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/cards.sql",
+        "../../db/schema/transfers.sql",
+        "../../db/schema/transactions.sql",
+        "../../db/schema/loans.sql",
+        "../../db/schema/payments.sql",
+    ))]
+    async fn test_triggers_audit_every_write_path(pool: PgPool) -> sqlx::Result<()> {
+        install_triggers(&pool).await?;
+        assert!(is_installed(&pool).await?);
+        let correlation_id = Uuid::new_v4();
+        let mut tx = pool.begin().await?;
+        set_audit_context(&mut tx, "teller", Some(correlation_id)).await?;
+        sqlx::raw_sql(
+            "
+            INSERT INTO public.users (given_name, family_name, username, email, phone)
+            VALUES ('a', 'b', 'ab', 'ab@example.com', '1');
+            INSERT INTO public.accounts (user_id, account_type) VALUES (1, 'checking');
+            INSERT INTO public.cards (account_id, card_number, card_type, expiration_date, status)
+            VALUES (1, '0000000000000001', 'debit', NOW(), 'active');
+            UPDATE public.cards SET status = 'blocked';
+            UPDATE public.cards SET status = 'blocked';
+            DELETE FROM public.cards;
+            INSERT INTO public.transactions (account_id, transaction_type, amount, status)
+            VALUES (1, 'deposit', 10.00, 'completed');
+            UPDATE public.transactions SET amount = 20.00;
+            DELETE FROM public.transactions;
+            ",
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        let repository = AuditLogRepository::new(pool.clone());
+        let actions = |logs: &[crate::models::audit::AuditLogs]| {
+            logs.iter().map(|log| log.action).collect::<Vec<_>>()
+        };
+
+        let cards = repository
+            .full_timeline(AuditLogSubjectTable::Cards, 1, &TimelineFilter::new())
+            .await?;
+        // The second update changed nothing, so it is not audited.
+        assert_eq!(
+            actions(&cards),
+            vec![
+                AuditLogAction::CardCreated,
+                AuditLogAction::CardUpdated,
+                AuditLogAction::CardDeleted
+            ]
+        );
+        assert_eq!(cards[0].details.actor.as_deref(), Some("teller"));
+        assert_eq!(cards[0].details.correlation_id, Some(correlation_id));
+        assert_eq!(cards[0].details.after.as_ref().unwrap()["status"], "active");
+        assert!(cards[0].details.after.as_ref().unwrap()["card_number"].is_null());
+        assert_eq!(
+            cards[1].details.before.as_ref().unwrap()["status"],
+            "active"
+        );
+        assert_eq!(
+            cards[1].details.after.as_ref().unwrap()["status"],
+            "blocked"
+        );
+        assert_eq!(cards[2].details.after, None);
+        // Transactions have no 'updated' action, so the update is not audited.
+        let transactions = repository
+            .full_timeline(
+                AuditLogSubjectTable::Transactions,
+                1,
+                &TimelineFilter::new(),
+            )
+            .await?;
+        assert_eq!(
+            actions(&transactions),
+            vec![
+                AuditLogAction::TransactionCreated,
+                AuditLogAction::TransactionDeleted
+            ]
+        );
+        let users = repository
+            .full_timeline(AuditLogSubjectTable::Users, 1, &TimelineFilter::new())
+            .await?;
+        assert_eq!(actions(&users), vec![AuditLogAction::UserCreated]);
+
+        uninstall_triggers(&pool).await?;
+        assert!(!is_installed(&pool).await?);
+        sqlx::query("UPDATE public.users SET given_name = 'c'")
+            .execute(&pool)
+            .await?;
+        let users = repository
+            .full_timeline(AuditLogSubjectTable::Users, 1, &TimelineFilter::new())
+            .await?;
+        assert_eq!(users.len(), 1);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/audit_logs.sql",
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/cards.sql",
+        "../../db/schema/transfers.sql",
+        "../../db/schema/transactions.sql",
+        "../../db/schema/loans.sql",
+        "../../db/schema/payments.sql",
+    ))]
+    async fn test_it_compares_trigger_and_application_auditing(pool: PgPool) -> sqlx::Result<()> {
+        BankSystemManager::with_num_users(pool.clone(), 50)
+            .bulk_insert_data()
+            .await?;
+        let audit_logs: i64 = sqlx::query("SELECT COUNT(*) AS count FROM public.audit_logs")
+            .fetch_one(&pool)
+            .await?
+            .get("count");
+        let experiment = AuditTriggerExperiment::new(pool.clone(), 50);
+
+        let reports = experiment.run(&AuditLogSubjectTable::all()).await?;
+
+        assert_eq!(reports.len(), 7);
+        for report in reports {
+            assert_eq!(report.audit_logs_application, 50);
+            assert_eq!(report.audit_logs_trigger, 50);
+            println!(
+                "{} - inserts/sec {:.0} unaudited, {:.0} application, {:.0} trigger",
+                report.table.to_string(),
+                report.inserts_per_sec_unaudited,
+                report.inserts_per_sec_application,
+                report.inserts_per_sec_trigger
+            );
+        }
+        assert!(!is_installed(&pool).await?);
+        let audit_logs_after: i64 = sqlx::query("SELECT COUNT(*) AS count FROM public.audit_logs")
+            .fetch_one(&pool)
+            .await?
+            .get("count");
+        assert_eq!(audit_logs_after, audit_logs);
+
+        install_triggers(&pool).await?;
+        let reports = experiment.run(&[AuditLogSubjectTable::Payments]).await?;
+        assert_eq!(reports[0].audit_logs_application, 50);
+        assert!(is_installed(&pool).await?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,449 @@
+use super::explain::{PlanNode, QueryPlan};
+use super::BenchmarkResult;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_REGRESSION_THRESHOLD_PCT: f64 = 20.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BaselineEntry {
+    pub query_id: String,
+    /// Number of users the database was seeded with.
+    pub dataset_size: usize,
+    /// Seed the data generator was run with.
+    pub seed: u64,
+    pub iterations: usize,
+    pub min_ms: f64,
+    pub median_ms: f64,
+    pub mean_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    /// Hash of the plan's node labels, ignoring costs and timings. `None` if no plan was captured.
+    pub plan_hash: Option<String>,
+    pub recorded_at: DateTime<Utc>,
+}
+
+impl BaselineEntry {
+    pub fn from_result(result: &BenchmarkResult, dataset_size: usize, seed: u64) -> Self {
+        let ms = |duration: std::time::Duration| duration.as_secs_f64() * 1_000.0;
+        Self {
+            query_id: result.name.clone(),
+            dataset_size,
+            seed,
+            iterations: result.stats.iterations,
+            min_ms: ms(result.stats.min),
+            median_ms: ms(result.stats.median),
+            mean_ms: ms(result.stats.mean),
+            p95_ms: ms(result.stats.p95),
+            p99_ms: ms(result.stats.p99),
+            plan_hash: result.plan.as_ref().map(plan_hash),
+            recorded_at: Utc::now(),
+        }
+    }
+}
+
+/// FNV-1a over the plan's node labels and depths, so only a change in plan shape alters it.
+pub fn plan_hash(plan: &QueryPlan) -> String {
+    let mut shape = String::new();
+    write_shape(&plan.root, 0, &mut shape);
+
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in shape.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+fn write_shape(node: &PlanNode, depth: usize, shape: &mut String) {
+    shape.push_str(&format!("{}:{}\n", depth, node.label()));
+    for child in &node.children {
+        write_shape(child, depth + 1, shape);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Regression {
+    Median {
+        query_id: String,
+        baseline_ms: f64,
+        current_ms: f64,
+        change_pct: f64,
+    },
+    PlanShape {
+        query_id: String,
+        baseline_hash: String,
+        current_hash: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub threshold_pct: f64,
+    pub compared: usize,
+    pub regressions: Vec<Regression>,
+    /// Results with no baseline for their query, dataset size and seed.
+    pub missing: Vec<String>,
+}
+
+impl Comparison {
+    pub fn passed(&self) -> bool {
+        self.regressions.is_empty()
+    }
+
+    /// `Err` with the full report if anything regressed.
+    pub fn check(self) -> Result<Self, String> {
+        if self.passed() {
+            Ok(self)
+        } else {
+            Err(format!("Error: performance regression detected\n{}", self))
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} compared, {} regressed (threshold {:.1}%), {} without baseline",
+            self.compared,
+            self.regressions.len(),
+            self.threshold_pct,
+            self.missing.len()
+        )?;
+        for regression in &self.regressions {
+            match regression {
+                Regression::Median {
+                    query_id,
+                    baseline_ms,
+                    current_ms,
+                    change_pct,
+                } => writeln!(
+                    f,
+                    "  {} - median {:.3} ms -> {:.3} ms ({:+.1}%)",
+                    query_id, baseline_ms, current_ms, change_pct
+                )?,
+                Regression::PlanShape {
+                    query_id,
+                    baseline_hash,
+                    current_hash,
+                } => writeln!(
+                    f,
+                    "  {} - plan shape changed {} -> {}",
+                    query_id, baseline_hash, current_hash
+                )?,
+            }
+        }
+        for query_id in &self.missing {
+            writeln!(f, "  {} - no baseline", query_id)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Baselines keyed by query id, dataset size and seed, kept in a JSON file. Timings from data
+/// generated with different seeds are not comparable, so each seed keeps its own baselines.
+pub struct BaselineStore {
+    path: PathBuf,
+    pub entries: Vec<BaselineEntry>,
+}
+
+impl BaselineStore {
+    /// Reads the store at `path`, starting empty if the file does not exist yet.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        if !path.exists() {
+            return Ok(Self {
+                path: path.to_path_buf(),
+                entries: Vec::new(),
+            });
+        }
+        let json = fs::read_to_string(path).map_err(|e| {
+            format!(
+                "Error: failed to read baseline store - <path={}> - <error={:?}>",
+                path.display(),
+                e
+            )
+        })?;
+        let entries = serde_json::from_str(&json).map_err(|e| {
+            format!(
+                "Error: failed to parse baseline store - <path={}> - <error={:?}>",
+                path.display(),
+                e
+            )
+        })?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            entries,
+        })
+    }
+
+    pub fn get(&self, query_id: &str, dataset_size: usize, seed: u64) -> Option<&BaselineEntry> {
+        self.entries.iter().find(|entry| {
+            entry.query_id == query_id && entry.dataset_size == dataset_size && entry.seed == seed
+        })
+    }
+
+    /// Adds `entry`, replacing any baseline for the same query, dataset size and seed.
+    pub fn record(&mut self, entry: BaselineEntry) {
+        self.entries.retain(|existing| {
+            existing.query_id != entry.query_id
+                || existing.dataset_size != entry.dataset_size
+                || existing.seed != entry.seed
+        });
+        self.entries.push(entry);
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(&self.entries).map_err(|e| {
+            format!(
+                "Error: failed to serialize baseline store - <error={:?}>",
+                e
+            )
+        })?;
+
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, json)
+            .and_then(|_| fs::rename(&tmp_path, &self.path))
+            .map_err(|e| {
+                format!(
+                    "Error: failed to write baseline store - <path={}> - <error={:?}>",
+                    self.path.display(),
+                    e
+                )
+            })
+    }
+
+    /// Flags medians more than `threshold_pct` slower than their baseline, and changed plans.
+    pub fn compare(&self, current: &[BaselineEntry], threshold_pct: f64) -> Comparison {
+        let mut comparison = Comparison {
+            threshold_pct,
+            compared: 0,
+            regressions: Vec::new(),
+            missing: Vec::new(),
+        };
+
+        for entry in current {
+            let Some(baseline) = self.get(&entry.query_id, entry.dataset_size, entry.seed) else {
+                comparison.missing.push(entry.query_id.clone());
+                continue;
+            };
+            comparison.compared += 1;
+
+            if baseline.median_ms > 0.0 {
+                let change_pct =
+                    ((entry.median_ms - baseline.median_ms) / baseline.median_ms) * 100.00;
+                if change_pct > threshold_pct {
+                    comparison.regressions.push(Regression::Median {
+                        query_id: entry.query_id.clone(),
+                        baseline_ms: baseline.median_ms,
+                        current_ms: entry.median_ms,
+                        change_pct,
+                    });
+                }
+            }
+            if let (Some(baseline_hash), Some(current_hash)) =
+                (&baseline.plan_hash, &entry.plan_hash)
+            {
+                if baseline_hash != current_hash {
+                    comparison.regressions.push(Regression::PlanShape {
+                        query_id: entry.query_id.clone(),
+                        baseline_hash: baseline_hash.clone(),
+                        current_hash: current_hash.clone(),
+                    });
+                }
+            }
+        }
+
+        comparison
+    }
+}
+
+/// Records or compares `results` against the store named by `BENCHMARK_BASELINE`, if set.
+///
+/// `BENCHMARK_BASELINE_MODE=record` overwrites the stored baselines; otherwise results are
+/// compared, allowing `BENCHMARK_REGRESSION_THRESHOLD` percent of slack (20 by default), and a
+/// regression is returned as an `Err` holding the report.
+pub fn record_or_compare_from_env(
+    results: &[&BenchmarkResult],
+    dataset_size: usize,
+    seed: u64,
+) -> Result<Option<Comparison>, String> {
+    let Ok(path) = std::env::var("BENCHMARK_BASELINE") else {
+        return Ok(None);
+    };
+    let threshold_pct = match std::env::var("BENCHMARK_REGRESSION_THRESHOLD") {
+        Ok(threshold) => threshold.parse().map_err(|e| {
+            format!(
+                "Error: invalid BENCHMARK_REGRESSION_THRESHOLD - <value={}> - <error={:?}>",
+                threshold, e
+            )
+        })?,
+        Err(_) => DEFAULT_REGRESSION_THRESHOLD_PCT,
+    };
+    let entries: Vec<BaselineEntry> = results
+        .iter()
+        .map(|result| BaselineEntry::from_result(result, dataset_size, seed))
+        .collect();
+
+    // Tests may run in parallel processes, so hold a lock file across load-modify-save.
+    let path = Path::new(&path);
+    let lock = fs::File::create(path.with_extension("lock"))
+        .and_then(|lock| lock.lock().map(|_| lock))
+        .map_err(|e| {
+            format!(
+                "Error: failed to lock baseline store - <path={}> - <error={:?}>",
+                path.display(),
+                e
+            )
+        })?;
+    let mut store = BaselineStore::load(path)?;
+    if std::env::var("BENCHMARK_BASELINE_MODE").as_deref() == Ok("record") {
+        for entry in entries {
+            store.record(entry);
+        }
+        store.save()?;
+        drop(lock);
+        return Ok(None);
+    }
+    drop(lock);
+
+    store.compare(&entries, threshold_pct).check().map(Some)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::benchmarking::explain::explain_analyze;
+    use crate::benchmarking::Benchmark;
+    use crate::enums::sample_query::SampleQuery;
+    use crate::optimisations::{BankSystemManager, NUM_USERS};
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    fn store_path() -> PathBuf {
+        std::env::temp_dir().join(format!("baselines-{}.json", Uuid::new_v4()))
+    }
+
+    fn entry(query_id: &str, median_ms: f64, plan_hash: &str) -> BaselineEntry {
+        BaselineEntry {
+            query_id: query_id.to_string(),
+            dataset_size: 100,
+            seed: 42,
+            iterations: 10,
+            min_ms: median_ms,
+            median_ms,
+            mean_ms: median_ms,
+            p95_ms: median_ms,
+            p99_ms: median_ms,
+            plan_hash: Some(plan_hash.to_string()),
+            recorded_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_it_flags_median_regressions_beyond_threshold() {
+        let path = store_path();
+        let mut store = BaselineStore::load(&path).unwrap();
+        store.record(entry("a", 10.0, "x"));
+        store.record(entry("b", 10.0, "x"));
+
+        let comparison = store.compare(&[entry("a", 11.0, "x"), entry("b", 13.0, "x")], 20.0);
+
+        assert_eq!(comparison.compared, 2);
+        assert_eq!(comparison.regressions.len(), 1);
+        assert!(matches!(
+            &comparison.regressions[0],
+            Regression::Median { query_id, .. } if query_id == "b"
+        ));
+        let error = comparison.check().unwrap_err();
+        assert!(error.contains("b - median 10.000 ms -> 13.000 ms (+30.0%)"));
+    }
+
+    #[test]
+    fn test_it_flags_plan_shape_changes() {
+        let mut store = BaselineStore::load(&store_path()).unwrap();
+        store.record(entry("a", 10.0, "x"));
+
+        let comparison = store.compare(&[entry("a", 5.0, "y"), entry("c", 1.0, "x")], 20.0);
+
+        assert_eq!(
+            comparison.regressions,
+            vec![Regression::PlanShape {
+                query_id: String::from("a"),
+                baseline_hash: String::from("x"),
+                current_hash: String::from("y"),
+            }]
+        );
+        assert_eq!(comparison.missing, vec![String::from("c")]);
+    }
+
+    #[test]
+    fn test_it_does_not_compare_results_from_another_seed() {
+        let mut store = BaselineStore::load(&store_path()).unwrap();
+        store.record(entry("a", 10.0, "x"));
+        let mut reseeded = entry("a", 20.0, "y");
+        reseeded.seed = 7;
+
+        let comparison = store.compare(&[reseeded.clone()], 20.0);
+
+        assert_eq!(comparison.compared, 0);
+        assert!(comparison.passed());
+        assert_eq!(comparison.missing, vec![String::from("a")]);
+
+        store.record(reseeded);
+        assert_eq!(store.entries.len(), 2);
+        assert_eq!(store.get("a", 100, 42).unwrap().median_ms, 10.0);
+    }
+
+    #[test]
+    fn test_it_saves_and_reloads_entries() {
+        let path = store_path();
+        let mut store = BaselineStore::load(&path).unwrap();
+        store.record(entry("a", 10.0, "x"));
+        store.record(entry("a", 12.0, "x"));
+
+        let saved = store.save();
+        let reloaded = BaselineStore::load(&path);
+        let _ = fs::remove_file(&path);
+
+        assert!(saved.is_ok());
+        let reloaded = reloaded.unwrap();
+        assert_eq!(reloaded.entries.len(), 1);
+        assert_eq!(reloaded.get("a", 100, 42).unwrap().median_ms, 12.0);
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/audit_logs.sql",
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/cards.sql",
+        "../../db/schema/transfers.sql",
+        "../../db/schema/transactions.sql",
+        "../../db/schema/loans.sql",
+        "../../db/schema/payments.sql",
+    ))]
+    async fn test_plan_hash_ignores_costs_but_not_shape(pool: PgPool) -> sqlx::Result<()> {
+        let bank_system_manager = BankSystemManager::new(pool.clone());
+        bank_system_manager.insert_data().await;
+        let benchmark = Benchmark::new(1, 5);
+
+        let first = benchmark
+            .run_with_plan(&pool, "total_loans", SampleQuery::TotalLoans.sql())
+            .await?;
+        let second = explain_analyze(&pool, SampleQuery::TotalLoans.sql()).await?;
+        let other = explain_analyze(&pool, SampleQuery::TotalBalances.sql()).await?;
+
+        let entry =
+            BaselineEntry::from_result(&first, NUM_USERS as usize, bank_system_manager.seed());
+        assert_eq!(entry.plan_hash, Some(plan_hash(&second)));
+        assert_ne!(plan_hash(&second), plan_hash(&other));
+        assert_eq!(entry.iterations, 5);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,251 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{Executor, Postgres, Row};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanNode {
+    #[serde(rename = "Node Type")]
+    pub node_type: String,
+    #[serde(rename = "Relation Name", default)]
+    pub relation_name: Option<String>,
+    #[serde(rename = "Index Name", default)]
+    pub index_name: Option<String>,
+    #[serde(rename = "Startup Cost")]
+    pub startup_cost: f64,
+    #[serde(rename = "Total Cost")]
+    pub total_cost: f64,
+    /// Rows the planner estimated, per loop.
+    #[serde(rename = "Plan Rows")]
+    pub estimated_rows: f64,
+    /// Rows actually returned, per loop. Only present for `ANALYZE` plans.
+    #[serde(rename = "Actual Rows", default)]
+    pub actual_rows: Option<f64>,
+    #[serde(rename = "Actual Loops", default)]
+    pub actual_loops: Option<f64>,
+    #[serde(rename = "Actual Startup Time", default)]
+    pub actual_startup_time_ms: Option<f64>,
+    #[serde(rename = "Actual Total Time", default)]
+    pub actual_total_time_ms: Option<f64>,
+    #[serde(rename = "Shared Hit Blocks", default)]
+    pub shared_hit_blocks: Option<i64>,
+    #[serde(rename = "Shared Read Blocks", default)]
+    pub shared_read_blocks: Option<i64>,
+    #[serde(rename = "Plans", default)]
+    pub children: Vec<PlanNode>,
+}
+
+impl PlanNode {
+    /// Every node in the tree, depth first, starting with this one.
+    pub fn nodes(&self) -> Vec<&PlanNode> {
+        let mut nodes = vec![self];
+        for child in &self.children {
+            nodes.extend(child.nodes());
+        }
+        nodes
+    }
+
+    /// How many times more rows were returned than estimated; below 1.0 is an overestimate.
+    pub fn row_estimate_ratio(&self) -> Option<f64> {
+        let actual_rows = self.actual_rows?;
+        Some(actual_rows.max(1.0) / self.estimated_rows.max(1.0))
+    }
+
+    pub fn label(&self) -> String {
+        match (&self.index_name, &self.relation_name) {
+            (Some(index), Some(relation)) => {
+                format!("{} using {} on {}", self.node_type, index, relation)
+            }
+            (Some(index), None) => format!("{} using {}", self.node_type, index),
+            (None, Some(relation)) => format!("{} on {}", self.node_type, relation),
+            (None, None) => self.node_type.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryPlan {
+    #[serde(rename = "Plan")]
+    pub root: PlanNode,
+    #[serde(rename = "Planning Time", default)]
+    pub planning_time_ms: Option<f64>,
+    #[serde(rename = "Execution Time", default)]
+    pub execution_time_ms: Option<f64>,
+}
+
+impl QueryPlan {
+    pub fn node_types(&self) -> Vec<&str> {
+        self.root
+            .nodes()
+            .iter()
+            .map(|node| node.node_type.as_str())
+            .collect()
+    }
+
+    /// One indented line per node, e.g. `Seq Scan on accounts (est 400, actual 400 x 1, ...)`.
+    pub fn summary(&self) -> String {
+        let mut lines = Vec::new();
+        summarise_node(&self.root, 0, &mut lines);
+        if let (Some(planning), Some(execution)) = (self.planning_time_ms, self.execution_time_ms) {
+            lines.push(format!(
+                "Planning {:.3} ms, execution {:.3} ms",
+                planning, execution
+            ));
+        }
+        lines.join("\n")
+    }
+}
+
+fn summarise_node(node: &PlanNode, depth: usize, lines: &mut Vec<String>) {
+    let actual = match (node.actual_rows, node.actual_loops) {
+        (Some(rows), Some(loops)) => format!(", actual {} x {}", rows, loops),
+        _ => String::new(),
+    };
+    let buffers = match (node.shared_hit_blocks, node.shared_read_blocks) {
+        (Some(hit), Some(read)) => format!(", hit {} read {}", hit, read),
+        _ => String::new(),
+    };
+    let time = node
+        .actual_total_time_ms
+        .map(|time| format!(", {:.3} ms", time))
+        .unwrap_or_default();
+    lines.push(format!(
+        "{}{} (cost {:.2}, est {}{}{}{})",
+        "  ".repeat(depth),
+        node.label(),
+        node.total_cost,
+        node.estimated_rows,
+        actual,
+        buffers,
+        time
+    ));
+    for child in &node.children {
+        summarise_node(child, depth + 1, lines);
+    }
+}
+
+/// Runs `sql` under `EXPLAIN (ANALYZE, BUFFERS, FORMAT JSON)`.
+///
+/// `ANALYZE` executes the statement, so only pass statements that are safe to run again.
+pub async fn explain_analyze<'c, E>(db: E, sql: &str) -> Result<QueryPlan, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    explain(db, "ANALYZE, BUFFERS, FORMAT JSON", sql).await
+}
+
+/// Runs `sql` under `EXPLAIN (FORMAT JSON)`, which plans without executing.
+///
+/// Takes any executor so session-local state, such as hypothetical indexes, can be planned against.
+pub async fn explain_estimate<'c, E>(db: E, sql: &str) -> Result<QueryPlan, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    explain(db, "FORMAT JSON", sql).await
+}
+
+async fn explain<'c, E>(db: E, options: &str, sql: &str) -> Result<QueryPlan, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let row = sqlx::query(&format!("EXPLAIN ({})\n{}", options, sql))
+        .fetch_one(db)
+        .await?;
+    parse_plan(row.get::<serde_json::Value, _>(0))
+}
+
+fn parse_plan(json: serde_json::Value) -> Result<QueryPlan, sqlx::Error> {
+    let mut plans: Vec<QueryPlan> =
+        serde_json::from_value(json).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+    if plans.is_empty() {
+        return Err(sqlx::Error::Decode(
+            "Error: EXPLAIN returned no plan".into(),
+        ));
+    }
+    Ok(plans.remove(0))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::enums::sample_query::SampleQuery;
+    use crate::optimisations::BankSystemManager;
+    use sqlx::PgPool;
+
+    #[test]
+    fn test_it_parses_explain_json() {
+        let json = serde_json::json!([{
+            "Plan": {
+                "Node Type": "Hash Join",
+                "Startup Cost": 1.5,
+                "Total Cost": 20.25,
+                "Plan Rows": 10,
+                "Actual Rows": 40,
+                "Actual Loops": 1,
+                "Actual Total Time": 0.5,
+                "Shared Hit Blocks": 7,
+                "Shared Read Blocks": 1,
+                "Plans": [
+                    {
+                        "Node Type": "Index Scan",
+                        "Relation Name": "accounts",
+                        "Index Name": "idx_accounts_user_id",
+                        "Startup Cost": 0.0,
+                        "Total Cost": 8.0,
+                        "Plan Rows": 4,
+                        "Actual Rows": 4,
+                        "Actual Loops": 10
+                    }
+                ]
+            },
+            "Planning Time": 0.1,
+            "Execution Time": 0.6
+        }]);
+
+        let plan = parse_plan(json).unwrap();
+
+        assert_eq!(plan.node_types(), vec!["Hash Join", "Index Scan"]);
+        assert_eq!(plan.root.row_estimate_ratio(), Some(4.0));
+        assert_eq!(plan.root.shared_hit_blocks, Some(7));
+        assert_eq!(
+            plan.root.children[0].label(),
+            "Index Scan using idx_accounts_user_id on accounts"
+        );
+        assert_eq!(plan.execution_time_ms, Some(0.6));
+    }
+
+    #[test]
+    fn test_it_errors_on_empty_explain_json() {
+        let result = parse_plan(serde_json::json!([]));
+
+        assert!(result.is_err());
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/audit_logs.sql",
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/cards.sql",
+        "../../db/schema/transfers.sql",
+        "../../db/schema/transactions.sql",
+        "../../db/schema/loans.sql",
+        "../../db/schema/payments.sql",
+    ))]
+    async fn test_it_explains_sample_queries(pool: PgPool) -> sqlx::Result<()> {
+        let bank_system_manager = BankSystemManager::new(pool.clone());
+        bank_system_manager.insert_data().await;
+
+        for query in SampleQuery::all() {
+            let plan = explain_analyze(&pool, query.sql()).await?;
+
+            assert!(plan.execution_time_ms.is_some());
+            assert!(plan.root.actual_rows.is_some());
+            assert!(plan.root.shared_hit_blocks.is_some());
+            println!("{}:\n{}", query.to_string(), plan.summary());
+        }
+
+        let estimate = explain_estimate(&pool, SampleQuery::TotalLoans.sql()).await?;
+        assert_eq!(estimate.execution_time_ms, None);
+        assert_eq!(estimate.root.actual_rows, None);
+
+        Ok(())
+    }
+}
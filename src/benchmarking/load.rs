@@ -0,0 +1,432 @@
+use super::percentile;
+use crate::enums::materialized_view::MaterializedView;
+use crate::enums::sample_query::SampleQuery;
+use rand::Rng;
+use sqlx::{Pool, Postgres, Row};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+
+// Distinct error messages kept in a report, so a failing run does not print thousands.
+const MAX_ERROR_MESSAGES: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoadOperation {
+    Query(SampleQuery),
+    ViewRead(MaterializedView),
+    /// Adds to an account's balance, taking a row lock until the statement commits.
+    WriteBalance,
+    InsertTransaction,
+}
+
+impl LoadOperation {
+    pub fn name(&self) -> String {
+        match self {
+            Self::Query(query) => query.to_string().to_string(),
+            Self::ViewRead(view) => format!("{} (view)", view.to_string()),
+            Self::WriteBalance => String::from("write balance"),
+            Self::InsertTransaction => String::from("insert transaction"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadConfig {
+    pub clients: usize,
+    pub duration: Duration,
+    /// Operations with relative weights, e.g. a weight of 2 is picked twice as often as 1.
+    pub mix: Vec<(LoadOperation, u32)>,
+    /// Writes pick from the first `write_accounts` accounts, all accounts if `None`; fewer
+    /// accounts means more row lock contention.
+    pub write_accounts: Option<i32>,
+    pub lock_sample_interval: Duration,
+}
+
+impl LoadConfig {
+    pub fn new(clients: usize, duration: Duration, mix: Vec<(LoadOperation, u32)>) -> Self {
+        Self {
+            clients,
+            duration,
+            mix,
+            write_accounts: None,
+            lock_sample_interval: Duration::from_millis(50),
+        }
+    }
+
+    /// Every sample query and view once, with view reads and writes weighted up.
+    pub fn default_mix() -> Vec<(LoadOperation, u32)> {
+        let mut mix: Vec<(LoadOperation, u32)> = SampleQuery::all()
+            .into_iter()
+            .map(|query| (LoadOperation::Query(query), 1))
+            .collect();
+        mix.extend(
+            MaterializedView::all()
+                .into_iter()
+                .map(|view| (LoadOperation::ViewRead(view), 2)),
+        );
+        mix.push((LoadOperation::WriteBalance, 4));
+        mix.push((LoadOperation::InsertTransaction, 2));
+        mix
+    }
+
+    fn pick<R: Rng>(&self, rng: &mut R) -> Option<LoadOperation> {
+        let total: u32 = self.mix.iter().map(|(_, weight)| weight).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut target = rng.random_range(0..total);
+        for (operation, weight) in &self.mix {
+            if target < *weight {
+                return Some(*operation);
+            }
+            target -= weight;
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OperationStats {
+    pub operation: String,
+    pub completed: usize,
+    pub errors: usize,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockStats {
+    pub samples: usize,
+    /// Most backends seen waiting on a heavyweight lock in one sample.
+    pub max_waiting: i64,
+    pub mean_waiting: f64,
+    /// Most `pg_locks` entries seen not granted in one sample.
+    pub max_ungranted: i64,
+    /// How many times each `wait_event` was seen across samples, for backends waiting on locks.
+    pub wait_events: BTreeMap<String, usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadReport {
+    pub clients: usize,
+    pub elapsed: Duration,
+    pub completed: usize,
+    pub errors: usize,
+    /// Completed operations per second.
+    pub throughput: f64,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub operations: Vec<OperationStats>,
+    pub locks: LockStats,
+    pub error_messages: Vec<String>,
+}
+
+struct ClientResult {
+    latencies: Vec<(LoadOperation, Duration)>,
+    errors: Vec<(LoadOperation, String)>,
+}
+
+/// Runs `clients` concurrent loops over the pool, each picking operations from the mix until
+/// `duration` has passed, while a monitor samples `pg_stat_activity` and `pg_locks`.
+#[derive(Clone)]
+pub struct LoadTest {
+    db: Pool<Postgres>,
+    config: LoadConfig,
+}
+
+impl LoadTest {
+    pub fn new(db: Pool<Postgres>, config: LoadConfig) -> Self {
+        Self { db, config }
+    }
+
+    pub async fn run(&self) -> Result<LoadReport, sqlx::Error> {
+        let num_accounts: i32 =
+            sqlx::query("SELECT COALESCE(MAX(id), 0) AS num_accounts FROM public.accounts")
+                .fetch_one(&self.db)
+                .await?
+                .get("num_accounts");
+        let write_accounts = self
+            .config
+            .write_accounts
+            .map_or(num_accounts, |write_accounts| {
+                write_accounts.min(num_accounts)
+            })
+            .max(1);
+
+        let start = Instant::now();
+        let deadline = start + self.config.duration;
+        let mut clients = JoinSet::new();
+        for _ in 0..self.config.clients {
+            let test = self.clone();
+            clients.spawn(async move { test.client(deadline, write_accounts).await });
+        }
+        let (results, locks) = tokio::join!(clients.join_all(), self.monitor(deadline));
+        let elapsed = start.elapsed();
+
+        Ok(self.report(results, locks?, elapsed))
+    }
+
+    async fn client(&self, deadline: Instant, write_accounts: i32) -> ClientResult {
+        let mut result = ClientResult {
+            latencies: Vec::new(),
+            errors: Vec::new(),
+        };
+        while Instant::now() < deadline {
+            let (operation, account_id) = {
+                let mut rng = rand::rng();
+                let Some(operation) = self.config.pick(&mut rng) else {
+                    break;
+                };
+                (operation, rng.random_range(1..=write_accounts))
+            };
+            let operation_start = Instant::now();
+            match self.execute(operation, account_id).await {
+                Ok(()) => result
+                    .latencies
+                    .push((operation, operation_start.elapsed())),
+                Err(e) => result.errors.push((operation, format!("{:?}", e))),
+            }
+        }
+        result
+    }
+
+    async fn execute(&self, operation: LoadOperation, account_id: i32) -> Result<(), sqlx::Error> {
+        match operation {
+            LoadOperation::Query(query) => {
+                sqlx::query(query.sql()).fetch_all(&self.db).await?;
+            }
+            LoadOperation::ViewRead(view) => {
+                sqlx::query(&format!("SELECT * FROM public.{}", view.to_string()))
+                    .fetch_all(&self.db)
+                    .await?;
+            }
+            LoadOperation::WriteBalance => {
+                sqlx::query("UPDATE public.accounts SET balance = balance + 1.00 WHERE id = $1")
+                    .bind(account_id)
+                    .execute(&self.db)
+                    .await?;
+            }
+            LoadOperation::InsertTransaction => {
+                sqlx::query(
+                    "
+                    INSERT INTO public.transactions
+                    (account_id, transaction_type, amount, status)
+                    VALUES ($1, 'deposit', 1.00, 'pending');
+                    ",
+                )
+                .bind(account_id)
+                .execute(&self.db)
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn monitor(&self, deadline: Instant) -> Result<LockStats, sqlx::Error> {
+        let mut locks = LockStats {
+            samples: 0,
+            max_waiting: 0,
+            mean_waiting: 0.0,
+            max_ungranted: 0,
+            wait_events: BTreeMap::new(),
+        };
+        let mut total_waiting = 0;
+        let mut conn = self.db.acquire().await?;
+
+        while Instant::now() < deadline {
+            tokio::time::sleep(self.config.lock_sample_interval).await;
+
+            let waiting = sqlx::query(
+                "
+                SELECT wait_event
+                FROM pg_stat_activity
+                WHERE datname = current_database()
+                    AND wait_event_type = 'Lock'
+                    AND pid <> pg_backend_pid();
+                ",
+            )
+            .fetch_all(&mut *conn)
+            .await?;
+            let ungranted: i64 = sqlx::query(
+                "
+                SELECT COUNT(*) AS ungranted
+                FROM pg_locks l
+                JOIN pg_database d ON d.oid = l.database AND d.datname = current_database()
+                WHERE NOT l.granted;
+                ",
+            )
+            .fetch_one(&mut *conn)
+            .await?
+            .get("ungranted");
+
+            locks.samples += 1;
+            total_waiting += waiting.len() as i64;
+            locks.max_waiting = locks.max_waiting.max(waiting.len() as i64);
+            locks.max_ungranted = locks.max_ungranted.max(ungranted);
+            for row in waiting {
+                let wait_event: Option<String> = row.get("wait_event");
+                *locks
+                    .wait_events
+                    .entry(wait_event.unwrap_or_default())
+                    .or_insert(0) += 1;
+            }
+        }
+
+        if locks.samples > 0 {
+            locks.mean_waiting = total_waiting as f64 / locks.samples as f64;
+        }
+        Ok(locks)
+    }
+
+    fn report(
+        &self,
+        results: Vec<ClientResult>,
+        locks: LockStats,
+        elapsed: Duration,
+    ) -> LoadReport {
+        let mut all_latencies = Vec::new();
+        let mut by_operation: BTreeMap<String, (Vec<Duration>, usize)> = BTreeMap::new();
+        let mut error_messages: Vec<String> = Vec::new();
+        let mut errors = 0;
+
+        for result in results {
+            for (operation, latency) in result.latencies {
+                by_operation
+                    .entry(operation.name())
+                    .or_default()
+                    .0
+                    .push(latency);
+                all_latencies.push(latency);
+            }
+            for (operation, message) in result.errors {
+                by_operation.entry(operation.name()).or_default().1 += 1;
+                errors += 1;
+                if error_messages.len() < MAX_ERROR_MESSAGES && !error_messages.contains(&message) {
+                    error_messages.push(message);
+                }
+            }
+        }
+
+        all_latencies.sort();
+        let operations = by_operation
+            .into_iter()
+            .map(|(operation, (mut latencies, errors))| {
+                latencies.sort();
+                OperationStats {
+                    operation,
+                    completed: latencies.len(),
+                    errors,
+                    p50: percentile(&latencies, 50.0),
+                    p95: percentile(&latencies, 95.0),
+                    p99: percentile(&latencies, 99.0),
+                }
+            })
+            .collect();
+
+        LoadReport {
+            clients: self.config.clients,
+            elapsed,
+            completed: all_latencies.len(),
+            errors,
+            throughput: all_latencies.len() as f64 / elapsed.as_secs_f64(),
+            p50: percentile(&all_latencies, 50.0),
+            p95: percentile(&all_latencies, 95.0),
+            p99: percentile(&all_latencies, 99.0),
+            operations,
+            locks,
+            error_messages,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::enums::refresh_mode::RefreshMode;
+    use crate::optimisations::BankSystemManager;
+    use crate::views::refresh;
+    use sqlx::PgPool;
+
+    #[test]
+    fn test_pick_respects_weights() {
+        let config = LoadConfig::new(
+            1,
+            Duration::ZERO,
+            vec![
+                (LoadOperation::WriteBalance, 0),
+                (LoadOperation::InsertTransaction, 3),
+            ],
+        );
+        let mut rng = rand::rng();
+
+        for _ in 0..100 {
+            assert_eq!(
+                config.pick(&mut rng),
+                Some(LoadOperation::InsertTransaction)
+            );
+        }
+        assert_eq!(
+            LoadConfig::new(1, Duration::ZERO, Vec::new()).pick(&mut rng),
+            None
+        );
+    }
+
+    #[test]
+    fn test_default_mix_covers_queries_views_and_writes() {
+        let mix = LoadConfig::default_mix();
+
+        assert_eq!(
+            mix.len(),
+            SampleQuery::all().len() + MaterializedView::all().len() + 2
+        );
+        assert_eq!(
+            LoadOperation::ViewRead(MaterializedView::LoansOutstanding).name(),
+            "loans_outstanding (view)"
+        );
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/audit_logs.sql",
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/cards.sql",
+        "../../db/schema/transfers.sql",
+        "../../db/schema/transactions.sql",
+        "../../db/schema/loans.sql",
+        "../../db/schema/payments.sql",
+        "../../db/views/average_transaction_amount.sql",
+        "../../db/views/loans_outstanding.sql",
+        "../../db/views/suspicious_transactions.sql",
+    ))]
+    async fn test_it_runs_concurrent_clients_for_duration(pool: PgPool) -> sqlx::Result<()> {
+        let bank_system_manager = BankSystemManager::new(pool.clone());
+        bank_system_manager.bulk_insert_data().await?;
+        for view in MaterializedView::all() {
+            refresh(&pool, view, RefreshMode::Blocking).await?;
+        }
+        let mut config = LoadConfig::new(4, Duration::from_secs(1), LoadConfig::default_mix());
+        config.write_accounts = Some(2);
+
+        let report = LoadTest::new(pool.clone(), config).run().await?;
+
+        assert_eq!(report.clients, 4);
+        assert!(report.elapsed >= Duration::from_secs(1));
+        assert!(report.completed > 0);
+        assert_eq!(report.errors, 0, "{:?}", report.error_messages);
+        assert!(report.locks.samples > 0);
+        assert_eq!(
+            report
+                .operations
+                .iter()
+                .map(|operation| operation.completed)
+                .sum::<usize>(),
+            report.completed
+        );
+        println!("{:#?}", report);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,353 @@
+pub mod baseline;
+pub mod explain;
+pub mod load;
+pub mod plan_diff;
+pub mod scaling;
+pub mod statements;
+
+use explain::QueryPlan;
+use sqlx::{Pool, Postgres, Row};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchmarkStats {
+    pub iterations: usize,
+    pub min: Duration,
+    pub max: Duration,
+    pub mean: Duration,
+    pub median: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub stddev: Duration,
+}
+
+impl BenchmarkStats {
+    pub fn from_samples(samples: &[Duration]) -> Self {
+        let mut sorted = samples.to_vec();
+        sorted.sort();
+
+        let n = sorted.len();
+        let mean = if n == 0 {
+            0.0
+        } else {
+            sorted.iter().map(Duration::as_secs_f64).sum::<f64>() / n as f64
+        };
+        let variance = if n < 2 {
+            0.0
+        } else {
+            sorted
+                .iter()
+                .map(|sample| (sample.as_secs_f64() - mean).powi(2))
+                .sum::<f64>()
+                / (n - 1) as f64
+        };
+        let median = match n {
+            0 => Duration::ZERO,
+            _ if n.is_multiple_of(2) => (sorted[n / 2 - 1] + sorted[n / 2]) / 2,
+            _ => sorted[n / 2],
+        };
+
+        Self {
+            iterations: n,
+            min: sorted.first().copied().unwrap_or_default(),
+            max: sorted.last().copied().unwrap_or_default(),
+            mean: Duration::from_secs_f64(mean),
+            median,
+            p95: percentile(&sorted, 95.0),
+            p99: percentile(&sorted, 99.0),
+            stddev: Duration::from_secs_f64(variance.sqrt()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchmarkResult {
+    pub name: String,
+    pub rows: usize,
+    pub stats: BenchmarkStats,
+    /// Timed iterations in the order they ran.
+    pub samples: Vec<Duration>,
+    pub plan: Option<QueryPlan>,
+}
+
+impl BenchmarkResult {
+    /// How much faster this result's median is than `other`'s, as a percentage of `other`.
+    pub fn percentage_faster_than(&self, other: &BenchmarkResult) -> f64 {
+        let other_median = other.stats.median.as_secs_f64();
+        if other_median == 0.0 {
+            return 0.0;
+        }
+        ((other_median - self.stats.median.as_secs_f64()) / other_median) * 100.00
+    }
+}
+
+pub struct Benchmark {
+    warmup: usize,
+    iterations: usize,
+}
+
+impl Benchmark {
+    pub fn new(warmup: usize, iterations: usize) -> Self {
+        Self { warmup, iterations }
+    }
+
+    /// Runs `sql` `warmup` times untimed, then `iterations` times timed, on a single connection.
+    pub async fn run(
+        &self,
+        db: &Pool<Postgres>,
+        name: &str,
+        sql: &str,
+    ) -> Result<BenchmarkResult, sqlx::Error> {
+        let mut conn = db.acquire().await?;
+
+        for _ in 0..self.warmup {
+            sqlx::query(sql).fetch_all(&mut *conn).await?;
+        }
+
+        let mut rows = 0;
+        let mut samples = Vec::with_capacity(self.iterations);
+        for _ in 0..self.iterations {
+            let start = Instant::now();
+            rows = sqlx::query(sql).fetch_all(&mut *conn).await?.len();
+            samples.push(start.elapsed());
+        }
+
+        Ok(BenchmarkResult {
+            name: name.to_string(),
+            rows,
+            stats: BenchmarkStats::from_samples(&samples),
+            samples,
+            plan: None,
+        })
+    }
+
+    /// Like `run`, then captures an `EXPLAIN (ANALYZE, BUFFERS)` plan to store with the timings.
+    pub async fn run_with_plan(
+        &self,
+        db: &Pool<Postgres>,
+        name: &str,
+        sql: &str,
+    ) -> Result<BenchmarkResult, sqlx::Error> {
+        let mut result = self.run(db, name, sql).await?;
+        result.plan = Some(explain::explain_analyze(db, sql).await?);
+
+        Ok(result)
+    }
+}
+
+/// Rows copied out of a table once and inserted back by every `insert_throughput` batch.
+const INSERT_SAMPLE_ROWS: i64 = 1_000;
+
+/// Columns to set when copying existing rows of `table`, each paired with the expression giving
+/// its value: the column itself, or a fresh value for a column with a single column unique
+/// constraint. `id` and unique columns with a default are left for the database to generate.
+pub async fn copy_columns(
+    db: &Pool<Postgres>,
+    table: &str,
+) -> Result<Vec<(String, String)>, sqlx::Error> {
+    let rows = sqlx::query(
+        "
+        SELECT
+            c.column_name::TEXT AS column_name,
+            c.data_type::TEXT AS data_type,
+            c.character_maximum_length::INT AS max_length,
+            c.column_default IS NOT NULL AS has_default,
+            EXISTS (
+                SELECT 1
+                FROM pg_index i
+                JOIN pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = i.indkey[0]
+                WHERE i.indrelid = format('public.%I', c.table_name)::regclass
+                    AND i.indisunique
+                    AND i.indnatts = 1
+                    AND a.attname = c.column_name
+            ) AS is_unique
+        FROM information_schema.columns c
+        WHERE c.table_schema = 'public' AND c.table_name = $1 AND c.column_name <> 'id'
+        ORDER BY c.ordinal_position;
+        ",
+    )
+    .bind(table)
+    .fetch_all(db)
+    .await?;
+
+    let mut columns = Vec::with_capacity(rows.len());
+    for row in rows {
+        let column: String = row.get("column_name");
+        if !row.get::<bool, _>("is_unique") {
+            columns.push((column.clone(), column));
+            continue;
+        }
+        if row.get::<bool, _>("has_default") {
+            continue;
+        }
+        let data_type: String = row.get("data_type");
+        let value = match data_type.as_str() {
+            "uuid" => String::from("gen_random_uuid()"),
+            "text" | "character varying" | "character" => format!(
+                "left(md5(random()::TEXT), {})",
+                row.get::<Option<i32>, _>("max_length").unwrap_or(32).min(32)
+            ),
+            _ => {
+                return Err(sqlx::Error::Protocol(format!(
+                    "Error: cannot generate unique values to copy rows - <table={}> - <column={}> - <data_type={}>",
+                    table, column, data_type
+                )))
+            }
+        };
+        columns.push((column, value));
+    }
+
+    Ok(columns)
+}
+
+/// Inserts the same sample of up to `INSERT_SAMPLE_ROWS` existing rows back into `table`
+/// `batches` times in a rolled back transaction, returning rows/sec.
+///
+/// Every batch inserts the same rows, so batches stay the same size as the table grows and the
+/// figure reflects per row overhead such as indexes and triggers. Unique columns get fresh values
+/// per batch, as `copy_columns` describes.
+pub async fn insert_throughput(
+    db: &Pool<Postgres>,
+    table: &str,
+    batches: usize,
+) -> Result<f64, sqlx::Error> {
+    let (columns, values): (Vec<String>, Vec<String>) =
+        copy_columns(db, table).await?.into_iter().unzip();
+    let insert = format!(
+        "INSERT INTO public.{table} ({columns}) SELECT {values} FROM insert_sample",
+        table = table,
+        columns = columns.join(", "),
+        values = values.join(", ")
+    );
+
+    let mut tx = db.begin().await?;
+    sqlx::query(&format!(
+        "CREATE TEMP TABLE insert_sample ON COMMIT DROP AS SELECT * FROM public.{table} LIMIT {limit}",
+        table = table,
+        limit = INSERT_SAMPLE_ROWS
+    ))
+    .execute(&mut *tx)
+    .await?;
+    let mut rows = 0;
+    let start = Instant::now();
+    for _ in 0..batches {
+        rows += sqlx::query(&insert)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+    }
+    let elapsed = start.elapsed();
+    tx.rollback().await?;
+
+    Ok(rows as f64 / elapsed.as_secs_f64())
+}
+
+/// Nearest-rank percentile of an already sorted slice.
+pub fn percentile(sorted: &[Duration], percentile: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = ((percentile / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::optimisations::BankSystemManager;
+    use sqlx::PgPool;
+
+    #[test]
+    fn test_percentile_nearest_rank() {
+        let sorted: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+
+        assert_eq!(percentile(&sorted, 50.0), Duration::from_millis(50));
+        assert_eq!(percentile(&sorted, 99.0), Duration::from_millis(99));
+        assert_eq!(percentile(&[], 99.0), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_stats_from_samples() {
+        let samples: Vec<Duration> = [4, 1, 3, 2]
+            .into_iter()
+            .map(Duration::from_millis)
+            .collect();
+
+        let stats = BenchmarkStats::from_samples(&samples);
+
+        assert_eq!(stats.iterations, 4);
+        assert_eq!(stats.min, Duration::from_millis(1));
+        assert_eq!(stats.max, Duration::from_millis(4));
+        assert_eq!(stats.median, Duration::from_micros(2_500));
+        assert_eq!(stats.mean, Duration::from_micros(2_500));
+        assert_eq!(stats.p99, Duration::from_millis(4));
+        // Sample standard deviation of 1, 2, 3, 4 ms is ~1.29 ms.
+        assert_eq!(stats.stddev.as_micros(), 1_290);
+    }
+
+    #[test]
+    fn test_stats_from_no_samples() {
+        let stats = BenchmarkStats::from_samples(&[]);
+
+        assert_eq!(stats.iterations, 0);
+        assert_eq!(stats.median, Duration::ZERO);
+        assert_eq!(stats.stddev, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_percentage_faster_than() {
+        let result = |median_ms| BenchmarkResult {
+            name: String::from("query"),
+            rows: 0,
+            stats: BenchmarkStats::from_samples(&[Duration::from_millis(median_ms)]),
+            samples: vec![Duration::from_millis(median_ms)],
+            plan: None,
+        };
+
+        assert_eq!(
+            result(25).percentage_faster_than(&result(100)).round(),
+            75.0
+        );
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/audit_logs.sql",
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/cards.sql",
+        "../../db/schema/transfers.sql",
+        "../../db/schema/transactions.sql",
+        "../../db/schema/loans.sql",
+        "../../db/schema/payments.sql",
+    ))]
+    async fn test_insert_throughput_on_tables_with_unique_columns(
+        pool: PgPool,
+    ) -> sqlx::Result<()> {
+        BankSystemManager::with_num_users(pool.clone(), 10)
+            .bulk_insert_data()
+            .await?;
+        let count = |table: &'static str| {
+            let pool = pool.clone();
+            async move {
+                sqlx::query(&format!("SELECT COUNT(*) AS count FROM public.{}", table))
+                    .fetch_one(&pool)
+                    .await
+                    .map(|row| row.get::<i64, _>("count"))
+            }
+        };
+
+        let users = copy_columns(&pool, "users").await?;
+        assert!(users.iter().all(|(column, _)| column != "public_id"));
+        assert!(users
+            .iter()
+            .any(|(column, value)| column == "phone" && value != column));
+        for table in ["users", "cards"] {
+            let rows = count(table).await?;
+
+            assert!(insert_throughput(&pool, table, 3).await? > 0.0);
+            assert_eq!(count(table).await?, rows);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,379 @@
+use super::explain::{explain_analyze, PlanNode, QueryPlan};
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+use std::fmt;
+
+// A node whose actual rows are off from the estimate by more than this factor is reported.
+const ROW_ESTIMATE_ERROR_FACTOR: f64 = 10.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PlanSet {
+    pub plans: Vec<(String, QueryPlan)>,
+}
+
+impl PlanSet {
+    /// Captures an `EXPLAIN ANALYZE` plan for each `(name, sql)` pair, in order.
+    pub async fn capture(
+        db: &Pool<Postgres>,
+        queries: &[(&str, &str)],
+    ) -> Result<Self, sqlx::Error> {
+        let mut plans = Vec::with_capacity(queries.len());
+        for (name, sql) in queries {
+            plans.push((name.to_string(), explain_analyze(db, sql).await?));
+        }
+
+        Ok(Self { plans })
+    }
+
+    fn get(&self, name: &str) -> Option<&QueryPlan> {
+        self.plans
+            .iter()
+            .find(|(plan_name, _)| plan_name == name)
+            .map(|(_, plan)| plan)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NodeChange {
+    /// Child indexes from the root, e.g. `0.1` is the second child of the root's first child.
+    pub path: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RowEstimateError {
+    pub path: String,
+    pub node: String,
+    pub estimated_rows: f64,
+    pub actual_rows: f64,
+    pub ratio: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QueryPlanDiff {
+    pub query: String,
+    pub node_changes: Vec<NodeChange>,
+    pub total_cost_before: f64,
+    pub total_cost_after: f64,
+    pub execution_time_ms_before: Option<f64>,
+    pub execution_time_ms_after: Option<f64>,
+    pub shared_hit_blocks_delta: i64,
+    pub shared_read_blocks_delta: i64,
+    pub row_estimate_errors_before: Vec<RowEstimateError>,
+    pub row_estimate_errors_after: Vec<RowEstimateError>,
+}
+
+impl QueryPlanDiff {
+    pub fn between(query: &str, before: &QueryPlan, after: &QueryPlan) -> Self {
+        let mut node_changes = Vec::new();
+        diff_nodes(
+            Some(&before.root),
+            Some(&after.root),
+            String::from("0"),
+            &mut node_changes,
+        );
+
+        Self {
+            query: query.to_string(),
+            node_changes,
+            total_cost_before: before.root.total_cost,
+            total_cost_after: after.root.total_cost,
+            execution_time_ms_before: before.execution_time_ms,
+            execution_time_ms_after: after.execution_time_ms,
+            shared_hit_blocks_delta: after.root.shared_hit_blocks.unwrap_or(0)
+                - before.root.shared_hit_blocks.unwrap_or(0),
+            shared_read_blocks_delta: after.root.shared_read_blocks.unwrap_or(0)
+                - before.root.shared_read_blocks.unwrap_or(0),
+            row_estimate_errors_before: row_estimate_errors(&before.root),
+            row_estimate_errors_after: row_estimate_errors(&after.root),
+        }
+    }
+
+    pub fn plan_changed(&self) -> bool {
+        !self.node_changes.is_empty()
+    }
+
+    pub fn total_cost_delta(&self) -> f64 {
+        self.total_cost_after - self.total_cost_before
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PlanDiff {
+    pub queries: Vec<QueryPlanDiff>,
+    /// Queries captured on only one side, which cannot be compared.
+    pub unmatched: Vec<String>,
+}
+
+impl PlanDiff {
+    pub fn between(before: &PlanSet, after: &PlanSet) -> Self {
+        let mut queries = Vec::new();
+        let mut unmatched = Vec::new();
+        for (name, before_plan) in &before.plans {
+            match after.get(name) {
+                Some(after_plan) => {
+                    queries.push(QueryPlanDiff::between(name, before_plan, after_plan))
+                }
+                None => unmatched.push(name.clone()),
+            }
+        }
+        for (name, _) in &after.plans {
+            if before.get(name).is_none() {
+                unmatched.push(name.clone());
+            }
+        }
+
+        Self { queries, unmatched }
+    }
+
+    pub fn changed(&self) -> Vec<&QueryPlanDiff> {
+        self.queries
+            .iter()
+            .filter(|query| query.plan_changed())
+            .collect()
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Error: failed to serialize plan diff - <error={:?}>", e))
+    }
+}
+
+impl fmt::Display for PlanDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for query in &self.queries {
+            writeln!(
+                f,
+                "{} - cost {:.2} -> {:.2} ({:+.2}), shared hit {:+}, shared read {:+}",
+                query.query,
+                query.total_cost_before,
+                query.total_cost_after,
+                query.total_cost_delta(),
+                query.shared_hit_blocks_delta,
+                query.shared_read_blocks_delta
+            )?;
+            for change in &query.node_changes {
+                writeln!(
+                    f,
+                    "  [{}] {} -> {}",
+                    change.path,
+                    change.before.as_deref().unwrap_or("(none)"),
+                    change.after.as_deref().unwrap_or("(none)")
+                )?;
+            }
+            for error in &query.row_estimate_errors_after {
+                writeln!(
+                    f,
+                    "  [{}] {} estimated {} rows, got {} ({:.1}x)",
+                    error.path, error.node, error.estimated_rows, error.actual_rows, error.ratio
+                )?;
+            }
+        }
+        for name in &self.unmatched {
+            writeln!(f, "{} - only captured on one side", name)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn diff_nodes(
+    before: Option<&PlanNode>,
+    after: Option<&PlanNode>,
+    path: String,
+    changes: &mut Vec<NodeChange>,
+) {
+    let before_label = before.map(PlanNode::label);
+    let after_label = after.map(PlanNode::label);
+    if before_label != after_label {
+        changes.push(NodeChange {
+            path: path.clone(),
+            before: before_label,
+            after: after_label,
+        });
+    }
+
+    let before_children = before.map(|node| node.children.as_slice()).unwrap_or(&[]);
+    let after_children = after.map(|node| node.children.as_slice()).unwrap_or(&[]);
+    for i in 0..before_children.len().max(after_children.len()) {
+        diff_nodes(
+            before_children.get(i),
+            after_children.get(i),
+            format!("{}.{}", path, i),
+            changes,
+        );
+    }
+}
+
+fn row_estimate_errors(root: &PlanNode) -> Vec<RowEstimateError> {
+    let mut errors = Vec::new();
+    collect_row_estimate_errors(root, String::from("0"), &mut errors);
+    errors
+}
+
+fn collect_row_estimate_errors(node: &PlanNode, path: String, errors: &mut Vec<RowEstimateError>) {
+    if let (Some(ratio), Some(actual_rows)) = (node.row_estimate_ratio(), node.actual_rows) {
+        if !(1.0 / ROW_ESTIMATE_ERROR_FACTOR..=ROW_ESTIMATE_ERROR_FACTOR).contains(&ratio) {
+            errors.push(RowEstimateError {
+                path: path.clone(),
+                node: node.label(),
+                estimated_rows: node.estimated_rows,
+                actual_rows,
+                ratio,
+            });
+        }
+    }
+    for (i, child) in node.children.iter().enumerate() {
+        collect_row_estimate_errors(child, format!("{}.{}", path, i), errors);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::enums::sample_query::SampleQuery;
+    use crate::optimisations::BankSystemManager;
+    use sqlx::PgPool;
+
+    fn node(node_type: &str, index_name: Option<&str>, children: Vec<PlanNode>) -> PlanNode {
+        PlanNode {
+            node_type: node_type.to_string(),
+            relation_name: Some(String::from("transactions")),
+            index_name: index_name.map(str::to_string),
+            startup_cost: 0.0,
+            total_cost: 10.0,
+            estimated_rows: 10.0,
+            actual_rows: Some(10.0),
+            actual_loops: Some(1.0),
+            actual_startup_time_ms: None,
+            actual_total_time_ms: None,
+            shared_hit_blocks: Some(5),
+            shared_read_blocks: Some(1),
+            children,
+        }
+    }
+
+    fn plan(root: PlanNode) -> QueryPlan {
+        QueryPlan {
+            root,
+            planning_time_ms: Some(0.1),
+            execution_time_ms: Some(1.0),
+        }
+    }
+
+    #[test]
+    fn test_it_reports_swapped_node_types() {
+        let before = plan(node(
+            "Aggregate",
+            None,
+            vec![node("Seq Scan", None, vec![])],
+        ));
+        let mut after_root = node(
+            "Aggregate",
+            None,
+            vec![node("Index Scan", Some("idx_transactions_amount"), vec![])],
+        );
+        after_root.total_cost = 4.0;
+        after_root.shared_read_blocks = Some(0);
+        let after = plan(after_root);
+
+        let diff = QueryPlanDiff::between("query", &before, &after);
+
+        assert!(diff.plan_changed());
+        assert_eq!(
+            diff.node_changes,
+            vec![NodeChange {
+                path: String::from("0.0"),
+                before: Some(String::from("Seq Scan on transactions")),
+                after: Some(String::from(
+                    "Index Scan using idx_transactions_amount on transactions"
+                )),
+            }]
+        );
+        assert_eq!(diff.total_cost_delta(), -6.0);
+        assert_eq!(diff.shared_read_blocks_delta, -1);
+    }
+
+    #[test]
+    fn test_it_reports_added_and_removed_nodes() {
+        let before = plan(node(
+            "Hash Join",
+            None,
+            vec![node("Seq Scan", None, vec![])],
+        ));
+        let after = plan(node(
+            "Hash Join",
+            None,
+            vec![node("Seq Scan", None, vec![]), node("Hash", None, vec![])],
+        ));
+
+        let diff = QueryPlanDiff::between("query", &before, &after);
+
+        assert_eq!(diff.node_changes.len(), 1);
+        assert_eq!(diff.node_changes[0].path, "0.1");
+        assert_eq!(diff.node_changes[0].before, None);
+    }
+
+    #[test]
+    fn test_it_reports_row_estimate_errors() {
+        let mut misestimated = node("Seq Scan", None, vec![]);
+        misestimated.actual_rows = Some(500.0);
+
+        let errors = row_estimate_errors(&node("Aggregate", None, vec![misestimated]));
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].path, "0.0");
+        assert_eq!(errors[0].ratio, 50.0);
+    }
+
+    #[test]
+    fn test_it_reports_unmatched_queries() {
+        let before = PlanSet {
+            plans: vec![(String::from("a"), plan(node("Seq Scan", None, vec![])))],
+        };
+        let after = PlanSet {
+            plans: vec![(String::from("b"), plan(node("Seq Scan", None, vec![])))],
+        };
+
+        let diff = PlanDiff::between(&before, &after);
+
+        assert!(diff.queries.is_empty());
+        assert_eq!(diff.unmatched, vec![String::from("a"), String::from("b")]);
+        assert!(diff.to_json().unwrap().contains("\"unmatched\""));
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/audit_logs.sql",
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/cards.sql",
+        "../../db/schema/transfers.sql",
+        "../../db/schema/transactions.sql",
+        "../../db/schema/loans.sql",
+        "../../db/schema/payments.sql",
+    ))]
+    async fn test_it_diffs_plans_around_indices(pool: PgPool) -> sqlx::Result<()> {
+        let bank_system_manager = BankSystemManager::new(pool.clone());
+        bank_system_manager.insert_data().await;
+        let queries: Vec<(&str, &str)> = SampleQuery::all()
+            .iter()
+            .map(|query| (query.to_string(), query.sql()))
+            .collect();
+
+        let before = PlanSet::capture(&pool, &queries).await?;
+        sqlx::raw_sql(include_str!("../../db/indices.sql"))
+            .execute(&pool)
+            .await?;
+        sqlx::raw_sql("ANALYZE").execute(&pool).await?;
+        let after = PlanSet::capture(&pool, &queries).await?;
+
+        let diff = PlanDiff::between(&before, &after);
+
+        assert_eq!(diff.queries.len(), queries.len());
+        assert!(diff.unmatched.is_empty());
+        println!("{}", diff);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,336 @@
+use super::Benchmark;
+use crate::enums::materialized_view::MaterializedView;
+use crate::enums::refresh_mode::RefreshMode;
+use crate::enums::sample_query::SampleQuery;
+use crate::indexing::view_definition;
+use crate::optimisations::BankSystemManager;
+use crate::views::scheduler::{RefreshScheduler, SchedulerConfig};
+use sqlx::{Pool, Postgres};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScalingPoint {
+    pub num_users: i32,
+    pub median: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryScaling {
+    pub query: String,
+    pub points: Vec<ScalingPoint>,
+}
+
+impl QueryScaling {
+    /// Least squares slope of log(median) against log(num_users); 1.0 is linear growth.
+    pub fn exponent(&self) -> Option<f64> {
+        let points: Vec<(f64, f64)> = self
+            .points
+            .iter()
+            .filter(|point| point.num_users > 0 && !point.median.is_zero())
+            .map(|point| {
+                (
+                    (point.num_users as f64).ln(),
+                    point.median.as_secs_f64().ln(),
+                )
+            })
+            .collect();
+        if points.len() < 2 {
+            return None;
+        }
+
+        let n = points.len() as f64;
+        let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+        let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+        let covariance: f64 = points
+            .iter()
+            .map(|(x, y)| (x - mean_x) * (y - mean_y))
+            .sum();
+        let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+        if variance == 0.0 {
+            return None;
+        }
+        Some(covariance / variance)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViewScalingPoint {
+    pub num_users: i32,
+    pub raw_sql: Duration,
+    pub materialized_view: Duration,
+    pub refresh: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViewScaling {
+    pub view: MaterializedView,
+    pub points: Vec<ViewScalingPoint>,
+}
+
+impl ViewScaling {
+    /// The smallest size from which reading the view beats the raw query at every larger size.
+    pub fn crossover(&self) -> Option<i32> {
+        let mut crossover = None;
+        for point in self.points.iter().rev() {
+            if point.materialized_view >= point.raw_sql {
+                break;
+            }
+            crossover = Some(point.num_users);
+        }
+        crossover
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScalingReport {
+    /// How long seeding took at each size.
+    pub seeding: Vec<(i32, Duration)>,
+    pub queries: Vec<QueryScaling>,
+    pub views: Vec<ViewScaling>,
+}
+
+impl fmt::Display for ScalingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (num_users, duration) in &self.seeding {
+            writeln!(f, "seeded {} users in {:?}", num_users, duration)?;
+        }
+        for query in &self.queries {
+            let medians: Vec<String> = query
+                .points
+                .iter()
+                .map(|point| format!("{}: {:?}", point.num_users, point.median))
+                .collect();
+            writeln!(
+                f,
+                "{} - exponent {} - {}",
+                query.query,
+                query
+                    .exponent()
+                    .map(|exponent| format!("{:.2}", exponent))
+                    .unwrap_or_else(|| String::from("n/a")),
+                medians.join(", ")
+            )?;
+        }
+        for view in &self.views {
+            writeln!(
+                f,
+                "{} - crossover at {}",
+                view.view.to_string(),
+                view.crossover()
+                    .map(|num_users| format!("{} users", num_users))
+                    .unwrap_or_else(|| String::from("none"))
+            )?;
+            for point in &view.points {
+                writeln!(
+                    f,
+                    "  {} users - raw {:?}, materialized {:?}, refresh {:?}",
+                    point.num_users, point.raw_sql, point.materialized_view, point.refresh
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Reseeds the database at each size and times the sample queries and materialized view reads.
+///
+/// Every table is truncated between sizes, so only run this against a scratch database.
+pub struct ScalingExperiment {
+    db: Pool<Postgres>,
+    benchmark: Benchmark,
+    sizes: Vec<i32>,
+}
+
+impl ScalingExperiment {
+    pub fn new(db: Pool<Postgres>, benchmark: Benchmark, sizes: Vec<i32>) -> Self {
+        Self {
+            db,
+            benchmark,
+            sizes,
+        }
+    }
+
+    pub async fn run(&self) -> Result<ScalingReport, sqlx::Error> {
+        let mut report = ScalingReport {
+            seeding: Vec::new(),
+            queries: SampleQuery::all()
+                .iter()
+                .map(|query| QueryScaling {
+                    query: query.to_string().to_string(),
+                    points: Vec::new(),
+                })
+                .collect(),
+            views: Vec::new(),
+        };
+        let mut views = Vec::new();
+        for view in MaterializedView::all() {
+            if let Some(definition) = view_definition(&self.db, view).await? {
+                views.push((view, definition));
+                report.views.push(ViewScaling {
+                    view,
+                    points: Vec::new(),
+                });
+            }
+        }
+
+        let mut scheduler =
+            RefreshScheduler::new(self.db.clone(), SchedulerConfig::new(RefreshMode::Blocking))
+                .await?;
+
+        for &num_users in &self.sizes {
+            sqlx::raw_sql(
+                "
+                TRUNCATE public.users, public.accounts, public.cards, public.transfers,
+                    public.transactions, public.loans, public.payments, public.audit_logs
+                RESTART IDENTITY CASCADE;
+                ",
+            )
+            .execute(&self.db)
+            .await?;
+            let start = Instant::now();
+            BankSystemManager::with_num_users(self.db.clone(), num_users)
+                .bulk_insert_data()
+                .await?;
+            report.seeding.push((num_users, start.elapsed()));
+
+            scheduler.refresh_all().await?;
+            let refreshes: Vec<Duration> = views
+                .iter()
+                .map(|(view, _)| {
+                    scheduler
+                        .last_refresh(*view)
+                        .map(|last| last.duration)
+                        .unwrap_or_default()
+                })
+                .collect();
+            sqlx::raw_sql("ANALYZE").execute(&self.db).await?;
+
+            for (query, scaling) in SampleQuery::all().iter().zip(&mut report.queries) {
+                let result = self
+                    .benchmark
+                    .run(&self.db, query.to_string(), query.sql())
+                    .await?;
+                scaling.points.push(ScalingPoint {
+                    num_users,
+                    median: result.stats.median,
+                });
+            }
+
+            for (((view, definition), scaling), refresh) in
+                views.iter().zip(&mut report.views).zip(refreshes)
+            {
+                let raw_sql = self
+                    .benchmark
+                    .run(&self.db, view.to_string(), definition)
+                    .await?;
+                let materialized_view = self
+                    .benchmark
+                    .run(
+                        &self.db,
+                        view.to_string(),
+                        &format!("SELECT * FROM public.{}", view.to_string()),
+                    )
+                    .await?;
+                scaling.points.push(ViewScalingPoint {
+                    num_users,
+                    raw_sql: raw_sql.stats.median,
+                    materialized_view: materialized_view.stats.median,
+                    refresh,
+                });
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sqlx::PgPool;
+
+    fn view_point(num_users: i32, raw_ms: u64, materialized_ms: u64) -> ViewScalingPoint {
+        ViewScalingPoint {
+            num_users,
+            raw_sql: Duration::from_millis(raw_ms),
+            materialized_view: Duration::from_millis(materialized_ms),
+            refresh: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn test_crossover_is_where_the_view_stays_faster() {
+        let view = ViewScaling {
+            view: MaterializedView::LoansOutstanding,
+            points: vec![
+                view_point(100, 1, 2),
+                view_point(1_000, 3, 2),
+                view_point(10_000, 5, 6),
+                view_point(100_000, 50, 8),
+                view_point(1_000_000, 500, 70),
+            ],
+        };
+
+        assert_eq!(view.crossover(), Some(100_000));
+
+        let never = ViewScaling {
+            view: MaterializedView::LoansOutstanding,
+            points: vec![view_point(100, 1, 2)],
+        };
+        assert_eq!(never.crossover(), None);
+    }
+
+    #[test]
+    fn test_exponent_of_linear_growth() {
+        let query = QueryScaling {
+            query: String::from("query"),
+            points: [100, 1_000, 10_000]
+                .into_iter()
+                .map(|num_users| ScalingPoint {
+                    num_users,
+                    median: Duration::from_micros(num_users as u64 * 3),
+                })
+                .collect(),
+        };
+
+        assert_eq!(query.exponent().map(|exponent| exponent.round()), Some(1.0));
+        assert_eq!(
+            QueryScaling {
+                query: String::from("query"),
+                points: Vec::new(),
+            }
+            .exponent(),
+            None
+        );
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/audit_logs.sql",
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/cards.sql",
+        "../../db/schema/transfers.sql",
+        "../../db/schema/transactions.sql",
+        "../../db/schema/loans.sql",
+        "../../db/schema/payments.sql",
+        "../../db/views/average_transaction_amount.sql",
+        "../../db/views/loans_outstanding.sql",
+        "../../db/views/suspicious_transactions.sql",
+    ))]
+    async fn test_it_measures_queries_at_each_size(pool: PgPool) -> sqlx::Result<()> {
+        let experiment = ScalingExperiment::new(pool.clone(), Benchmark::new(1, 5), vec![10, 100]);
+
+        let report = experiment.run().await?;
+
+        assert_eq!(report.seeding.len(), 2);
+        assert_eq!(report.queries.len(), SampleQuery::all().len());
+        assert!(report.queries.iter().all(|query| query.points.len() == 2));
+        assert_eq!(report.views.len(), 3);
+        assert!(report.views.iter().all(|view| view.points.len() == 2));
+        println!("{}", report);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,228 @@
+use serde::Serialize;
+use sqlx::{Pool, Postgres, Row};
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatementStats {
+    /// Normalised statement text, with constants replaced by `$n` placeholders.
+    pub query: String,
+    pub calls: i64,
+    pub total_time_ms: f64,
+    pub mean_time_ms: f64,
+    pub rows: i64,
+    pub shared_blks_hit: i64,
+    pub shared_blks_read: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StatementOrder {
+    TotalTime,
+    MeanTime,
+    Calls,
+    Rows,
+    SharedBlksHit,
+    SharedBlksRead,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatementReport {
+    pub statements: Vec<StatementStats>,
+}
+
+impl StatementReport {
+    /// The `n` statements ranked highest by `order`.
+    pub fn top(&self, order: StatementOrder, n: usize) -> Vec<&StatementStats> {
+        let mut statements: Vec<&StatementStats> = self.statements.iter().collect();
+        statements.sort_by(|a, b| compare(b, a, order));
+        statements.truncate(n);
+        statements
+    }
+
+    pub fn total_calls(&self) -> i64 {
+        self.statements
+            .iter()
+            .map(|statement| statement.calls)
+            .sum()
+    }
+}
+
+fn compare(a: &StatementStats, b: &StatementStats, order: StatementOrder) -> Ordering {
+    match order {
+        StatementOrder::TotalTime => a.total_time_ms.total_cmp(&b.total_time_ms),
+        StatementOrder::MeanTime => a.mean_time_ms.total_cmp(&b.mean_time_ms),
+        StatementOrder::Calls => a.calls.cmp(&b.calls),
+        StatementOrder::Rows => a.rows.cmp(&b.rows),
+        StatementOrder::SharedBlksHit => a.shared_blks_hit.cmp(&b.shared_blks_hit),
+        StatementOrder::SharedBlksRead => a.shared_blks_read.cmp(&b.shared_blks_read),
+    }
+}
+
+/// Resets and reads `pg_stat_statements` for the current database only.
+///
+/// Needs the server started with `shared_preload_libraries = 'pg_stat_statements'`.
+pub struct StatementTracker {
+    db: Pool<Postgres>,
+}
+
+impl StatementTracker {
+    pub fn new(db: Pool<Postgres>) -> Self {
+        Self { db }
+    }
+
+    /// Whether the server preloads the library, creating the extension in this database if so.
+    pub async fn available(&self) -> Result<bool, sqlx::Error> {
+        let preloaded: String =
+            sqlx::query("SELECT current_setting('shared_preload_libraries') AS libraries")
+                .fetch_one(&self.db)
+                .await?
+                .get("libraries");
+        if !preloaded.contains("pg_stat_statements") {
+            return Ok(false);
+        }
+        sqlx::raw_sql("CREATE EXTENSION IF NOT EXISTS pg_stat_statements")
+            .execute(&self.db)
+            .await?;
+
+        Ok(true)
+    }
+
+    pub async fn reset(&self) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "
+            SELECT pg_stat_statements_reset(0, d.oid, 0)
+            FROM pg_database d
+            WHERE d.datname = current_database();
+            ",
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Every statement recorded since the last reset, excluding the tracker's own queries.
+    pub async fn report(&self) -> Result<StatementReport, sqlx::Error> {
+        let statements = sqlx::query(
+            "
+            SELECT
+                s.query,
+                s.calls,
+                s.total_exec_time AS total_time_ms,
+                s.mean_exec_time AS mean_time_ms,
+                s.rows,
+                s.shared_blks_hit,
+                s.shared_blks_read
+            FROM pg_stat_statements s
+            JOIN pg_database d ON d.oid = s.dbid AND d.datname = current_database()
+            WHERE s.query NOT LIKE '%pg_stat_statements%'
+            ORDER BY s.total_exec_time DESC;
+            ",
+        )
+        .fetch_all(&self.db)
+        .await?
+        .iter()
+        .map(|row| StatementStats {
+            query: row.get("query"),
+            calls: row.get("calls"),
+            total_time_ms: row.get("total_time_ms"),
+            mean_time_ms: row.get("mean_time_ms"),
+            rows: row.get("rows"),
+            shared_blks_hit: row.get("shared_blks_hit"),
+            shared_blks_read: row.get("shared_blks_read"),
+        })
+        .collect();
+
+        Ok(StatementReport { statements })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::enums::sample_query::SampleQuery;
+    use crate::optimisations::BankSystemManager;
+    use sqlx::PgPool;
+
+    fn statement(query: &str, calls: i64, total_time_ms: f64) -> StatementStats {
+        StatementStats {
+            query: query.to_string(),
+            calls,
+            total_time_ms,
+            mean_time_ms: total_time_ms / calls as f64,
+            rows: calls,
+            shared_blks_hit: 0,
+            shared_blks_read: 0,
+        }
+    }
+
+    #[test]
+    fn test_it_ranks_top_statements() {
+        let report = StatementReport {
+            statements: vec![
+                statement("a", 10, 5.0),
+                statement("b", 1, 8.0),
+                statement("c", 100, 2.0),
+            ],
+        };
+
+        let by_total: Vec<&str> = report
+            .top(StatementOrder::TotalTime, 2)
+            .iter()
+            .map(|statement| statement.query.as_str())
+            .collect();
+        let by_mean = report.top(StatementOrder::MeanTime, 1);
+        let by_calls = report.top(StatementOrder::Calls, 1);
+
+        assert_eq!(by_total, vec!["b", "a"]);
+        assert_eq!(by_mean[0].query, "b");
+        assert_eq!(by_calls[0].query, "c");
+        assert_eq!(report.total_calls(), 111);
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/audit_logs.sql",
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/cards.sql",
+        "../../db/schema/transfers.sql",
+        "../../db/schema/transactions.sql",
+        "../../db/schema/loans.sql",
+        "../../db/schema/payments.sql",
+    ))]
+    async fn test_it_reports_statements_issued_since_reset(pool: PgPool) -> sqlx::Result<()> {
+        let tracker = StatementTracker::new(pool.clone());
+        if !tracker.available().await? {
+            println!("pg_stat_statements is not preloaded, skipping");
+            return Ok(());
+        }
+        let bank_system_manager = BankSystemManager::new(pool.clone());
+        bank_system_manager.insert_data().await;
+
+        tracker.reset().await?;
+        for _ in 0..3 {
+            sqlx::query(SampleQuery::TotalBalances.sql())
+                .fetch_all(&pool)
+                .await?;
+        }
+        let report = tracker.report().await?;
+
+        let total_balances = report
+            .statements
+            .iter()
+            .find(|statement| statement.query.contains("total_balance"))
+            .expect("Error: sample query was not recorded");
+        assert_eq!(total_balances.calls, 3);
+        assert!(report
+            .statements
+            .iter()
+            .all(|statement| !statement.query.contains("INSERT")));
+        for statement in report.top(StatementOrder::TotalTime, 5) {
+            println!(
+                "{:.3} ms total, {} calls - {}",
+                statement.total_time_ms, statement.calls, statement.query
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,359 @@
+use super::{CacheEntry, FinanceCache};
+use chrono::Utc;
+use rand::seq::SliceRandom;
+use sqlx::{Pool, Postgres, Row};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+// Cached values are f32 so anything below a cent is rounding, not staleness.
+const DRIFT_TOLERANCE: f32 = 0.005;
+
+// Upper bounds (inclusive) of the drift buckets, in currency units.
+const DRIFT_BUCKETS: [f64; 5] = [0.01, 1.0, 100.0, 10_000.0, 1_000_000.0];
+
+// Upper bounds (inclusive) of the age buckets, in seconds.
+const AGE_BUCKETS: [f64; 6] = [60.0, 600.0, 1_800.0, 3_600.0, 43_200.0, 86_400.0];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistogramBucket {
+    /// `None` is the overflow bucket holding everything above the last bound.
+    pub upper_bound: Option<f64>,
+    pub sampled: usize,
+    pub divergent: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    pub buckets: Vec<HistogramBucket>,
+}
+
+impl Histogram {
+    fn new(bounds: &[f64]) -> Self {
+        let mut buckets: Vec<HistogramBucket> = bounds
+            .iter()
+            .map(|bound| HistogramBucket {
+                upper_bound: Some(*bound),
+                sampled: 0,
+                divergent: 0,
+            })
+            .collect();
+        buckets.push(HistogramBucket {
+            upper_bound: None,
+            sampled: 0,
+            divergent: 0,
+        });
+        Self { buckets }
+    }
+
+    fn record(&mut self, value: f64, is_divergent: bool) {
+        let bucket = self
+            .buckets
+            .iter_mut()
+            .find(|bucket| bucket.upper_bound.is_none_or(|bound| value <= bound))
+            .expect("overflow bucket always matches");
+        bucket.sampled += 1;
+        if is_divergent {
+            bucket.divergent += 1;
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheAuditReport {
+    pub cache: &'static str,
+    pub entries: usize,
+    pub sampled: usize,
+    pub divergent: usize,
+    /// Cached ids whose row no longer exists in the database.
+    pub missing: usize,
+    pub invalidated: usize,
+    /// How far off divergent entries were from the database.
+    pub drift: Histogram,
+    /// How old every sampled entry was, with the divergent ones counted per bucket.
+    pub age_seconds: Histogram,
+}
+
+impl CacheAuditReport {
+    fn new(cache: &'static str, entries: usize) -> Self {
+        Self {
+            cache,
+            entries,
+            sampled: 0,
+            divergent: 0,
+            missing: 0,
+            invalidated: 0,
+            drift: Histogram::new(&DRIFT_BUCKETS),
+            age_seconds: Histogram::new(&AGE_BUCKETS),
+        }
+    }
+
+    pub fn divergence_rate(&self) -> f64 {
+        if self.sampled == 0 {
+            return 0.0;
+        }
+        self.divergent as f64 / self.sampled as f64
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditReport {
+    pub account_balance: CacheAuditReport,
+    pub user_outstanding_loans: CacheAuditReport,
+}
+
+pub struct CacheAuditor {
+    db: Pool<Postgres>,
+    sample_size: usize,
+    auto_invalidate: bool,
+}
+
+impl CacheAuditor {
+    pub fn new(db: Pool<Postgres>, sample_size: usize, auto_invalidate: bool) -> Self {
+        Self {
+            db,
+            sample_size,
+            auto_invalidate,
+        }
+    }
+
+    pub async fn audit(&self, cache: &FinanceCache) -> Result<AuditReport, sqlx::Error> {
+        Ok(AuditReport {
+            account_balance: self.audit_account_balance(cache).await?,
+            user_outstanding_loans: self.audit_user_outstanding_loans(cache).await?,
+        })
+    }
+
+    /// Runs an audit every `interval`, sending each outcome to `reports`, until the returned
+    /// handle is aborted or the receiver is dropped.
+    pub fn spawn(
+        self,
+        cache: Arc<FinanceCache>,
+        interval: Duration,
+        reports: mpsc::Sender<Result<AuditReport, sqlx::Error>>,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if reports.send(self.audit(&cache).await).await.is_err() {
+                    break;
+                }
+            }
+        })
+    }
+
+    async fn audit_account_balance(
+        &self,
+        cache: &FinanceCache,
+    ) -> Result<CacheAuditReport, sqlx::Error> {
+        let entries = cache.account_balance_entries();
+        let mut report = CacheAuditReport::new("account_balance", entries.len());
+
+        for (account_id, entry) in self.sample(entries) {
+            let balance =
+                sqlx::query("SELECT balance::FLOAT8 AS balance FROM public.accounts WHERE id = $1")
+                    .bind(account_id as i32)
+                    .fetch_optional(&self.db)
+                    .await?
+                    .map(|row| row.get::<f64, _>("balance"));
+
+            // The entry may have expired since it was sampled, which is fine.
+            if self.record(&mut report, &entry, balance)
+                && self.auto_invalidate
+                && cache.invalidate_account_balance(&account_id).await.is_ok()
+            {
+                report.invalidated += 1;
+            }
+        }
+
+        Ok(report)
+    }
+
+    async fn audit_user_outstanding_loans(
+        &self,
+        cache: &FinanceCache,
+    ) -> Result<CacheAuditReport, sqlx::Error> {
+        let entries = cache.user_outstanding_loans_entries();
+        let mut report = CacheAuditReport::new("user_outstanding_loans", entries.len());
+
+        for (user_id, entry) in self.sample(entries) {
+            let loans = sqlx::query(
+                "
+                SELECT COALESCE(SUM(amount), 0)::FLOAT8 AS loans_outstanding
+                FROM public.loans
+                WHERE user_id = $1 AND status = 'active';
+                ",
+            )
+            .bind(user_id as i32)
+            .fetch_one(&self.db)
+            .await?
+            .get::<f64, _>("loans_outstanding");
+
+            if self.record(&mut report, &entry, Some(loans))
+                && self.auto_invalidate
+                && cache
+                    .invalidate_user_outstanding_loans(&user_id)
+                    .await
+                    .is_ok()
+            {
+                report.invalidated += 1;
+            }
+        }
+
+        Ok(report)
+    }
+
+    fn sample(&self, mut entries: Vec<(u32, CacheEntry)>) -> Vec<(u32, CacheEntry)> {
+        entries.shuffle(&mut rand::rng());
+        entries.truncate(self.sample_size);
+        entries
+    }
+
+    /// Records one sampled entry against its authoritative value, returning whether it diverged.
+    fn record(
+        &self,
+        report: &mut CacheAuditReport,
+        entry: &CacheEntry,
+        authoritative: Option<f64>,
+    ) -> bool {
+        let age = Utc::now()
+            .signed_duration_since(entry.cached_at)
+            .num_milliseconds() as f64
+            / 1_000.0;
+        let drift = match authoritative {
+            Some(value) => (entry.value - value as f32).abs(),
+            None => {
+                report.missing += 1;
+                entry.value.abs()
+            }
+        };
+        let is_divergent = authoritative.is_none() || drift > DRIFT_TOLERANCE;
+
+        report.sampled += 1;
+        report.age_seconds.record(age, is_divergent);
+        if is_divergent {
+            report.divergent += 1;
+            report.drift.record(drift as f64, true);
+        }
+
+        is_divergent
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sqlx::PgPool;
+
+    #[test]
+    fn test_histogram_records_into_overflow_bucket() {
+        let mut histogram = Histogram::new(&[1.0, 10.0]);
+
+        histogram.record(0.5, false);
+        histogram.record(10.0, true);
+        histogram.record(50.0, true);
+
+        assert_eq!(histogram.buckets[0].sampled, 1);
+        assert_eq!(histogram.buckets[0].divergent, 0);
+        assert_eq!(histogram.buckets[1].divergent, 1);
+        assert_eq!(histogram.buckets[2].upper_bound, None);
+        assert_eq!(histogram.buckets[2].divergent, 1);
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/loans.sql"
+    ))]
+    async fn test_it_reports_divergent_entries(pool: PgPool) -> sqlx::Result<()> {
+        sqlx::raw_sql(
+            "
+            INSERT INTO public.users (given_name, family_name, username, email, phone)
+            VALUES ('Ada', 'Lovelace', 'ada', 'ada@example.com', '0001');
+            INSERT INTO public.accounts (user_id, account_type, balance) VALUES (1, 'checking', 100.00);
+            INSERT INTO public.accounts (user_id, account_type, balance) VALUES (1, 'savings', 250.00);
+            INSERT INTO public.loans (user_id, amount, interest_rate, term_months, status)
+            VALUES (1, 5000.00, 4.50, 24, 'active');
+            ",
+        )
+        .execute(&pool)
+        .await?;
+
+        let cache = FinanceCache::new();
+        cache.set_account_balance(1, 100.00).await;
+        cache.set_account_balance(2, 999.00).await;
+        cache.set_account_balance(3, 10.00).await;
+        cache.set_user_outstanding_loans(1, 5000.00).await;
+
+        let auditor = CacheAuditor::new(pool.clone(), 10, false);
+        let report = auditor.audit(&cache).await?;
+
+        assert_eq!(report.account_balance.sampled, 3);
+        assert_eq!(report.account_balance.divergent, 2);
+        assert_eq!(report.account_balance.missing, 1);
+        assert_eq!(report.account_balance.invalidated, 0);
+        assert_eq!(report.user_outstanding_loans.divergent, 0);
+        assert_eq!(Some(999.00), cache.get_account_balance(&2).await);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/loans.sql"
+    ))]
+    async fn test_it_invalidates_divergent_entries(pool: PgPool) -> sqlx::Result<()> {
+        sqlx::raw_sql(
+            "
+            INSERT INTO public.users (given_name, family_name, username, email, phone)
+            VALUES ('Ada', 'Lovelace', 'ada', 'ada@example.com', '0001');
+            INSERT INTO public.loans (user_id, amount, interest_rate, term_months, status)
+            VALUES (1, 5000.00, 4.50, 24, 'closed');
+            ",
+        )
+        .execute(&pool)
+        .await?;
+
+        let cache = FinanceCache::new();
+        cache.set_user_outstanding_loans(1, 5000.00).await;
+
+        let auditor = CacheAuditor::new(pool.clone(), 10, true);
+        let report = auditor.audit(&cache).await?;
+
+        assert_eq!(report.user_outstanding_loans.divergent, 1);
+        assert_eq!(report.user_outstanding_loans.invalidated, 1);
+        assert_eq!(None, cache.get_user_outstanding_loans(&1).await);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/loans.sql"
+    ))]
+    async fn test_it_sends_periodic_reports(pool: PgPool) -> sqlx::Result<()> {
+        let cache = Arc::new(FinanceCache::new());
+        cache.set_account_balance(1, 100.00).await;
+        let (sender, mut receiver) = mpsc::channel(1);
+
+        let handle = CacheAuditor::new(pool.clone(), 10, false).spawn(
+            cache,
+            Duration::from_millis(10),
+            sender,
+        );
+        let first = receiver.recv().await.unwrap()?;
+        let second = receiver.recv().await.unwrap()?;
+        drop(receiver);
+        handle.await.unwrap();
+
+        println!("Cache audit - <report={:?}>", second);
+        assert_eq!(first.account_balance.missing, 1);
+        assert_eq!(second.account_balance.missing, 1);
+
+        Ok(())
+    }
+}
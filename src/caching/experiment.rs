@@ -0,0 +1,356 @@
+use super::FinanceCache;
+use crate::benchmarking::percentile;
+use crate::benchmarking::statements::{StatementReport, StatementTracker};
+use rand::Rng;
+use sqlx::{Pool, Postgres, Row};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CacheMode {
+    Cached,
+    Uncached,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operation {
+    ReadBalance(u32),
+    WriteBalance(u32),
+}
+
+/// The workload fields are private so they can only be set through the checks in `new`.
+pub struct ExperimentConfig {
+    operations: usize,
+    /// Fraction of operations that are reads, e.g. 0.95 for a 95/5 read/write mix.
+    read_ratio: f64,
+    /// Zipf exponent for account popularity; 0.0 is uniform, larger is more skewed.
+    zipf_exponent: f64,
+    /// Account ids are drawn from `1..=num_accounts`.
+    num_accounts: u32,
+    /// Attach `pg_stat_statements` figures to each report, when the extension is preloaded.
+    pub profile_statements: bool,
+}
+
+impl ExperimentConfig {
+    pub fn new(
+        operations: usize,
+        read_ratio: f64,
+        zipf_exponent: f64,
+        num_accounts: u32,
+    ) -> Result<Self, String> {
+        if num_accounts == 0 {
+            return Err(format!(
+                "Error: cache experiment needs at least one account - <num_accounts={}>",
+                num_accounts
+            ));
+        }
+        if !(0.0..=1.0).contains(&read_ratio) {
+            return Err(format!(
+                "Error: cache experiment read ratio must be between 0 and 1 - <read_ratio={}>",
+                read_ratio
+            ));
+        }
+        if !zipf_exponent.is_finite() {
+            return Err(format!(
+                "Error: cache experiment zipf exponent must be finite - <zipf_exponent={}>",
+                zipf_exponent
+            ));
+        }
+
+        Ok(Self {
+            operations,
+            read_ratio,
+            zipf_exponent,
+            num_accounts,
+            profile_statements: false,
+        })
+    }
+
+    pub fn workload(&self) -> Vec<Operation> {
+        let sampler = ZipfSampler::new(self.num_accounts, self.zipf_exponent);
+        let mut rng = rand::rng();
+        (0..self.operations)
+            .map(|_| {
+                let account_id = sampler.sample(&mut rng);
+                if rng.random_bool(self.read_ratio) {
+                    Operation::ReadBalance(account_id)
+                } else {
+                    Operation::WriteBalance(account_id)
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExperimentReport {
+    pub mode: CacheMode,
+    pub operations: usize,
+    pub reads: usize,
+    pub writes: usize,
+    pub elapsed: Duration,
+    /// Operations per second.
+    pub throughput: f64,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub db_queries: usize,
+    pub cache_hits: usize,
+    pub statements: Option<StatementReport>,
+}
+
+pub struct CacheExperiment {
+    db: Pool<Postgres>,
+    config: ExperimentConfig,
+}
+
+impl CacheExperiment {
+    pub fn new(db: Pool<Postgres>, config: ExperimentConfig) -> Self {
+        Self { db, config }
+    }
+
+    /// Replays the same workload with and without a cache, returning `(cached, uncached)`.
+    pub async fn compare(&self) -> Result<(ExperimentReport, ExperimentReport), sqlx::Error> {
+        let workload = self.config.workload();
+        let cached = self.run(CacheMode::Cached, &workload).await?;
+        let uncached = self.run(CacheMode::Uncached, &workload).await?;
+
+        Ok((cached, uncached))
+    }
+
+    pub async fn run(
+        &self,
+        mode: CacheMode,
+        workload: &[Operation],
+    ) -> Result<ExperimentReport, sqlx::Error> {
+        let cache = FinanceCache::new();
+        let mut latencies = Vec::with_capacity(workload.len());
+        let mut reads = 0;
+        let mut db_queries = 0;
+        let mut cache_hits = 0;
+
+        let tracker = StatementTracker::new(self.db.clone());
+        let profile = self.config.profile_statements && tracker.available().await?;
+        if profile {
+            tracker.reset().await?;
+        }
+
+        let start = Instant::now();
+        for operation in workload {
+            let operation_start = Instant::now();
+            match (mode, *operation) {
+                (CacheMode::Cached, Operation::ReadBalance(account_id)) => {
+                    reads += 1;
+                    if cache.get_account_balance(&account_id).await.is_some() {
+                        cache_hits += 1;
+                    } else {
+                        db_queries += 1;
+                        if let Some(balance) = self.read_balance(account_id).await? {
+                            cache.set_account_balance(account_id, balance).await;
+                        }
+                    }
+                }
+                (CacheMode::Uncached, Operation::ReadBalance(account_id)) => {
+                    reads += 1;
+                    db_queries += 1;
+                    self.read_balance(account_id).await?;
+                }
+                (CacheMode::Cached, Operation::WriteBalance(account_id)) => {
+                    // Write-through so subsequent reads never see the old balance.
+                    db_queries += 1;
+                    if let Some(balance) = self.write_balance(account_id).await? {
+                        cache.set_account_balance(account_id, balance).await;
+                    }
+                }
+                (CacheMode::Uncached, Operation::WriteBalance(account_id)) => {
+                    db_queries += 1;
+                    self.write_balance(account_id).await?;
+                }
+            }
+            latencies.push(operation_start.elapsed());
+        }
+        let elapsed = start.elapsed();
+        let statements = if profile {
+            Some(tracker.report().await?)
+        } else {
+            None
+        };
+
+        latencies.sort();
+        Ok(ExperimentReport {
+            mode,
+            operations: workload.len(),
+            reads,
+            writes: workload.len() - reads,
+            elapsed,
+            throughput: workload.len() as f64 / elapsed.as_secs_f64(),
+            p50: percentile(&latencies, 50.0),
+            p95: percentile(&latencies, 95.0),
+            p99: percentile(&latencies, 99.0),
+            db_queries,
+            cache_hits,
+            statements,
+        })
+    }
+
+    async fn read_balance(&self, account_id: u32) -> Result<Option<f32>, sqlx::Error> {
+        Ok(
+            sqlx::query("SELECT balance::FLOAT4 AS balance FROM public.accounts WHERE id = $1")
+                .bind(account_id as i32)
+                .fetch_optional(&self.db)
+                .await?
+                .map(|row| row.get::<f32, _>("balance")),
+        )
+    }
+
+    async fn write_balance(&self, account_id: u32) -> Result<Option<f32>, sqlx::Error> {
+        Ok(sqlx::query(
+            "
+            UPDATE public.accounts
+            SET balance = balance + 1.00
+            WHERE id = $1
+            RETURNING balance::FLOAT4 AS balance;
+            ",
+        )
+        .bind(account_id as i32)
+        .fetch_optional(&self.db)
+        .await?
+        .map(|row| row.get::<f32, _>("balance")))
+    }
+}
+
+/// Samples ranks `1..=n` with probability proportional to `1 / rank^exponent`.
+struct ZipfSampler {
+    cumulative: Vec<f64>,
+}
+
+impl ZipfSampler {
+    fn new(n: u32, exponent: f64) -> Self {
+        let mut total = 0.0;
+        let cumulative = (1..=n)
+            .map(|rank| {
+                total += 1.0 / (rank as f64).powf(exponent);
+                total
+            })
+            .collect();
+        Self { cumulative }
+    }
+
+    fn sample<R: Rng>(&self, rng: &mut R) -> u32 {
+        let total = self.cumulative.last().copied().unwrap_or(0.0);
+        let target = rng.random_range(0.0..total);
+        self.cumulative.partition_point(|weight| *weight <= target) as u32 + 1
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::benchmarking::statements::StatementOrder;
+    use crate::optimisations::{BankSystemManager, NUM_ACCOUNTS_PER_USER, NUM_USERS};
+    use sqlx::PgPool;
+
+    #[test]
+    fn test_zipf_sampler_favours_low_ranks() {
+        let sampler = ZipfSampler::new(100, 1.2);
+        let mut rng = rand::rng();
+        let mut counts = [0; 100];
+        for _ in 0..10_000 {
+            counts[sampler.sample(&mut rng) as usize - 1] += 1;
+        }
+
+        assert!(counts[0] > counts[9]);
+        assert!(counts[9] > counts[99]);
+    }
+
+    #[test]
+    fn test_workload_respects_read_ratio() {
+        let config = ExperimentConfig::new(1_000, 1.0, 1.0, 10).unwrap();
+
+        let workload = config.workload();
+
+        assert_eq!(workload.len(), 1_000);
+        assert!(workload.iter().all(
+            |operation| matches!(operation, Operation::ReadBalance(id) if (1..=10).contains(id))
+        ));
+    }
+
+    #[test]
+    fn test_config_rejects_invalid_values() {
+        assert!(ExperimentConfig::new(1_000, 0.9, 1.0, 0).is_err());
+        assert!(ExperimentConfig::new(1_000, 1.5, 1.0, 10).is_err());
+        assert!(ExperimentConfig::new(1_000, -0.1, 1.0, 10).is_err());
+        assert!(ExperimentConfig::new(1_000, f64::NAN, 1.0, 10).is_err());
+        assert!(ExperimentConfig::new(1_000, 0.9, f64::NAN, 10).is_err());
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/audit_logs.sql",
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/cards.sql",
+        "../../db/schema/transfers.sql",
+        "../../db/schema/transactions.sql",
+        "../../db/schema/loans.sql",
+        "../../db/schema/payments.sql",
+    ))]
+    async fn test_cached_vs_uncached_read_write_mix(pool: PgPool) -> sqlx::Result<()> {
+        let bank_system_manager = BankSystemManager::new(pool.clone());
+        bank_system_manager.insert_data().await;
+
+        let config =
+            ExperimentConfig::new(2_000, 0.95, 1.1, (NUM_USERS * NUM_ACCOUNTS_PER_USER) as u32)
+                .unwrap();
+        let experiment = CacheExperiment::new(pool.clone(), config);
+
+        let (cached, uncached) = experiment.compare().await?;
+
+        assert_eq!(uncached.db_queries, 2_000);
+        assert!(cached.db_queries < uncached.db_queries);
+        assert_eq!(cached.db_queries + cached.cache_hits, 2_000);
+        assert_eq!(cached.statements, None);
+
+        println!("{:?}", cached);
+        println!("{:?}", uncached);
+        println!(
+            "Cached throughput {:.0} ops/s vs uncached {:.0} ops/s",
+            cached.throughput, uncached.throughput
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/audit_logs.sql",
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/cards.sql",
+        "../../db/schema/transfers.sql",
+        "../../db/schema/transactions.sql",
+        "../../db/schema/loans.sql",
+        "../../db/schema/payments.sql",
+    ))]
+    async fn test_it_attaches_statement_stats_to_reports(pool: PgPool) -> sqlx::Result<()> {
+        if !StatementTracker::new(pool.clone()).available().await? {
+            println!("pg_stat_statements is not preloaded, skipping");
+            return Ok(());
+        }
+        let bank_system_manager = BankSystemManager::new(pool.clone());
+        bank_system_manager.insert_data().await;
+
+        let mut config = ExperimentConfig::new(500, 0.9, 1.1, 100).unwrap();
+        config.profile_statements = true;
+        let experiment = CacheExperiment::new(pool.clone(), config);
+
+        let (cached, uncached) = experiment.compare().await?;
+
+        let cached_statements = cached.statements.unwrap();
+        let uncached_statements = uncached.statements.unwrap();
+        assert_eq!(cached_statements.total_calls(), cached.db_queries as i64);
+        assert_eq!(uncached_statements.total_calls(), 500);
+        for statement in uncached_statements.top(StatementOrder::TotalTime, 2) {
+            println!("{:?}", statement);
+        }
+
+        Ok(())
+    }
+}
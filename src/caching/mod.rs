@@ -1,41 +1,369 @@
+pub mod auditor;
+pub mod experiment;
+pub mod query_results;
+pub mod snapshot;
+
+use chrono::{DateTime, Utc};
 use moka::future::Cache;
-use std::time::Duration;
+use moka::Expiry;
+use sqlx::postgres::PgListener;
+use sqlx::{Pool, Postgres, Row};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+const ACCOUNT_BALANCE_TTL: Duration = Duration::from_secs(3_600); // 1 hour
+const USER_OUTSTANDING_LOANS_TTL: Duration = Duration::from_secs(86_400); // 1 day
+/// Short so an id that gets created is not reported missing for long, even without invalidation.
+const NEGATIVE_TTL: Duration = Duration::from_secs(60); // 1 min
+const ROW_CREATED_CHANNEL: &str = "row_created";
+
+/// Installs the triggers that announce new users and accounts to `listen_for_created_rows`.
+pub async fn install_row_created_triggers(db: &Pool<Postgres>) -> Result<(), sqlx::Error> {
+    sqlx::raw_sql(include_str!("../../db/triggers/row_created.sql"))
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CacheLookup {
+    Hit(f32),
+    /// The id was recently confirmed to not exist in the database.
+    Absent,
+    Miss,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct NegativeCacheMetrics {
+    /// Lookups answered by a negative entry, i.e. database queries saved.
+    pub negative_hits: u64,
+    pub negative_inserts: u64,
+    pub negative_invalidations: u64,
+    /// Lookups that had to go to the database.
+    pub db_lookups: u64,
+}
+
+#[derive(Default)]
+struct NegativeCacheCounters {
+    negative_hits: AtomicU64,
+    negative_inserts: AtomicU64,
+    negative_invalidations: AtomicU64,
+    db_lookups: AtomicU64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CacheEntry {
+    pub value: f32,
+    pub cached_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl CacheEntry {
+    fn new(value: f32, ttl: Duration) -> Self {
+        let cached_at = Utc::now();
+        Self {
+            value,
+            cached_at,
+            expires_at: cached_at + ttl,
+        }
+    }
+
+    pub fn remaining_ttl(&self) -> Option<Duration> {
+        (self.expires_at - Utc::now())
+            .to_std()
+            .ok()
+            .filter(|ttl| !ttl.is_zero())
+    }
+}
+
+/// Expires each entry at its own `expires_at`, so restored entries keep their remaining TTL.
+struct EntryExpiry;
+
+impl Expiry<u32, CacheEntry> for EntryExpiry {
+    fn expire_after_create(
+        &self,
+        _key: &u32,
+        entry: &CacheEntry,
+        _created_at: Instant,
+    ) -> Option<Duration> {
+        Some(entry.remaining_ttl().unwrap_or(Duration::ZERO))
+    }
+
+    fn expire_after_update(
+        &self,
+        _key: &u32,
+        entry: &CacheEntry,
+        _updated_at: Instant,
+        _duration_until_expiry: Option<Duration>,
+    ) -> Option<Duration> {
+        Some(entry.remaining_ttl().unwrap_or(Duration::ZERO))
+    }
+}
 
 pub struct FinanceCache {
-    account_balance: Cache<u32, f32>,
-    user_outstanding_loans: Cache<u32, f32>,
+    account_balance: Cache<u32, CacheEntry>,
+    user_outstanding_loans: Cache<u32, CacheEntry>,
+    missing_accounts: Cache<u32, ()>,
+    missing_users: Cache<u32, ()>,
+    negative_counters: NegativeCacheCounters,
 }
 
 impl FinanceCache {
     pub fn new() -> Self {
         Self {
             account_balance: Cache::builder()
-                .time_to_live(Duration::from_secs(3_600)) // 1 hour
+                .expire_after(EntryExpiry)
                 .time_to_idle(Duration::from_secs(1_800)) // 30 mins
                 .max_capacity(5000)
                 .build(),
             user_outstanding_loans: Cache::builder()
-                .time_to_live(Duration::from_secs(86_400)) // 1 day
+                .expire_after(EntryExpiry)
                 .time_to_idle(Duration::from_secs(43_200)) // 12 hours
                 .max_capacity(1000)
                 .build(),
+            missing_accounts: Cache::builder()
+                .time_to_live(NEGATIVE_TTL)
+                .max_capacity(10_000)
+                .build(),
+            missing_users: Cache::builder()
+                .time_to_live(NEGATIVE_TTL)
+                .max_capacity(10_000)
+                .build(),
+            negative_counters: NegativeCacheCounters::default(),
         }
     }
 
     pub async fn get_account_balance(&self, account_id: &u32) -> Option<f32> {
-        self.account_balance.get(account_id).await
+        self.account_balance
+            .get(account_id)
+            .await
+            .map(|entry| entry.value)
     }
 
     pub async fn get_user_outstanding_loans(&self, user_id: &u32) -> Option<f32> {
-        self.user_outstanding_loans.get(user_id).await
+        self.user_outstanding_loans
+            .get(user_id)
+            .await
+            .map(|entry| entry.value)
+    }
+
+    pub async fn lookup_account_balance(&self, account_id: &u32) -> CacheLookup {
+        if self.missing_accounts.contains_key(account_id) {
+            self.negative_counters
+                .negative_hits
+                .fetch_add(1, Ordering::Relaxed);
+            return CacheLookup::Absent;
+        }
+        match self.get_account_balance(account_id).await {
+            Some(value) => CacheLookup::Hit(value),
+            None => CacheLookup::Miss,
+        }
+    }
+
+    pub async fn lookup_user_outstanding_loans(&self, user_id: &u32) -> CacheLookup {
+        if self.missing_users.contains_key(user_id) {
+            self.negative_counters
+                .negative_hits
+                .fetch_add(1, Ordering::Relaxed);
+            return CacheLookup::Absent;
+        }
+        match self.get_user_outstanding_loans(user_id).await {
+            Some(value) => CacheLookup::Hit(value),
+            None => CacheLookup::Miss,
+        }
+    }
+
+    /// Reads an account balance through the cache, remembering ids that do not exist.
+    pub async fn load_account_balance(
+        &self,
+        db: &Pool<Postgres>,
+        account_id: u32,
+    ) -> Result<Option<f32>, sqlx::Error> {
+        match self.lookup_account_balance(&account_id).await {
+            CacheLookup::Hit(value) => return Ok(Some(value)),
+            CacheLookup::Absent => return Ok(None),
+            CacheLookup::Miss => {}
+        }
+
+        self.negative_counters
+            .db_lookups
+            .fetch_add(1, Ordering::Relaxed);
+        let balance =
+            sqlx::query("SELECT balance::FLOAT4 AS balance FROM public.accounts WHERE id = $1")
+                .bind(account_id as i32)
+                .fetch_optional(db)
+                .await?
+                .map(|row| row.get::<f32, _>("balance"));
+        match balance {
+            Some(value) => self.set_account_balance(account_id, value).await,
+            None => self.set_account_absent(account_id).await,
+        }
+
+        Ok(balance)
+    }
+
+    /// Reads a user's outstanding loans through the cache, remembering ids that do not exist.
+    pub async fn load_user_outstanding_loans(
+        &self,
+        db: &Pool<Postgres>,
+        user_id: u32,
+    ) -> Result<Option<f32>, sqlx::Error> {
+        match self.lookup_user_outstanding_loans(&user_id).await {
+            CacheLookup::Hit(value) => return Ok(Some(value)),
+            CacheLookup::Absent => return Ok(None),
+            CacheLookup::Miss => {}
+        }
+
+        self.negative_counters
+            .db_lookups
+            .fetch_add(1, Ordering::Relaxed);
+        let loans = sqlx::query(
+            "
+            SELECT COALESCE(SUM(loans.amount), 0)::FLOAT4 AS loans_outstanding
+            FROM public.users
+            LEFT JOIN public.loans ON users.id = loans.user_id AND loans.status = 'active'
+            WHERE users.id = $1
+            GROUP BY users.id;
+            ",
+        )
+        .bind(user_id as i32)
+        .fetch_optional(db)
+        .await?
+        .map(|row| row.get::<f32, _>("loans_outstanding"));
+        match loans {
+            Some(value) => self.set_user_outstanding_loans(user_id, value).await,
+            None => self.set_user_absent(user_id).await,
+        }
+
+        Ok(loans)
+    }
+
+    pub async fn set_account_absent(&self, account_id: u32) {
+        self.negative_counters
+            .negative_inserts
+            .fetch_add(1, Ordering::Relaxed);
+        self.missing_accounts.insert(account_id, ()).await
+    }
+
+    pub async fn set_user_absent(&self, user_id: u32) {
+        self.negative_counters
+            .negative_inserts
+            .fetch_add(1, Ordering::Relaxed);
+        self.missing_users.insert(user_id, ()).await
+    }
+
+    /// Stops reporting `account_id` missing, as the account now exists.
+    pub async fn record_account_created(&self, account_id: &u32) {
+        if self.missing_accounts.remove(account_id).await.is_some() {
+            self.negative_counters
+                .negative_invalidations
+                .fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Stops reporting `user_id` missing, as the user now exists.
+    pub async fn record_user_created(&self, user_id: &u32) {
+        if self.missing_users.remove(user_id).await.is_some() {
+            self.negative_counters
+                .negative_invalidations
+                .fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Records every user and account created, by any writer, until the handle is aborted.
+    /// Expects `install_row_created_triggers` to have been run.
+    pub async fn listen_for_created_rows(
+        self: Arc<Self>,
+        db: &Pool<Postgres>,
+    ) -> Result<JoinHandle<()>, sqlx::Error> {
+        let mut listener = PgListener::connect_with(db).await?;
+        listener.listen(ROW_CREATED_CHANNEL).await?;
+
+        Ok(tokio::spawn(async move {
+            loop {
+                match listener.try_recv().await {
+                    Ok(Some(notification)) => self.record_row_created(notification.payload()).await,
+                    // The listener reconnects on the next call, but anything created meanwhile
+                    // was missed, so forget every negative entry.
+                    Ok(None) => self.clear_absent(),
+                    Err(_) => {
+                        self.clear_absent();
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                    }
+                }
+            }
+        }))
+    }
+
+    async fn record_row_created(&self, payload: &str) {
+        let Some((table, id)) = payload.split_once(':') else {
+            return;
+        };
+        let Ok(id) = id.parse::<u32>() else {
+            return;
+        };
+        match table {
+            "accounts" => self.record_account_created(&id).await,
+            "users" => self.record_user_created(&id).await,
+            _ => {}
+        }
+    }
+
+    fn clear_absent(&self) {
+        self.missing_accounts.invalidate_all();
+        self.missing_users.invalidate_all();
+    }
+
+    pub fn negative_cache_metrics(&self) -> NegativeCacheMetrics {
+        NegativeCacheMetrics {
+            negative_hits: self.negative_counters.negative_hits.load(Ordering::Relaxed),
+            negative_inserts: self
+                .negative_counters
+                .negative_inserts
+                .load(Ordering::Relaxed),
+            negative_invalidations: self
+                .negative_counters
+                .negative_invalidations
+                .load(Ordering::Relaxed),
+            db_lookups: self.negative_counters.db_lookups.load(Ordering::Relaxed),
+        }
     }
 
     pub async fn set_account_balance(&self, account_id: u32, value: f32) {
-        self.account_balance.insert(account_id, value).await
+        self.record_account_created(&account_id).await;
+        self.account_balance
+            .insert(account_id, CacheEntry::new(value, ACCOUNT_BALANCE_TTL))
+            .await
     }
 
     pub async fn set_user_outstanding_loans(&self, user_id: u32, value: f32) {
-        self.user_outstanding_loans.insert(user_id, value).await
+        self.record_user_created(&user_id).await;
+        self.user_outstanding_loans
+            .insert(user_id, CacheEntry::new(value, USER_OUTSTANDING_LOANS_TTL))
+            .await
+    }
+
+    pub fn account_balance_entries(&self) -> Vec<(u32, CacheEntry)> {
+        self.account_balance
+            .iter()
+            .map(|(account_id, entry)| (*account_id, entry))
+            .collect()
+    }
+
+    pub fn user_outstanding_loans_entries(&self) -> Vec<(u32, CacheEntry)> {
+        self.user_outstanding_loans
+            .iter()
+            .map(|(user_id, entry)| (*user_id, entry))
+            .collect()
+    }
+
+    pub fn invalidate_all(&self) {
+        self.account_balance.invalidate_all();
+        self.user_outstanding_loans.invalidate_all();
+        self.missing_accounts.invalidate_all();
+        self.missing_users.invalidate_all();
     }
 
     pub async fn invalidate_account_balance(&self, account_id: &u32) -> Result<(), String> {
@@ -74,6 +402,18 @@ mod test_account_balance {
         assert_eq!(Some(1000.00), cache.get_account_balance(&1).await)
     }
 
+    #[tokio::test]
+    async fn test_it_lists_account_balance_entries() {
+        let cache = FinanceCache::new();
+        cache.set_account_balance(1, 1000.00).await;
+
+        let entries = cache.account_balance_entries();
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].0, 1);
+        assert_eq!(entries[0].1.value, 1000.00);
+    }
+
     #[tokio::test]
     async fn test_it_gets_empty_account_balance() {
         let cache = FinanceCache::new();
@@ -149,3 +489,118 @@ mod test_user_outstanding_loans {
         );
     }
 }
+
+#[cfg(test)]
+mod test_negative_caching {
+    use super::*;
+    use sqlx::PgPool;
+
+    #[tokio::test]
+    async fn test_it_distinguishes_absent_from_miss() {
+        let cache = FinanceCache::new();
+        cache.set_account_absent(1).await;
+
+        assert_eq!(CacheLookup::Absent, cache.lookup_account_balance(&1).await);
+        assert_eq!(CacheLookup::Miss, cache.lookup_account_balance(&2).await);
+    }
+
+    #[tokio::test]
+    async fn test_it_clears_absent_entry_when_account_created() {
+        let cache = FinanceCache::new();
+        cache.set_account_absent(1).await;
+
+        cache.record_account_created(&1).await;
+
+        assert_eq!(CacheLookup::Miss, cache.lookup_account_balance(&1).await);
+        assert_eq!(cache.negative_cache_metrics().negative_invalidations, 1);
+    }
+
+    #[tokio::test]
+    async fn test_it_clears_absent_entry_when_user_loans_set() {
+        let cache = FinanceCache::new();
+        cache.set_user_absent(1).await;
+
+        cache.set_user_outstanding_loans(1, 5000.00).await;
+
+        assert_eq!(
+            CacheLookup::Hit(5000.00),
+            cache.lookup_user_outstanding_loans(&1).await
+        );
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/loans.sql"
+    ))]
+    async fn test_it_saves_db_lookups_for_invalid_ids(pool: PgPool) -> sqlx::Result<()> {
+        sqlx::raw_sql(
+            "
+            INSERT INTO public.users (given_name, family_name, username, email, phone)
+            VALUES ('Ada', 'Lovelace', 'ada', 'ada@example.com', '0001');
+            INSERT INTO public.accounts (user_id, account_type, balance) VALUES (1, 'checking', 100.00);
+            ",
+        )
+        .execute(&pool)
+        .await?;
+        let cache = FinanceCache::new();
+
+        for _ in 0..10 {
+            assert_eq!(None, cache.load_account_balance(&pool, 99).await?);
+            assert_eq!(None, cache.load_user_outstanding_loans(&pool, 99).await?);
+        }
+        assert_eq!(Some(100.00), cache.load_account_balance(&pool, 1).await?);
+        assert_eq!(
+            Some(0.00),
+            cache.load_user_outstanding_loans(&pool, 1).await?
+        );
+
+        let metrics = cache.negative_cache_metrics();
+        assert_eq!(metrics.db_lookups, 4);
+        assert_eq!(metrics.negative_inserts, 2);
+        assert_eq!(metrics.negative_hits, 18);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/loans.sql"
+    ))]
+    async fn test_it_clears_absent_entries_when_rows_are_inserted(
+        pool: PgPool,
+    ) -> sqlx::Result<()> {
+        install_row_created_triggers(&pool).await?;
+        let cache = Arc::new(FinanceCache::new());
+        let listener = cache.clone().listen_for_created_rows(&pool).await?;
+        assert_eq!(None, cache.load_user_outstanding_loans(&pool, 1).await?);
+        assert_eq!(None, cache.load_account_balance(&pool, 1).await?);
+
+        sqlx::raw_sql(
+            "
+            INSERT INTO public.users (given_name, family_name, username, email, phone)
+            VALUES ('Ada', 'Lovelace', 'ada', 'ada@example.com', '0001');
+            INSERT INTO public.accounts (user_id, account_type, balance) VALUES (1, 'checking', 100.00);
+            ",
+        )
+        .execute(&pool)
+        .await?;
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while cache.negative_cache_metrics().negative_invalidations < 2 && Instant::now() < deadline
+        {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        listener.abort();
+        let _ = listener.await;
+
+        assert_eq!(cache.negative_cache_metrics().negative_invalidations, 2);
+        assert_eq!(Some(100.00), cache.load_account_balance(&pool, 1).await?);
+        assert_eq!(
+            Some(0.00),
+            cache.load_user_outstanding_loans(&pool, 1).await?
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,208 @@
+use crate::enums::audit_log_subject_table::AuditLogSubjectTable;
+use crate::enums::sample_query::SampleQuery;
+use moka::future::Cache;
+use sqlx::postgres::PgRow;
+use sqlx::{Pool, Postgres};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct QueryResultKey {
+    pub query: SampleQuery,
+    pub params: Vec<String>,
+}
+
+pub struct QueryResultCache {
+    results: Cache<QueryResultKey, Arc<Vec<PgRow>>>,
+    /// Bumped by `invalidate_table`, so a fetch that raced an invalidation is not left cached.
+    generations: Mutex<HashMap<AuditLogSubjectTable, u64>>,
+}
+
+impl Default for QueryResultCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl QueryResultCache {
+    pub fn new() -> Self {
+        Self {
+            results: Cache::builder()
+                .time_to_live(Duration::from_secs(600)) // 10 mins
+                .max_capacity(100)
+                .support_invalidation_closures()
+                .build(),
+            generations: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn get(&self, query: SampleQuery, params: &[String]) -> Option<Arc<Vec<PgRow>>> {
+        self.results
+            .get(&QueryResultKey {
+                query,
+                params: params.to_vec(),
+            })
+            .await
+    }
+
+    pub async fn set(&self, query: SampleQuery, params: Vec<String>, rows: Vec<PgRow>) {
+        self.results
+            .insert(QueryResultKey { query, params }, Arc::new(rows))
+            .await
+    }
+
+    /// Returns the cached result set for a sample query, running it against the database on a miss.
+    pub async fn get_or_fetch(
+        &self,
+        db: &Pool<Postgres>,
+        query: SampleQuery,
+    ) -> Result<Arc<Vec<PgRow>>, sqlx::Error> {
+        if let Some(rows) = self.get(query, &[]).await {
+            return Ok(rows);
+        }
+
+        let generation = self.generation(query);
+        let rows = Arc::new(sqlx::query(query.sql()).fetch_all(db).await?);
+        let key = QueryResultKey {
+            query,
+            params: Vec::new(),
+        };
+        self.results.insert(key.clone(), rows.clone()).await;
+        // An invalidation that started during the fetch has either bumped the generation by now,
+        // or registers its predicate after this insert and so removes the entry itself.
+        if self.generation(query) != generation {
+            self.results.invalidate(&key).await;
+        }
+
+        Ok(rows)
+    }
+
+    fn generation(&self, query: SampleQuery) -> Vec<u64> {
+        let generations = self.generations.lock().unwrap();
+        query
+            .dependencies()
+            .iter()
+            .map(|table| generations.get(table).copied().unwrap_or(0))
+            .collect()
+    }
+
+    /// Drops every cached result whose query reads from `table`.
+    pub fn invalidate_table(&self, table: AuditLogSubjectTable) -> Result<(), String> {
+        *self.generations.lock().unwrap().entry(table).or_insert(0) += 1;
+        self.results
+            .invalidate_entries_if(move |key, _| key.query.dependencies().contains(&table))
+            .map(|_| ())
+            .map_err(|e| {
+                format!(
+                    "Error: failed to invalidate query results for <table={}> - <error={:?}>",
+                    table.to_string(),
+                    e
+                )
+            })
+    }
+
+    pub async fn invalidate_query(&self, query: SampleQuery, params: &[String]) {
+        self.results
+            .invalidate(&QueryResultKey {
+                query,
+                params: params.to_vec(),
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sqlx::PgPool;
+
+    async fn insert_rows(pool: &PgPool) -> sqlx::Result<()> {
+        sqlx::raw_sql(
+            "
+            INSERT INTO public.users (given_name, family_name, username, email, phone)
+            VALUES ('Ada', 'Lovelace', 'ada', 'ada@example.com', '0001');
+            INSERT INTO public.accounts (user_id, account_type, balance) VALUES (1, 'checking', 100.00);
+            INSERT INTO public.cards (account_id, card_number, card_type, expiration_date)
+            VALUES (1, '4111111111111111', 'debit', '2030-01-01');
+            INSERT INTO public.loans (user_id, amount, interest_rate, term_months, status)
+            VALUES (1, 5000.00, 4.50, 24, 'active');
+            ",
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/cards.sql",
+        "../../db/schema/loans.sql"
+    ))]
+    async fn test_it_caches_query_results(pool: PgPool) -> sqlx::Result<()> {
+        insert_rows(&pool).await?;
+        let cache = QueryResultCache::new();
+
+        let rows = cache.get_or_fetch(&pool, SampleQuery::NumberCards).await?;
+
+        assert_eq!(rows.len(), 1);
+        assert!(cache.get(SampleQuery::NumberCards, &[]).await.is_some());
+        assert!(cache.get(SampleQuery::TotalLoans, &[]).await.is_none());
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/cards.sql",
+        "../../db/schema/loans.sql"
+    ))]
+    async fn test_it_invalidates_only_dependent_query_results(pool: PgPool) -> sqlx::Result<()> {
+        insert_rows(&pool).await?;
+        let cache = QueryResultCache::new();
+        cache.get_or_fetch(&pool, SampleQuery::NumberCards).await?;
+        cache.get_or_fetch(&pool, SampleQuery::TotalLoans).await?;
+
+        let number_cards = cache.generation(SampleQuery::NumberCards);
+        let total_loans = cache.generation(SampleQuery::TotalLoans);
+
+        let result = cache.invalidate_table(AuditLogSubjectTable::Cards);
+
+        assert!(result.is_ok());
+        assert_ne!(cache.generation(SampleQuery::NumberCards), number_cards);
+        assert_eq!(cache.generation(SampleQuery::TotalLoans), total_loans);
+        assert!(cache.get(SampleQuery::NumberCards, &[]).await.is_none());
+        assert!(cache.get(SampleQuery::TotalLoans, &[]).await.is_some());
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/cards.sql",
+        "../../db/schema/loans.sql"
+    ))]
+    async fn test_it_keys_results_by_params(pool: PgPool) -> sqlx::Result<()> {
+        insert_rows(&pool).await?;
+        let cache = QueryResultCache::new();
+        let rows = sqlx::query(SampleQuery::TotalBalances.sql())
+            .fetch_all(&pool)
+            .await?;
+
+        cache
+            .set(SampleQuery::TotalBalances, vec![String::from("1")], rows)
+            .await;
+
+        assert!(cache
+            .get(SampleQuery::TotalBalances, &[String::from("1")])
+            .await
+            .is_some());
+        assert!(cache.get(SampleQuery::TotalBalances, &[]).await.is_none());
+
+        Ok(())
+    }
+}
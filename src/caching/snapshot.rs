@@ -0,0 +1,297 @@
+use super::auditor::{AuditReport, CacheAuditor};
+use super::{CacheEntry, FinanceCache};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotEntry {
+    pub key: u32,
+    pub value: f32,
+    pub cached_at: DateTime<Utc>,
+    pub remaining_ttl_ms: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CacheSnapshot {
+    pub taken_at: DateTime<Utc>,
+    pub account_balance: Vec<SnapshotEntry>,
+    pub user_outstanding_loans: Vec<SnapshotEntry>,
+}
+
+/// Audits a sample of the restored entries and throws the snapshot away if too many disagree.
+pub struct SnapshotValidation {
+    pub db: Pool<Postgres>,
+    pub sample_size: usize,
+    pub max_divergence_rate: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RestoreReport {
+    pub restored: usize,
+    pub skipped_expired: usize,
+    pub audit: Option<AuditReport>,
+    /// Set when validation failed and every restored entry was invalidated again.
+    pub discarded: bool,
+}
+
+impl FinanceCache {
+    pub fn snapshot(&self) -> CacheSnapshot {
+        let taken_at = Utc::now();
+        let to_snapshot_entries = |entries: Vec<(u32, CacheEntry)>| {
+            entries
+                .into_iter()
+                .filter(|(_, entry)| entry.expires_at > taken_at)
+                .map(|(key, entry)| SnapshotEntry {
+                    key,
+                    value: entry.value,
+                    cached_at: entry.cached_at,
+                    remaining_ttl_ms: (entry.expires_at - taken_at).num_milliseconds(),
+                })
+                .collect()
+        };
+
+        CacheSnapshot {
+            taken_at,
+            account_balance: to_snapshot_entries(self.account_balance_entries()),
+            user_outstanding_loans: to_snapshot_entries(self.user_outstanding_loans_entries()),
+        }
+    }
+
+    /// Writes the live entries to `path`, returning how many were saved.
+    pub fn save_snapshot(&self, path: &Path) -> Result<usize, String> {
+        let snapshot = self.snapshot();
+        let json = serde_json::to_string(&snapshot).map_err(|e| {
+            format!(
+                "Error: failed to serialize cache snapshot - <error={:?}>",
+                e
+            )
+        })?;
+
+        // Write then rename so a crash mid-write never leaves a truncated snapshot behind.
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, json)
+            .and_then(|_| fs::rename(&tmp_path, path))
+            .map_err(|e| {
+                format!(
+                    "Error: failed to write cache snapshot - <path={}> - <error={:?}>",
+                    path.display(),
+                    e
+                )
+            })?;
+
+        Ok(snapshot.account_balance.len() + snapshot.user_outstanding_loans.len())
+    }
+
+    pub async fn restore_snapshot(
+        &self,
+        path: &Path,
+        validation: Option<&SnapshotValidation>,
+    ) -> Result<RestoreReport, String> {
+        let json = fs::read_to_string(path).map_err(|e| {
+            format!(
+                "Error: failed to read cache snapshot - <path={}> - <error={:?}>",
+                path.display(),
+                e
+            )
+        })?;
+        let snapshot: CacheSnapshot = serde_json::from_str(&json).map_err(|e| {
+            format!(
+                "Error: failed to parse cache snapshot - <path={}> - <error={:?}>",
+                path.display(),
+                e
+            )
+        })?;
+
+        let mut report = self.restore(snapshot).await;
+
+        if let Some(validation) = validation {
+            let auditor = CacheAuditor::new(validation.db.clone(), validation.sample_size, false);
+            let audit = auditor.audit(self).await.map_err(|e| {
+                format!(
+                    "Error: failed to validate cache snapshot - <path={}> - <error={:?}>",
+                    path.display(),
+                    e
+                )
+            })?;
+            if audit.account_balance.divergence_rate() > validation.max_divergence_rate
+                || audit.user_outstanding_loans.divergence_rate() > validation.max_divergence_rate
+            {
+                self.invalidate_all();
+                report.discarded = true;
+            }
+            report.audit = Some(audit);
+        }
+
+        Ok(report)
+    }
+
+    pub async fn restore(&self, snapshot: CacheSnapshot) -> RestoreReport {
+        let now = Utc::now();
+        let mut report = RestoreReport {
+            restored: 0,
+            skipped_expired: 0,
+            audit: None,
+            discarded: false,
+        };
+
+        for (cache, entries) in [
+            (&self.account_balance, snapshot.account_balance),
+            (
+                &self.user_outstanding_loans,
+                snapshot.user_outstanding_loans,
+            ),
+        ] {
+            for entry in entries {
+                // Time spent down counts against the TTL, so expiry is anchored to the snapshot.
+                let expires_at = snapshot.taken_at + Duration::milliseconds(entry.remaining_ttl_ms);
+                if expires_at <= now {
+                    report.skipped_expired += 1;
+                    continue;
+                }
+                cache
+                    .insert(
+                        entry.key,
+                        CacheEntry {
+                            value: entry.value,
+                            cached_at: entry.cached_at,
+                            expires_at,
+                        },
+                    )
+                    .await;
+                report.restored += 1;
+            }
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sqlx::PgPool;
+    use std::path::PathBuf;
+    use uuid::Uuid;
+
+    fn snapshot_path() -> PathBuf {
+        std::env::temp_dir().join(format!("finance-cache-{}.json", Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn test_it_saves_and_restores_snapshot() {
+        let path = snapshot_path();
+        let cache = FinanceCache::new();
+        cache.set_account_balance(1, 1000.00).await;
+        cache.set_user_outstanding_loans(2, 5000.00).await;
+
+        let saved = cache.save_snapshot(&path);
+        let restored_cache = FinanceCache::new();
+        let report = restored_cache.restore_snapshot(&path, None).await;
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(saved, Ok(2));
+        assert_eq!(report.unwrap().restored, 2);
+        assert_eq!(Some(1000.00), restored_cache.get_account_balance(&1).await);
+        assert_eq!(
+            Some(5000.00),
+            restored_cache.get_user_outstanding_loans(&2).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_it_keeps_remaining_ttl_on_restore() {
+        let cache = FinanceCache::new();
+        cache.set_account_balance(1, 1000.00).await;
+        let snapshot = cache.snapshot();
+        let expires_at = cache.account_balance_entries()[0].1.expires_at;
+
+        let restored_cache = FinanceCache::new();
+        restored_cache.restore(snapshot).await;
+
+        let restored_expires_at = restored_cache.account_balance_entries()[0].1.expires_at;
+        assert!((restored_expires_at - expires_at).num_milliseconds().abs() <= 1);
+    }
+
+    #[tokio::test]
+    async fn test_it_skips_expired_entries_on_restore() {
+        let taken_at = Utc::now() - Duration::hours(2);
+        let snapshot = CacheSnapshot {
+            taken_at,
+            account_balance: vec![SnapshotEntry {
+                key: 1,
+                value: 1000.00,
+                cached_at: taken_at,
+                remaining_ttl_ms: 3_600_000,
+            }],
+            user_outstanding_loans: vec![SnapshotEntry {
+                key: 1,
+                value: 5000.00,
+                cached_at: taken_at,
+                remaining_ttl_ms: 86_400_000,
+            }],
+        };
+        let cache = FinanceCache::new();
+
+        let report = cache.restore(snapshot).await;
+
+        assert_eq!(report.restored, 1);
+        assert_eq!(report.skipped_expired, 1);
+        assert_eq!(None, cache.get_account_balance(&1).await);
+        assert_eq!(Some(5000.00), cache.get_user_outstanding_loans(&1).await);
+    }
+
+    #[tokio::test]
+    async fn test_it_errors_on_missing_snapshot() {
+        let path = snapshot_path();
+        let cache = FinanceCache::new();
+
+        let result = cache.restore_snapshot(&path, None).await;
+
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .starts_with("Error: failed to read cache snapshot"));
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/loans.sql"
+    ))]
+    async fn test_it_discards_snapshot_failing_validation(pool: PgPool) -> sqlx::Result<()> {
+        sqlx::raw_sql(
+            "
+            INSERT INTO public.users (given_name, family_name, username, email, phone)
+            VALUES ('Ada', 'Lovelace', 'ada', 'ada@example.com', '0001');
+            INSERT INTO public.accounts (user_id, account_type, balance) VALUES (1, 'checking', 100.00);
+            ",
+        )
+        .execute(&pool)
+        .await?;
+        let path = snapshot_path();
+        let cache = FinanceCache::new();
+        cache.set_account_balance(1, 999.00).await;
+        let _ = cache.save_snapshot(&path);
+
+        let restored_cache = FinanceCache::new();
+        let validation = SnapshotValidation {
+            db: pool.clone(),
+            sample_size: 10,
+            max_divergence_rate: 0.1,
+        };
+        let report = restored_cache
+            .restore_snapshot(&path, Some(&validation))
+            .await;
+        let _ = fs::remove_file(&path);
+
+        let report = report.unwrap();
+        assert!(report.discarded);
+        assert_eq!(report.audit.unwrap().account_balance.divergent, 1);
+        assert_eq!(None, restored_cache.get_account_balance(&1).await);
+
+        Ok(())
+    }
+}
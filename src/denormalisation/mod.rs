@@ -0,0 +1,424 @@
+use crate::benchmarking::{Benchmark, BenchmarkResult};
+use crate::enums::card_status::CardStatus;
+use crate::enums::sample_query::SampleQuery;
+use crate::models::card::CardRowInsertion;
+use sqlx::{PgConnection, Pool, Postgres, Row};
+
+/// `number_cards` rewritten to sum `accounts.num_active_cards` instead of joining `cards`.
+pub const NUMBER_CARDS_DENORMALISED_SQL: &str =
+    include_str!("../../db/sample_queries/number_cards_denormalised.sql");
+
+/// Installs the trigger that keeps `accounts.num_active_cards` in line with `cards`, and
+/// corrects any accounts that have already drifted.
+pub async fn install_trigger(db: &Pool<Postgres>) -> Result<(), sqlx::Error> {
+    sqlx::raw_sql(include_str!("../../db/triggers/num_active_cards.sql"))
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+pub async fn uninstall_trigger(db: &Pool<Postgres>) -> Result<(), sqlx::Error> {
+    sqlx::raw_sql(
+        "
+        DROP TRIGGER IF EXISTS trg_num_active_cards ON public.cards;
+        DROP FUNCTION IF EXISTS maintain_num_active_cards();
+        DROP FUNCTION IF EXISTS recount_num_active_cards(INT);
+        ",
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumActiveCardsMismatch {
+    pub account_id: i32,
+    pub stored: Option<i32>,
+    pub actual: i64,
+}
+
+/// Accounts whose `num_active_cards` differs from the number of active cards they have.
+pub async fn find_num_active_cards_mismatches(
+    db: &Pool<Postgres>,
+) -> Result<Vec<NumActiveCardsMismatch>, sqlx::Error> {
+    let rows = sqlx::query(
+        "
+        SELECT accounts.id AS account_id, accounts.num_active_cards AS stored,
+            COUNT(cards.id) AS actual
+        FROM public.accounts
+        LEFT JOIN public.cards ON cards.account_id = accounts.id AND cards.status = 'active'
+        GROUP BY accounts.id
+        HAVING accounts.num_active_cards IS DISTINCT FROM COUNT(cards.id)
+        ORDER BY accounts.id;
+        ",
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| NumActiveCardsMismatch {
+            account_id: row.get("account_id"),
+            stored: row.get("stored"),
+            actual: row.get("actual"),
+        })
+        .collect())
+}
+
+/// Recounts every account that has drifted, returning how many were corrected.
+pub async fn repair_num_active_cards(db: &Pool<Postgres>) -> Result<u64, sqlx::Error> {
+    Ok(sqlx::query(
+        "
+        UPDATE public.accounts
+        SET num_active_cards = active_cards.actual
+        FROM (
+            SELECT accounts.id, COUNT(cards.id) AS actual
+            FROM public.accounts
+            LEFT JOIN public.cards ON cards.account_id = accounts.id AND cards.status = 'active'
+            GROUP BY accounts.id
+        ) AS active_cards
+        WHERE accounts.id = active_cards.id
+            AND accounts.num_active_cards IS DISTINCT FROM active_cards.actual;
+        ",
+    )
+    .execute(db)
+    .await?
+    .rows_affected())
+}
+
+/// Application level alternative to the trigger: card writes made through here recount the
+/// account's active cards in the same transaction.
+pub struct CardWriter {
+    db: Pool<Postgres>,
+}
+
+impl CardWriter {
+    pub fn new(db: Pool<Postgres>) -> Self {
+        Self { db }
+    }
+
+    pub async fn insert_card(&self, card: &CardRowInsertion) -> Result<i32, sqlx::Error> {
+        let mut tx = self.db.begin().await?;
+        lock_account(&mut tx, card.account_id).await?;
+        let card_id: i32 = sqlx::query(
+            "
+            INSERT INTO public.cards
+            (account_id, card_number, card_type, expiration_date, status)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id;
+            ",
+        )
+        .bind(card.account_id)
+        .bind(&card.card_number)
+        .bind(&card.card_type)
+        .bind(card.expiration_date)
+        .bind(&card.status)
+        .fetch_one(&mut *tx)
+        .await?
+        .get("id");
+        recount(&mut tx, card.account_id).await?;
+        tx.commit().await?;
+
+        Ok(card_id)
+    }
+
+    /// Returns false if there is no such card.
+    pub async fn set_status(&self, card_id: i32, status: CardStatus) -> Result<bool, sqlx::Error> {
+        let mut tx = self.db.begin().await?;
+        let Some(account_id) = card_account(&mut tx, card_id).await? else {
+            return Ok(false);
+        };
+        lock_account(&mut tx, account_id).await?;
+        sqlx::query("UPDATE public.cards SET status = $2 WHERE id = $1")
+            .bind(card_id)
+            .bind(status.to_string())
+            .execute(&mut *tx)
+            .await?;
+        recount(&mut tx, account_id).await?;
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    /// Returns false if there is no such card.
+    pub async fn delete_card(&self, card_id: i32) -> Result<bool, sqlx::Error> {
+        let mut tx = self.db.begin().await?;
+        let Some(account_id) = card_account(&mut tx, card_id).await? else {
+            return Ok(false);
+        };
+        lock_account(&mut tx, account_id).await?;
+        sqlx::query("DELETE FROM public.cards WHERE id = $1")
+            .bind(card_id)
+            .execute(&mut *tx)
+            .await?;
+        recount(&mut tx, account_id).await?;
+        tx.commit().await?;
+
+        Ok(true)
+    }
+}
+
+async fn card_account(conn: &mut PgConnection, card_id: i32) -> Result<Option<i32>, sqlx::Error> {
+    Ok(
+        sqlx::query("SELECT account_id FROM public.cards WHERE id = $1")
+            .bind(card_id)
+            .fetch_optional(conn)
+            .await?
+            .and_then(|row| row.get("account_id")),
+    )
+}
+
+/// Serialises card writes per account, so each recount sees every card committed before it.
+async fn lock_account(conn: &mut PgConnection, account_id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query("SELECT 1 FROM public.accounts WHERE id = $1 FOR UPDATE")
+        .bind(account_id)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+async fn recount(conn: &mut PgConnection, account_id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "
+        UPDATE public.accounts
+        SET num_active_cards = (
+            SELECT COUNT(*) FROM public.cards WHERE account_id = $1 AND status = 'active'
+        )
+        WHERE id = $1;
+        ",
+    )
+    .bind(account_id)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumberCardsComparison {
+    pub join: BenchmarkResult,
+    pub denormalised: BenchmarkResult,
+}
+
+/// Times `number_cards` as written, joining `cards`, against the denormalised rewrite.
+pub async fn compare_number_cards(
+    db: &Pool<Postgres>,
+    benchmark: &Benchmark,
+) -> Result<NumberCardsComparison, sqlx::Error> {
+    let join = benchmark
+        .run(
+            db,
+            SampleQuery::NumberCards.to_string(),
+            SampleQuery::NumberCards.sql(),
+        )
+        .await?;
+    let denormalised = benchmark
+        .run(
+            db,
+            "number_cards denormalised",
+            NUMBER_CARDS_DENORMALISED_SQL,
+        )
+        .await?;
+
+    Ok(NumberCardsComparison { join, denormalised })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::optimisations::BankSystemManager;
+    use chrono::Utc;
+    use sqlx::PgPool;
+
+    async fn insert_accounts(pool: &PgPool) -> sqlx::Result<()> {
+        sqlx::raw_sql(
+            "
+            INSERT INTO public.users (given_name, family_name, username, email, phone)
+            VALUES ('a', 'b', 'ab', 'ab@example.com', '1');
+            INSERT INTO public.accounts (user_id, account_type) VALUES (1, 'checking'), (1, 'business');
+            ",
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn num_active_cards(pool: &PgPool) -> sqlx::Result<Vec<i32>> {
+        Ok(
+            sqlx::query("SELECT num_active_cards FROM public.accounts ORDER BY id")
+                .fetch_all(pool)
+                .await?
+                .iter()
+                .map(|row| row.get("num_active_cards"))
+                .collect(),
+        )
+    }
+
+    fn card(account_id: i32, card_number: &str) -> CardRowInsertion {
+        CardRowInsertion {
+            account_id,
+            card_number: card_number.to_string(),
+            card_type: String::from("debit"),
+            expiration_date: Utc::now(),
+            status: CardStatus::Active.to_string(),
+        }
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/cards.sql",
+        "../../db/triggers/num_active_cards.sql",
+    ))]
+    async fn test_trigger_maintains_num_active_cards(pool: PgPool) -> sqlx::Result<()> {
+        insert_accounts(&pool).await?;
+
+        sqlx::raw_sql(
+            "
+            INSERT INTO public.cards (account_id, card_number, card_type, expiration_date)
+            VALUES (1, '1', 'debit', NOW()), (2, '2', 'debit', NOW()), (2, '3', 'credit', NOW());
+            ",
+        )
+        .execute(&pool)
+        .await?;
+        assert_eq!(num_active_cards(&pool).await?, vec![1, 2]);
+
+        sqlx::raw_sql(
+            "
+            UPDATE public.cards SET status = 'blocked' WHERE id = 2;
+            UPDATE public.cards SET account_id = 1 WHERE id = 3;
+            ",
+        )
+        .execute(&pool)
+        .await?;
+        assert_eq!(num_active_cards(&pool).await?, vec![2, 0]);
+
+        sqlx::raw_sql(
+            "
+            UPDATE public.cards SET status = 'expired' WHERE id = 1;
+            DELETE FROM public.cards WHERE id = 3;
+            UPDATE public.cards SET status = 'active' WHERE id = 2;
+            ",
+        )
+        .execute(&pool)
+        .await?;
+        assert_eq!(num_active_cards(&pool).await?, vec![0, 1]);
+        assert!(find_num_active_cards_mismatches(&pool).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/cards.sql",
+    ))]
+    async fn test_card_writer_maintains_num_active_cards(pool: PgPool) -> sqlx::Result<()> {
+        insert_accounts(&pool).await?;
+        let writer = CardWriter::new(pool.clone());
+
+        let first = writer.insert_card(&card(1, "1")).await?;
+        let second = writer.insert_card(&card(2, "2")).await?;
+        writer.insert_card(&card(2, "3")).await?;
+        assert_eq!(num_active_cards(&pool).await?, vec![1, 2]);
+
+        assert!(writer.set_status(second, CardStatus::Blocked).await?);
+        assert!(writer.delete_card(first).await?);
+        assert!(!writer.delete_card(first).await?);
+        assert_eq!(num_active_cards(&pool).await?, vec![0, 1]);
+        assert!(find_num_active_cards_mismatches(&pool).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/cards.sql",
+    ))]
+    async fn test_it_finds_and_repairs_drift(pool: PgPool) -> sqlx::Result<()> {
+        insert_accounts(&pool).await?;
+        sqlx::raw_sql(
+            "
+            INSERT INTO public.cards (account_id, card_number, card_type, expiration_date)
+            VALUES (2, '1', 'debit', NOW());
+            UPDATE public.accounts SET num_active_cards = 3 WHERE id = 1;
+            ",
+        )
+        .execute(&pool)
+        .await?;
+
+        assert_eq!(
+            find_num_active_cards_mismatches(&pool).await?,
+            vec![
+                NumActiveCardsMismatch {
+                    account_id: 1,
+                    stored: Some(3),
+                    actual: 0,
+                },
+                NumActiveCardsMismatch {
+                    account_id: 2,
+                    stored: Some(0),
+                    actual: 1,
+                },
+            ]
+        );
+        assert_eq!(repair_num_active_cards(&pool).await?, 2);
+        assert!(find_num_active_cards_mismatches(&pool).await?.is_empty());
+
+        install_trigger(&pool).await?;
+        sqlx::query("UPDATE public.cards SET status = 'blocked'")
+            .execute(&pool)
+            .await?;
+        assert!(find_num_active_cards_mismatches(&pool).await?.is_empty());
+        uninstall_trigger(&pool).await?;
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/audit_logs.sql",
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/cards.sql",
+        "../../db/schema/transfers.sql",
+        "../../db/schema/transactions.sql",
+        "../../db/schema/loans.sql",
+        "../../db/schema/payments.sql",
+    ))]
+    async fn test_number_cards_join_vs_denormalised(pool: PgPool) -> sqlx::Result<()> {
+        BankSystemManager::with_num_users(pool.clone(), 1_000)
+            .bulk_insert_data()
+            .await?;
+        assert!(find_num_active_cards_mismatches(&pool).await?.is_empty());
+
+        let comparison = compare_number_cards(&pool, &Benchmark::new(2, 10)).await?;
+
+        let differences = sqlx::query(&format!(
+            "
+            WITH joined AS ({join}), denormalised AS ({denormalised})
+            SELECT COUNT(*) AS differences FROM (
+                (SELECT * FROM joined EXCEPT SELECT * FROM denormalised)
+                UNION ALL
+                (SELECT * FROM denormalised EXCEPT SELECT * FROM joined)
+            ) differences;
+            ",
+            join = SampleQuery::NumberCards.sql().trim().trim_end_matches(';'),
+            denormalised = NUMBER_CARDS_DENORMALISED_SQL.trim().trim_end_matches(';')
+        ))
+        .fetch_one(&pool)
+        .await?
+        .get::<i64, _>("differences");
+        assert_eq!(differences, 0);
+        assert_eq!(comparison.join.rows, comparison.denormalised.rows);
+        println!(
+            "number_cards - join {:?}, denormalised {:?} - {:.1}% faster",
+            comparison.join.stats.median,
+            comparison.denormalised.stats.median,
+            comparison
+                .denormalised
+                .percentage_faster_than(&comparison.join)
+        );
+
+        Ok(())
+    }
+}
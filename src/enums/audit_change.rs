@@ -0,0 +1,40 @@
+/// The kind of change an audit log records, which decides the payload's before and after values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AuditChange {
+    /// Only an after value.
+    Created,
+    /// Both a before and an after value.
+    Updated,
+    /// Only a before value.
+    Deleted,
+}
+
+impl AuditChange {
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::Updated => "updated",
+            Self::Deleted => "deleted",
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_audit_change_to_string_created() {
+        assert_eq!(AuditChange::Created.to_string(), "created");
+    }
+
+    #[test]
+    fn test_audit_change_to_string_updated() {
+        assert_eq!(AuditChange::Updated.to_string(), "updated");
+    }
+
+    #[test]
+    fn test_audit_change_to_string_deleted() {
+        assert_eq!(AuditChange::Deleted.to_string(), "deleted");
+    }
+}
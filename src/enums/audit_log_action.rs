@@ -1,4 +1,7 @@
-#[derive(Debug, PartialEq)]
+use super::audit_change::AuditChange;
+use super::audit_log_subject_table::AuditLogSubjectTable;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum AuditLogAction {
     UserCreated,
     UserUpdated,
@@ -70,6 +73,47 @@ impl AuditLogAction {
             }
         }
     }
+    pub fn subject_table(&self) -> AuditLogSubjectTable {
+        match self {
+            Self::UserCreated | Self::UserUpdated | Self::UserDeleted => {
+                AuditLogSubjectTable::Users
+            }
+            Self::TransferCreated | Self::TransferUpdated => AuditLogSubjectTable::Transfers,
+            Self::TransactionCreated | Self::TransactionDeleted => {
+                AuditLogSubjectTable::Transactions
+            }
+            Self::PaymentCreated | Self::PaymentUpdated => AuditLogSubjectTable::Payments,
+            Self::LoanCreated | Self::LoanUpdated => AuditLogSubjectTable::Loans,
+            Self::CardCreated | Self::CardUpdated | Self::CardDeleted => {
+                AuditLogSubjectTable::Cards
+            }
+            Self::AccountCreated | Self::AccountUpdated | Self::AccountDeleted => {
+                AuditLogSubjectTable::Accounts
+            }
+        }
+    }
+
+    pub fn change(&self) -> AuditChange {
+        match self {
+            Self::UserCreated
+            | Self::TransferCreated
+            | Self::TransactionCreated
+            | Self::PaymentCreated
+            | Self::LoanCreated
+            | Self::CardCreated
+            | Self::AccountCreated => AuditChange::Created,
+            Self::UserUpdated
+            | Self::TransferUpdated
+            | Self::PaymentUpdated
+            | Self::LoanUpdated
+            | Self::CardUpdated
+            | Self::AccountUpdated => AuditChange::Updated,
+            Self::UserDeleted
+            | Self::TransactionDeleted
+            | Self::CardDeleted
+            | Self::AccountDeleted => AuditChange::Deleted,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -320,4 +364,27 @@ mod test {
     fn test_audit_log_action_from_string_not_found() {
         assert_eq!(AuditLogAction::from_string("not found"), None,);
     }
+
+    #[test]
+    fn test_audit_log_action_subject_table() {
+        assert_eq!(
+            AuditLogAction::PaymentCreated.subject_table(),
+            AuditLogSubjectTable::Payments
+        );
+        assert_eq!(
+            AuditLogAction::TransactionDeleted.subject_table(),
+            AuditLogSubjectTable::Transactions
+        );
+        assert_eq!(
+            AuditLogAction::AccountUpdated.subject_table(),
+            AuditLogSubjectTable::Accounts
+        );
+    }
+
+    #[test]
+    fn test_audit_log_action_change() {
+        assert_eq!(AuditLogAction::CardCreated.change(), AuditChange::Created);
+        assert_eq!(AuditLogAction::LoanUpdated.change(), AuditChange::Updated);
+        assert_eq!(AuditLogAction::UserDeleted.change(), AuditChange::Deleted);
+    }
 }
@@ -1,3 +1,4 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum AuditLogSubjectTable {
     Users,
     Accounts,
@@ -20,6 +21,31 @@ impl AuditLogSubjectTable {
             Self::Payments => "payments",
         }
     }
+
+    pub fn from_string(table: &str) -> Option<Self> {
+        match table {
+            "users" => Some(Self::Users),
+            "accounts" => Some(Self::Accounts),
+            "cards" => Some(Self::Cards),
+            "transfers" => Some(Self::Transfers),
+            "transactions" => Some(Self::Transactions),
+            "loans" => Some(Self::Loans),
+            "payments" => Some(Self::Payments),
+            _ => None,
+        }
+    }
+
+    pub fn all() -> [Self; 7] {
+        [
+            Self::Users,
+            Self::Accounts,
+            Self::Cards,
+            Self::Transfers,
+            Self::Transactions,
+            Self::Loans,
+            Self::Payments,
+        ]
+    }
 }
 
 #[cfg(test)]
@@ -63,4 +89,22 @@ mod test {
     fn test_audit_subject_type_to_string_payments() {
         assert_eq!(AuditLogSubjectTable::Payments.to_string(), "payments");
     }
+
+    #[test]
+    fn test_audit_subject_type_from_string() {
+        assert_eq!(
+            AuditLogSubjectTable::from_string("payments"),
+            Some(AuditLogSubjectTable::Payments)
+        );
+        assert_eq!(
+            AuditLogSubjectTable::from_string("accounts"),
+            Some(AuditLogSubjectTable::Accounts)
+        );
+        assert_eq!(AuditLogSubjectTable::from_string("not found"), None);
+    }
+
+    #[test]
+    fn test_audit_subject_type_all() {
+        assert_eq!(AuditLogSubjectTable::all().len(), 7);
+    }
 }
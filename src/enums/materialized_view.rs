@@ -0,0 +1,88 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MaterializedView {
+    AverageTransactionAmount,
+    LoansOutstanding,
+    SuspiciousTransactions,
+}
+
+impl MaterializedView {
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            Self::AverageTransactionAmount => "average_transaction_amount",
+            Self::LoansOutstanding => "loans_outstanding",
+            Self::SuspiciousTransactions => "suspicious_transactions",
+        }
+    }
+
+    pub fn from_string(view: &str) -> Option<Self> {
+        match view {
+            "average_transaction_amount" => Some(Self::AverageTransactionAmount),
+            "loans_outstanding" => Some(Self::LoansOutstanding),
+            "suspicious_transactions" => Some(Self::SuspiciousTransactions),
+            _ => None,
+        }
+    }
+
+    /// The table whose `created_at` marks how far a refresh of the view reached.
+    pub fn source_table(&self) -> &'static str {
+        match self {
+            Self::AverageTransactionAmount => "transactions",
+            Self::LoansOutstanding => "loans",
+            Self::SuspiciousTransactions => "transactions",
+        }
+    }
+
+    pub fn all() -> [Self; 3] {
+        [
+            Self::AverageTransactionAmount,
+            Self::LoansOutstanding,
+            Self::SuspiciousTransactions,
+        ]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_materialized_view_to_string_average_transaction_amount() {
+        assert_eq!(
+            MaterializedView::AverageTransactionAmount.to_string(),
+            "average_transaction_amount"
+        );
+    }
+
+    #[test]
+    fn test_materialized_view_to_string_loans_outstanding() {
+        assert_eq!(
+            MaterializedView::LoansOutstanding.to_string(),
+            "loans_outstanding"
+        );
+    }
+
+    #[test]
+    fn test_materialized_view_to_string_suspicious_transactions() {
+        assert_eq!(
+            MaterializedView::SuspiciousTransactions.to_string(),
+            "suspicious_transactions"
+        );
+    }
+
+    #[test]
+    fn test_materialized_view_from_string_round_trips() {
+        for view in MaterializedView::all() {
+            assert_eq!(MaterializedView::from_string(view.to_string()), Some(view));
+        }
+    }
+
+    #[test]
+    fn test_materialized_view_from_string_not_found() {
+        assert_eq!(MaterializedView::from_string("transactions"), None);
+    }
+
+    #[test]
+    fn test_materialized_view_source_table_loans_outstanding() {
+        assert_eq!(MaterializedView::LoansOutstanding.source_table(), "loans");
+    }
+}
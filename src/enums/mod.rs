@@ -1,10 +1,18 @@
 pub mod account_type;
+pub mod audit_change;
 pub mod audit_log_action;
 pub mod audit_log_subject_table;
 pub mod card_status;
 pub mod card_type;
 pub mod loan_status;
+pub mod materialized_view;
+pub mod partitioned_table;
 pub mod payment_status;
+pub mod refresh_mode;
+pub mod sample_query;
+pub mod stale_view_action;
+pub mod summary_table;
 pub mod transaction_status;
 pub mod transaction_type;
 pub mod transfer_status;
+pub mod view_read_source;
@@ -0,0 +1,79 @@
+/// Append heavy, time ordered tables that the partitioning experiment copies into monthly
+/// range partitions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PartitionedTable {
+    Transactions,
+    AuditLogs,
+}
+
+impl PartitionedTable {
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            Self::Transactions => "transactions",
+            Self::AuditLogs => "audit_logs",
+        }
+    }
+
+    /// The partitioned copy of the table.
+    pub fn partitioned_name(&self) -> &'static str {
+        match self {
+            Self::Transactions => "transactions_partitioned",
+            Self::AuditLogs => "audit_logs_partitioned",
+        }
+    }
+
+    /// Named queries over one month, with `{table}`, `{from}` and `{to}` placeholders.
+    pub fn time_bounded_queries(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Self::Transactions => &[
+                (
+                    "monthly totals",
+                    "SELECT transaction_type, COUNT(*), SUM(amount) FROM public.{table} WHERE created_at >= '{from}' AND created_at < '{to}' GROUP BY transaction_type",
+                ),
+                (
+                    "latest in month",
+                    "SELECT * FROM public.{table} WHERE created_at >= '{from}' AND created_at < '{to}' ORDER BY created_at DESC LIMIT 100",
+                ),
+            ],
+            Self::AuditLogs => &[
+                (
+                    "monthly actions",
+                    "SELECT action, COUNT(*) FROM public.{table} WHERE created_at >= '{from}' AND created_at < '{to}' GROUP BY action",
+                ),
+                (
+                    "latest in month",
+                    "SELECT * FROM public.{table} WHERE created_at >= '{from}' AND created_at < '{to}' ORDER BY created_at DESC LIMIT 100",
+                ),
+            ],
+        }
+    }
+
+    pub fn all() -> [Self; 2] {
+        [Self::Transactions, Self::AuditLogs]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_partitioned_table_to_string_transactions() {
+        assert_eq!(PartitionedTable::Transactions.to_string(), "transactions");
+    }
+
+    #[test]
+    fn test_partitioned_table_to_string_audit_logs() {
+        assert_eq!(PartitionedTable::AuditLogs.to_string(), "audit_logs");
+    }
+
+    #[test]
+    fn test_partitioned_table_queries_are_time_bounded() {
+        for table in PartitionedTable::all() {
+            for (_, sql) in table.time_bounded_queries() {
+                assert!(sql.contains("{table}"));
+                assert!(sql.contains("created_at >= '{from}' AND created_at < '{to}'"));
+            }
+        }
+    }
+}
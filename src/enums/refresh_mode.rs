@@ -0,0 +1,36 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RefreshMode {
+    /// Takes an exclusive lock, so readers wait until the refresh commits.
+    Blocking,
+    /// Diffs into the view under a weaker lock, so readers see the old rows meanwhile. Needs a
+    /// unique index on the view and for it to already be populated.
+    Concurrent,
+}
+
+impl RefreshMode {
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            Self::Blocking => "blocking",
+            Self::Concurrent => "concurrent",
+        }
+    }
+
+    pub fn all() -> [Self; 2] {
+        [Self::Blocking, Self::Concurrent]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_refresh_mode_to_string_blocking() {
+        assert_eq!(RefreshMode::Blocking.to_string(), "blocking");
+    }
+
+    #[test]
+    fn test_refresh_mode_to_string_concurrent() {
+        assert_eq!(RefreshMode::Concurrent.to_string(), "concurrent");
+    }
+}
@@ -0,0 +1,106 @@
+use crate::enums::audit_log_subject_table::AuditLogSubjectTable;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SampleQuery {
+    NumberCards,
+    TotalBalances,
+    TotalLoans,
+    UnusedAccounts,
+    TimeToTransaction,
+}
+
+impl SampleQuery {
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            Self::NumberCards => "number_cards",
+            Self::TotalBalances => "total_balances",
+            Self::TotalLoans => "total_loans",
+            Self::UnusedAccounts => "unused_accounts",
+            Self::TimeToTransaction => "time_to_transaction",
+        }
+    }
+
+    pub fn sql(&self) -> &'static str {
+        match self {
+            Self::NumberCards => include_str!("../../db/sample_queries/number_cards.sql"),
+            Self::TotalBalances => include_str!("../../db/sample_queries/total_balances.sql"),
+            Self::TotalLoans => include_str!("../../db/sample_queries/total_loans.sql"),
+            Self::UnusedAccounts => include_str!("../../db/sample_queries/unused_accounts.sql"),
+            Self::TimeToTransaction => {
+                include_str!("../../db/sample_queries/time_to_transaction.sql")
+            }
+        }
+    }
+
+    /// Tables the query reads from, so a write to any of them makes its results stale.
+    pub fn dependencies(&self) -> &'static [AuditLogSubjectTable] {
+        match self {
+            Self::NumberCards => &[
+                AuditLogSubjectTable::Users,
+                AuditLogSubjectTable::Accounts,
+                AuditLogSubjectTable::Cards,
+            ],
+            Self::TotalBalances => &[AuditLogSubjectTable::Users, AuditLogSubjectTable::Accounts],
+            Self::TotalLoans => &[AuditLogSubjectTable::Users, AuditLogSubjectTable::Loans],
+            Self::UnusedAccounts | Self::TimeToTransaction => &[
+                AuditLogSubjectTable::Accounts,
+                AuditLogSubjectTable::Transactions,
+                AuditLogSubjectTable::Transfers,
+                AuditLogSubjectTable::Payments,
+            ],
+        }
+    }
+
+    pub fn all() -> [Self; 5] {
+        [
+            Self::NumberCards,
+            Self::TotalBalances,
+            Self::TotalLoans,
+            Self::UnusedAccounts,
+            Self::TimeToTransaction,
+        ]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sample_query_to_string_number_cards() {
+        assert_eq!(SampleQuery::NumberCards.to_string(), "number_cards");
+    }
+
+    #[test]
+    fn test_sample_query_to_string_total_balances() {
+        assert_eq!(SampleQuery::TotalBalances.to_string(), "total_balances");
+    }
+
+    #[test]
+    fn test_sample_query_to_string_total_loans() {
+        assert_eq!(SampleQuery::TotalLoans.to_string(), "total_loans");
+    }
+
+    #[test]
+    fn test_sample_query_to_string_unused_accounts() {
+        assert_eq!(SampleQuery::UnusedAccounts.to_string(), "unused_accounts");
+    }
+
+    #[test]
+    fn test_sample_query_to_string_time_to_transaction() {
+        assert_eq!(
+            SampleQuery::TimeToTransaction.to_string(),
+            "time_to_transaction"
+        );
+    }
+
+    #[test]
+    fn test_sample_query_only_number_cards_depends_on_cards() {
+        let dependents: Vec<_> = SampleQuery::all()
+            .into_iter()
+            .filter(|query| query.dependencies().contains(&AuditLogSubjectTable::Cards))
+            .collect();
+
+        assert_eq!(dependents, vec![SampleQuery::NumberCards]);
+    }
+}
@@ -0,0 +1,32 @@
+/// What a freshness bounded read does when the view is older than allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StaleViewAction {
+    /// Refresh the view, then read it.
+    Refresh,
+    /// Leave the view alone and run its query against the base tables.
+    RawQuery,
+}
+
+impl StaleViewAction {
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            Self::Refresh => "refresh",
+            Self::RawQuery => "raw query",
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_stale_view_action_to_string_refresh() {
+        assert_eq!(StaleViewAction::Refresh.to_string(), "refresh");
+    }
+
+    #[test]
+    fn test_stale_view_action_to_string_raw_query() {
+        assert_eq!(StaleViewAction::RawQuery.to_string(), "raw query");
+    }
+}
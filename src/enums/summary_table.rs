@@ -0,0 +1,106 @@
+use crate::enums::materialized_view::MaterializedView;
+
+/// Trigger maintained tables kept as an alternative to a materialized view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SummaryTable {
+    AverageTransactionAmount,
+    LoansOutstanding,
+}
+
+impl SummaryTable {
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            Self::AverageTransactionAmount => "average_transaction_amount_summary",
+            Self::LoansOutstanding => "loans_outstanding_summary",
+        }
+    }
+
+    /// Creates the table, backfills it and installs the trigger that maintains it.
+    pub fn definition(&self) -> &'static str {
+        match self {
+            Self::AverageTransactionAmount => {
+                include_str!("../../db/summaries/average_transaction_amount.sql")
+            }
+            Self::LoansOutstanding => include_str!("../../db/summaries/loans_outstanding.sql"),
+        }
+    }
+
+    /// Reads the table with the same columns as the materialized view it replaces.
+    pub fn sql(&self) -> &'static str {
+        match self {
+            Self::AverageTransactionAmount => {
+                "SELECT account_id, transaction_sum / transaction_count AS average_transaction FROM public.average_transaction_amount_summary"
+            }
+            Self::LoansOutstanding => {
+                "SELECT user_id, sum_loans_outstanding FROM public.loans_outstanding_summary"
+            }
+        }
+    }
+
+    pub fn view(&self) -> MaterializedView {
+        match self {
+            Self::AverageTransactionAmount => MaterializedView::AverageTransactionAmount,
+            Self::LoansOutstanding => MaterializedView::LoansOutstanding,
+        }
+    }
+
+    /// The table whose trigger maintains the summary.
+    pub fn source_table(&self) -> &'static str {
+        match self {
+            Self::AverageTransactionAmount => "transactions",
+            Self::LoansOutstanding => "loans",
+        }
+    }
+
+    pub fn trigger(&self) -> &'static str {
+        match self {
+            Self::AverageTransactionAmount => "trg_average_transaction_amount_summary",
+            Self::LoansOutstanding => "trg_loans_outstanding_summary",
+        }
+    }
+
+    /// Key and value columns shared by the summary, the view and the view's query.
+    pub fn columns(&self) -> (&'static str, &'static str) {
+        match self {
+            Self::AverageTransactionAmount => ("account_id", "average_transaction"),
+            Self::LoansOutstanding => ("user_id", "sum_loans_outstanding"),
+        }
+    }
+
+    pub fn all() -> [Self; 2] {
+        [Self::AverageTransactionAmount, Self::LoansOutstanding]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_summary_table_to_string_average_transaction_amount() {
+        assert_eq!(
+            SummaryTable::AverageTransactionAmount.to_string(),
+            "average_transaction_amount_summary"
+        );
+    }
+
+    #[test]
+    fn test_summary_table_to_string_loans_outstanding() {
+        assert_eq!(
+            SummaryTable::LoansOutstanding.to_string(),
+            "loans_outstanding_summary"
+        );
+    }
+
+    #[test]
+    fn test_summary_table_definitions_create_their_table_and_trigger() {
+        for summary in SummaryTable::all() {
+            assert!(summary
+                .definition()
+                .contains(&format!("CREATE TABLE {}", summary.to_string())));
+            assert!(summary
+                .definition()
+                .contains(&format!("CREATE TRIGGER {}", summary.trigger())));
+        }
+    }
+}
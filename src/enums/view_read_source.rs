@@ -0,0 +1,40 @@
+/// Where a freshness bounded read got its rows from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ViewReadSource {
+    MaterializedView,
+    RefreshedView,
+    RawQuery,
+}
+
+impl ViewReadSource {
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            Self::MaterializedView => "materialized view",
+            Self::RefreshedView => "refreshed view",
+            Self::RawQuery => "raw query",
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_view_read_source_to_string_materialized_view() {
+        assert_eq!(
+            ViewReadSource::MaterializedView.to_string(),
+            "materialized view"
+        );
+    }
+
+    #[test]
+    fn test_view_read_source_to_string_refreshed_view() {
+        assert_eq!(ViewReadSource::RefreshedView.to_string(), "refreshed view");
+    }
+
+    #[test]
+    fn test_view_read_source_to_string_raw_query() {
+        assert_eq!(ViewReadSource::RawQuery.to_string(), "raw query");
+    }
+}
@@ -0,0 +1,533 @@
+use crate::benchmarking::explain::explain_estimate;
+use sqlx::{Acquire, PgConnection, Pool, Postgres, Row};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Clause {
+    Filter,
+    Join,
+    GroupBy,
+    OrderBy,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ColumnUsage {
+    pub table: String,
+    pub column: String,
+    pub clause: Clause,
+}
+
+/// Finds `alias.column` references in WHERE/ON/GROUP BY/ORDER BY/PARTITION BY clauses.
+///
+/// Aliases are resolved through FROM and JOIN items. Unqualified columns are ignored as they
+/// cannot be attributed to a table without the catalog, and so are CTE and subquery aliases once
+/// the usages are checked against real columns.
+pub fn parse_column_usages(sql: &str) -> Vec<ColumnUsage> {
+    let tokens = tokenize(sql);
+
+    let mut aliases: HashMap<&str, &str> = HashMap::new();
+    for i in 0..tokens.len() {
+        if tokens[i] != "from" && tokens[i] != "join" {
+            continue;
+        }
+        let Some(table) = tokens.get(i + 1).filter(|token| is_identifier(token)) else {
+            continue;
+        };
+        let table = table.trim_start_matches("public.");
+        aliases.insert(table, table);
+        let alias_index = if tokens.get(i + 2).map(String::as_str) == Some("as") {
+            i + 3
+        } else {
+            i + 2
+        };
+        if let Some(alias) = tokens
+            .get(alias_index)
+            .filter(|token| is_identifier(token) && !is_keyword(token))
+        {
+            aliases.insert(alias, table);
+        }
+    }
+
+    let mut usages = BTreeSet::new();
+    let mut clause = None;
+    for i in 0..tokens.len() {
+        let token = tokens[i].as_str();
+        let previous = i.checked_sub(1).map(|j| tokens[j].as_str());
+        match token {
+            "where" | "having" => clause = Some(Clause::Filter),
+            "on" => clause = Some(Clause::Join),
+            "by" if previous == Some("group") => clause = Some(Clause::GroupBy),
+            "by" if previous == Some("order") || previous == Some("partition") => {
+                clause = Some(Clause::OrderBy)
+            }
+            "select" | "from" | "join" | "union" | "limit" | "range" | "rows" | "returning" => {
+                clause = None
+            }
+            _ => {
+                let (Some(clause), Some((qualifier, column))) = (clause, token.split_once('.'))
+                else {
+                    continue;
+                };
+                if let Some(table) = aliases.get(qualifier) {
+                    usages.insert(ColumnUsage {
+                        table: table.to_string(),
+                        column: column.to_string(),
+                        clause,
+                    });
+                }
+            }
+        }
+    }
+
+    usages.into_iter().collect()
+}
+
+fn tokenize(sql: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        if c == '-' && chars.peek() == Some(&'-') {
+            for c in chars.by_ref() {
+                if c == '\n' {
+                    break;
+                }
+            }
+            continue;
+        }
+        if c == '\'' {
+            for c in chars.by_ref() {
+                if c == '\'' {
+                    break;
+                }
+            }
+            tokens.push(String::from("'"));
+            continue;
+        }
+        if c.is_alphanumeric() || c == '_' {
+            let mut token = c.to_ascii_lowercase().to_string();
+            while let Some(next) = chars.next_if(|c| c.is_alphanumeric() || *c == '_' || *c == '.')
+            {
+                token.push(next.to_ascii_lowercase());
+            }
+            tokens.push(token);
+            continue;
+        }
+        tokens.push(c.to_string());
+    }
+    tokens
+}
+
+fn is_identifier(token: &str) -> bool {
+    token
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_')
+}
+
+fn is_keyword(token: &str) -> bool {
+    matches!(
+        token,
+        "on" | "where"
+            | "join"
+            | "left"
+            | "right"
+            | "inner"
+            | "outer"
+            | "full"
+            | "cross"
+            | "group"
+            | "order"
+            | "limit"
+            | "union"
+            | "as"
+            | "using"
+            | "having"
+    )
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct IndexCandidate {
+    pub table: String,
+    pub columns: Vec<String>,
+}
+
+impl IndexCandidate {
+    pub fn name(&self) -> String {
+        format!("idx_advised_{}_{}", self.table, self.columns.join("_"))
+    }
+
+    pub fn definition(&self) -> String {
+        format!(
+            "CREATE INDEX {} ON public.{} ({})",
+            self.name(),
+            self.table,
+            self.columns.join(", ")
+        )
+    }
+}
+
+/// Single-column candidates for every used column, plus two-column candidates leading with a
+/// filter or join column and followed by any other column the same statement uses on that table.
+pub fn enumerate_candidates(workload: &[String]) -> Vec<IndexCandidate> {
+    let mut candidates = BTreeSet::new();
+    for statement in workload {
+        let usages = parse_column_usages(statement);
+        for usage in &usages {
+            candidates.insert(IndexCandidate {
+                table: usage.table.clone(),
+                columns: vec![usage.column.clone()],
+            });
+        }
+        for leading in usages
+            .iter()
+            .filter(|usage| matches!(usage.clause, Clause::Filter | Clause::Join))
+        {
+            for trailing in usages
+                .iter()
+                .filter(|usage| usage.table == leading.table && usage.column != leading.column)
+            {
+                candidates.insert(IndexCandidate {
+                    table: leading.table.clone(),
+                    columns: vec![leading.column.clone(), trailing.column.clone()],
+                });
+            }
+        }
+    }
+    candidates.into_iter().collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EvaluationMode {
+    /// HypoPG hypothetical indexes, which cost nothing to build.
+    Hypothetical,
+    /// Real `CREATE INDEX` in a transaction that is rolled back afterwards. Building the index
+    /// still locks the table against writes, so only opt into this on a scratch database.
+    Real,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexRecommendation {
+    pub candidate: IndexCandidate,
+    /// Summed planner cost of the statements touching the table, without and with the index.
+    pub cost_without: f64,
+    pub cost_with: f64,
+    pub size_bytes: i64,
+}
+
+impl IndexRecommendation {
+    pub fn benefit(&self) -> f64 {
+        self.cost_without - self.cost_with
+    }
+
+    pub fn benefit_per_mb(&self) -> f64 {
+        self.benefit() / (self.size_bytes.max(1) as f64 / 1_048_576.0)
+    }
+}
+
+pub struct IndexAdvisor {
+    db: Pool<Postgres>,
+}
+
+impl IndexAdvisor {
+    pub fn new(db: Pool<Postgres>) -> Self {
+        Self { db }
+    }
+
+    /// Installs HypoPG when the server ships it, returning whether `EvaluationMode::Hypothetical`
+    /// can be used. There is no automatic fallback to `EvaluationMode::Real`.
+    pub async fn install_hypopg(&self) -> Result<bool, sqlx::Error> {
+        let available = sqlx::query("SELECT 1 FROM pg_available_extensions WHERE name = 'hypopg'")
+            .fetch_optional(&self.db)
+            .await?
+            .is_some();
+        if available {
+            sqlx::raw_sql("CREATE EXTENSION IF NOT EXISTS hypopg")
+                .execute(&self.db)
+                .await?;
+        }
+
+        Ok(available)
+    }
+
+    /// Recommendations with a positive benefit, best benefit per MB of index first.
+    pub async fn advise(
+        &self,
+        workload: &[String],
+        mode: EvaluationMode,
+    ) -> Result<Vec<IndexRecommendation>, sqlx::Error> {
+        let columns = self.table_columns().await?;
+        let existing = self.existing_indices().await?;
+        let candidates: Vec<IndexCandidate> = enumerate_candidates(workload)
+            .into_iter()
+            .filter(|candidate| {
+                candidate
+                    .columns
+                    .iter()
+                    .all(|column| columns.contains(&(candidate.table.clone(), column.clone())))
+            })
+            .filter(|candidate| !existing.contains(candidate))
+            .collect();
+
+        // Hypothetical indexes only exist in the session that created them.
+        let mut conn = self.db.acquire().await?;
+        let mut recommendations = Vec::new();
+        for candidate in candidates {
+            let statements: Vec<&String> = workload
+                .iter()
+                .filter(|statement| {
+                    parse_column_usages(statement)
+                        .iter()
+                        .any(|usage| usage.table == candidate.table)
+                })
+                .collect();
+            let cost_without = workload_cost(&mut conn, &statements).await?;
+
+            let (size_bytes, cost_with) = match mode {
+                EvaluationMode::Hypothetical => {
+                    let evaluated = hypothetical_cost(&mut conn, &candidate, &statements).await;
+                    sqlx::raw_sql("SELECT hypopg_reset()")
+                        .execute(&mut *conn)
+                        .await?;
+                    evaluated?
+                }
+                EvaluationMode::Real => {
+                    // Rolling back drops the index, including when measuring it failed.
+                    let mut tx = conn.begin().await?;
+                    let evaluated = real_cost(&mut tx, &candidate, &statements).await;
+                    tx.rollback().await?;
+                    evaluated?
+                }
+            };
+
+            recommendations.push(IndexRecommendation {
+                candidate,
+                cost_without,
+                cost_with,
+                size_bytes,
+            });
+        }
+
+        recommendations.retain(|recommendation| recommendation.benefit() > 0.0);
+        recommendations.sort_by(|a, b| b.benefit_per_mb().total_cmp(&a.benefit_per_mb()));
+
+        Ok(recommendations)
+    }
+
+    async fn table_columns(&self) -> Result<HashSet<(String, String)>, sqlx::Error> {
+        Ok(sqlx::query(
+            "
+            SELECT table_name::TEXT AS table_name, column_name::TEXT AS column_name
+            FROM information_schema.columns
+            WHERE table_schema = 'public';
+            ",
+        )
+        .fetch_all(&self.db)
+        .await?
+        .iter()
+        .map(|row| (row.get("table_name"), row.get("column_name")))
+        .collect())
+    }
+
+    async fn existing_indices(&self) -> Result<HashSet<IndexCandidate>, sqlx::Error> {
+        Ok(sqlx::query(
+            "
+            SELECT
+                t.relname::TEXT AS table_name,
+                array_agg(a.attname::TEXT ORDER BY k.ord) AS columns
+            FROM pg_index i
+            JOIN pg_class t ON t.oid = i.indrelid
+            JOIN pg_namespace n ON n.oid = t.relnamespace AND n.nspname = 'public'
+            JOIN LATERAL unnest(i.indkey) WITH ORDINALITY AS k(attnum, ord) ON true
+            JOIN pg_attribute a ON a.attrelid = t.oid AND a.attnum = k.attnum
+            GROUP BY i.indexrelid, t.relname;
+            ",
+        )
+        .fetch_all(&self.db)
+        .await?
+        .iter()
+        .map(|row| IndexCandidate {
+            table: row.get("table_name"),
+            columns: row.get("columns"),
+        })
+        .collect())
+    }
+}
+
+/// Size and workload cost of `candidate` as a HypoPG index. The caller resets HypoPG afterwards.
+async fn hypothetical_cost(
+    conn: &mut PgConnection,
+    candidate: &IndexCandidate,
+    statements: &[&String],
+) -> Result<(i64, f64), sqlx::Error> {
+    let size =
+        sqlx::query("SELECT hypopg_relation_size(indexrelid) AS size FROM hypopg_create_index($1)")
+            .bind(candidate.definition())
+            .fetch_one(&mut *conn)
+            .await?
+            .get::<i64, _>("size");
+
+    Ok((size, workload_cost(conn, statements).await?))
+}
+
+/// Size and workload cost of `candidate` built for real. The caller rolls the index back.
+async fn real_cost(
+    conn: &mut PgConnection,
+    candidate: &IndexCandidate,
+    statements: &[&String],
+) -> Result<(i64, f64), sqlx::Error> {
+    sqlx::raw_sql(&candidate.definition())
+        .execute(&mut *conn)
+        .await?;
+    let size = sqlx::query("SELECT pg_relation_size($1::regclass) AS size")
+        .bind(candidate.name())
+        .fetch_one(&mut *conn)
+        .await?
+        .get::<i64, _>("size");
+
+    Ok((size, workload_cost(conn, statements).await?))
+}
+
+async fn workload_cost(
+    conn: &mut PgConnection,
+    statements: &[&String],
+) -> Result<f64, sqlx::Error> {
+    let mut cost = 0.0;
+    for statement in statements {
+        cost += explain_estimate(&mut *conn, statement)
+            .await?
+            .root
+            .total_cost;
+    }
+
+    Ok(cost)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::enums::sample_query::SampleQuery;
+    use crate::optimisations::BankSystemManager;
+    use sqlx::PgPool;
+
+    fn usage(table: &str, column: &str, clause: Clause) -> ColumnUsage {
+        ColumnUsage {
+            table: table.to_string(),
+            column: column.to_string(),
+            clause,
+        }
+    }
+
+    #[test]
+    fn test_it_parses_join_and_group_by_columns() {
+        let usages = parse_column_usages(SampleQuery::TotalLoans.sql());
+
+        assert_eq!(
+            usages,
+            vec![
+                usage("loans", "status", Clause::Join),
+                usage("loans", "user_id", Clause::Join),
+                usage("users", "id", Clause::Join),
+                usage("users", "id", Clause::GroupBy),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_it_resolves_aliases() {
+        let usages = parse_column_usages(SampleQuery::UnusedAccounts.sql());
+
+        assert!(usages.contains(&usage("transactions", "account_id", Clause::Filter)));
+        assert!(usages.contains(&usage("payments", "account_id", Clause::Filter)));
+        assert!(usages.contains(&usage("transfers", "receiver_account_id", Clause::Filter)));
+        assert!(usages.contains(&usage("accounts", "id", Clause::Filter)));
+    }
+
+    #[test]
+    fn test_it_ignores_comments_and_string_literals() {
+        let usages = parse_column_usages(
+            "-- FROM cards c WHERE c.status\nSELECT 1 FROM loans l WHERE l.status = 'x.y'",
+        );
+
+        assert_eq!(usages, vec![usage("loans", "status", Clause::Filter)]);
+    }
+
+    #[test]
+    fn test_it_enumerates_single_and_composite_candidates() {
+        let candidates = enumerate_candidates(&[String::from(
+            "SELECT * FROM transactions t WHERE t.account_id = 1 ORDER BY t.created_at",
+        )]);
+
+        let columns: Vec<Vec<String>> = candidates.into_iter().map(|c| c.columns).collect();
+        assert_eq!(
+            columns,
+            vec![
+                vec![String::from("account_id")],
+                vec![String::from("account_id"), String::from("created_at")],
+                vec![String::from("created_at")],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_index_candidate_definition() {
+        let candidate = IndexCandidate {
+            table: String::from("loans"),
+            columns: vec![String::from("user_id"), String::from("status")],
+        };
+
+        assert_eq!(
+            candidate.definition(),
+            "CREATE INDEX idx_advised_loans_user_id_status ON public.loans (user_id, status)"
+        );
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/audit_logs.sql",
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/cards.sql",
+        "../../db/schema/transfers.sql",
+        "../../db/schema/transactions.sql",
+        "../../db/schema/loans.sql",
+        "../../db/schema/payments.sql",
+    ))]
+    async fn test_it_ranks_recommendations_for_sample_workload(pool: PgPool) -> sqlx::Result<()> {
+        let bank_system_manager = BankSystemManager::new(pool.clone());
+        bank_system_manager.insert_data().await;
+        sqlx::raw_sql("ANALYZE").execute(&pool).await?;
+        let workload: Vec<String> = SampleQuery::all()
+            .iter()
+            .map(|query| query.sql().to_string())
+            .collect();
+        let advisor = IndexAdvisor::new(pool.clone());
+
+        let mode = if advisor.install_hypopg().await? {
+            EvaluationMode::Hypothetical
+        } else {
+            EvaluationMode::Real
+        };
+        let recommendations = advisor.advise(&workload, mode).await?;
+
+        for pair in recommendations.windows(2) {
+            assert!(pair[0].benefit_per_mb() >= pair[1].benefit_per_mb());
+        }
+        for recommendation in &recommendations {
+            assert_ne!(recommendation.candidate.columns, vec![String::from("id")]);
+            println!(
+                "{} - benefit {:.2} - size {} bytes",
+                recommendation.candidate.definition(),
+                recommendation.benefit(),
+                recommendation.size_bytes
+            );
+        }
+        let leftover = sqlx::query("SELECT 1 FROM pg_indexes WHERE indexname LIKE 'idx_advised_%'")
+            .fetch_all(&pool)
+            .await?;
+        assert!(leftover.is_empty());
+
+        Ok(())
+    }
+}
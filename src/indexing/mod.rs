@@ -0,0 +1,300 @@
+pub mod advisor;
+
+use crate::benchmarking::{insert_throughput, Benchmark, BenchmarkResult};
+use crate::enums::materialized_view::MaterializedView;
+use crate::enums::sample_query::SampleQuery;
+use sqlx::{Pool, Postgres, Row};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexDefinition {
+    pub name: String,
+    pub table: String,
+    pub definition: String,
+}
+
+/// The indexes created by `db/indices.sql`.
+pub fn candidate_indices() -> Vec<IndexDefinition> {
+    parse_indices(include_str!("../../db/indices.sql"))
+}
+
+/// Parses `CREATE INDEX <name> ON <table>(...)` statements, ignoring anything else.
+pub fn parse_indices(sql: &str) -> Vec<IndexDefinition> {
+    sql.split(';')
+        .map(str::trim)
+        .filter_map(|statement| {
+            let words: Vec<&str> = statement.split_whitespace().collect();
+            let on = words
+                .iter()
+                .position(|word| word.eq_ignore_ascii_case("ON"))?;
+            if !statement.to_uppercase().starts_with("CREATE INDEX") || on < 3 {
+                return None;
+            }
+            let table = words.get(on + 1)?.split('(').next()?;
+            Some(IndexDefinition {
+                name: words[on - 1].to_string(),
+                table: table.to_string(),
+                definition: statement.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// The sample queries plus the defining query of every materialized view that exists.
+pub async fn experiment_queries(db: &Pool<Postgres>) -> Result<Vec<(String, String)>, sqlx::Error> {
+    let mut queries: Vec<(String, String)> = SampleQuery::all()
+        .iter()
+        .map(|query| (query.to_string().to_string(), query.sql().to_string()))
+        .collect();
+
+    for view in MaterializedView::all() {
+        if let Some(definition) = view_definition(db, view).await? {
+            queries.push((format!("{} (raw)", view.to_string()), definition));
+        }
+    }
+
+    Ok(queries)
+}
+
+/// The defining query of `view`, or `None` if it has not been created.
+pub async fn view_definition(
+    db: &Pool<Postgres>,
+    view: MaterializedView,
+) -> Result<Option<String>, sqlx::Error> {
+    Ok(sqlx::query(
+        "
+        SELECT pg_get_viewdef(c.oid, true) AS definition
+        FROM pg_class c
+        WHERE c.relname = $1 AND c.relkind = 'm';
+        ",
+    )
+    .bind(view.to_string())
+    .fetch_optional(db)
+    .await?
+    .map(|row| row.get::<String, _>("definition")))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryImpact {
+    pub query: String,
+    pub with_index: BenchmarkResult,
+    pub without_index: BenchmarkResult,
+    /// How much faster the median is with the index, as a percentage of the median without it.
+    pub improvement_pct: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexReport {
+    pub index: IndexDefinition,
+    pub size_bytes: i64,
+    pub queries: Vec<QueryImpact>,
+    pub inserts_per_sec_with_index: f64,
+    pub inserts_per_sec_without_index: f64,
+    /// How much slower inserts are with the index, as a percentage of the rate without it.
+    pub insert_penalty_pct: f64,
+}
+
+pub struct IndexExperiment {
+    db: Pool<Postgres>,
+    benchmark: Benchmark,
+    queries: Vec<(String, String)>,
+    insert_batches: usize,
+}
+
+impl IndexExperiment {
+    pub fn new(
+        db: Pool<Postgres>,
+        benchmark: Benchmark,
+        queries: Vec<(String, String)>,
+        insert_batches: usize,
+    ) -> Self {
+        Self {
+            db,
+            benchmark,
+            queries,
+            insert_batches,
+        }
+    }
+
+    /// Measures each index in turn with every other index left as it was found.
+    pub async fn run(&self, indices: &[IndexDefinition]) -> Result<Vec<IndexReport>, sqlx::Error> {
+        let mut reports = Vec::with_capacity(indices.len());
+        for index in indices {
+            reports.push(self.measure(index).await?);
+        }
+
+        Ok(reports)
+    }
+
+    async fn measure(&self, index: &IndexDefinition) -> Result<IndexReport, sqlx::Error> {
+        let existed = self.index_exists(index).await?;
+
+        if !existed {
+            self.execute(&index.definition).await?;
+        }
+        self.execute(&format!("ANALYZE {}", index.table)).await?;
+        let size_bytes: i64 = sqlx::query("SELECT pg_relation_size($1::regclass) AS size")
+            .bind(&index.name)
+            .fetch_one(&self.db)
+            .await?
+            .get("size");
+        let with_index = self.run_queries().await?;
+        let inserts_per_sec_with_index =
+            insert_throughput(&self.db, &index.table, self.insert_batches).await?;
+
+        self.execute(&format!("DROP INDEX {}", index.name)).await?;
+        let without_index = self.measure_without_index(index).await;
+        // Put back an index that was already there even if measuring failed.
+        if existed {
+            self.execute(&index.definition).await?;
+            self.execute(&format!("ANALYZE {}", index.table)).await?;
+        }
+        let (without_index, inserts_per_sec_without_index) = without_index?;
+
+        let queries = with_index
+            .into_iter()
+            .zip(without_index)
+            .map(|(with_index, without_index)| QueryImpact {
+                query: with_index.name.clone(),
+                improvement_pct: with_index.percentage_faster_than(&without_index),
+                with_index,
+                without_index,
+            })
+            .collect();
+
+        Ok(IndexReport {
+            index: index.clone(),
+            size_bytes,
+            queries,
+            inserts_per_sec_with_index,
+            inserts_per_sec_without_index,
+            insert_penalty_pct: if inserts_per_sec_without_index == 0.0 {
+                0.0
+            } else {
+                ((inserts_per_sec_without_index - inserts_per_sec_with_index)
+                    / inserts_per_sec_without_index)
+                    * 100.00
+            },
+        })
+    }
+
+    async fn measure_without_index(
+        &self,
+        index: &IndexDefinition,
+    ) -> Result<(Vec<BenchmarkResult>, f64), sqlx::Error> {
+        self.execute(&format!("ANALYZE {}", index.table)).await?;
+        let without_index = self.run_queries().await?;
+        let inserts_per_sec_without_index =
+            insert_throughput(&self.db, &index.table, self.insert_batches).await?;
+
+        Ok((without_index, inserts_per_sec_without_index))
+    }
+
+    async fn run_queries(&self) -> Result<Vec<BenchmarkResult>, sqlx::Error> {
+        let mut results = Vec::with_capacity(self.queries.len());
+        for (name, sql) in &self.queries {
+            results.push(self.benchmark.run(&self.db, name, sql).await?);
+        }
+
+        Ok(results)
+    }
+
+    async fn index_exists(&self, index: &IndexDefinition) -> Result<bool, sqlx::Error> {
+        Ok(
+            sqlx::query("SELECT 1 FROM pg_indexes WHERE schemaname = 'public' AND indexname = $1")
+                .bind(&index.name)
+                .fetch_optional(&self.db)
+                .await?
+                .is_some(),
+        )
+    }
+
+    async fn execute(&self, sql: &str) -> Result<(), sqlx::Error> {
+        sqlx::raw_sql(sql).execute(&self.db).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::optimisations::BankSystemManager;
+    use crate::reporting::Report;
+    use sqlx::PgPool;
+
+    #[test]
+    fn test_it_parses_candidate_indices() {
+        let indices = candidate_indices();
+
+        assert_eq!(indices.len(), 6);
+        assert_eq!(
+            indices[0],
+            IndexDefinition {
+                name: String::from("idx_transactions_account_id"),
+                table: String::from("transactions"),
+                definition: String::from(
+                    "CREATE INDEX idx_transactions_account_id ON transactions(account_id)"
+                ),
+            }
+        );
+        assert_eq!(indices[5].table, "loans");
+    }
+
+    #[test]
+    fn test_it_ignores_non_index_statements() {
+        let indices = parse_indices("ANALYZE accounts; CREATE INDEX idx_a ON accounts (user_id);");
+
+        assert_eq!(indices.len(), 1);
+        assert_eq!(indices[0].name, "idx_a");
+        assert_eq!(indices[0].table, "accounts");
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/audit_logs.sql",
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/cards.sql",
+        "../../db/schema/transfers.sql",
+        "../../db/schema/transactions.sql",
+        "../../db/schema/loans.sql",
+        "../../db/schema/payments.sql",
+        "../../db/views/average_transaction_amount.sql",
+        "../../db/views/loans_outstanding.sql",
+        "../../db/indices.sql",
+    ))]
+    async fn test_it_measures_each_candidate_index(pool: PgPool) -> sqlx::Result<()> {
+        let bank_system_manager = BankSystemManager::new(pool.clone());
+        bank_system_manager.insert_data().await;
+        let queries = experiment_queries(&pool).await?;
+        let experiment = IndexExperiment::new(pool.clone(), Benchmark::new(1, 5), queries, 2);
+
+        let reports = experiment.run(&candidate_indices()).await?;
+
+        assert_eq!(reports.len(), 6);
+        for report in &reports {
+            assert!(report.size_bytes > 0);
+            assert_eq!(report.queries.len(), SampleQuery::all().len() + 2);
+            println!(
+                "{} - size {} bytes - insert penalty {:.1}%",
+                report.index.name, report.size_bytes, report.insert_penalty_pct
+            );
+            for query in &report.queries {
+                println!("  {} - {:.1}% faster", query.query, query.improvement_pct);
+            }
+        }
+        let remaining =
+            sqlx::query("SELECT indexname FROM pg_indexes WHERE indexname LIKE 'idx_%' AND tablename <> 'audit_logs'")
+                .fetch_all(&pool)
+                .await?;
+        assert_eq!(remaining.len(), 6);
+
+        let mut report = Report::new("Candidate indexes");
+        for index_report in reports {
+            report.add_index_report(index_report);
+        }
+        if let Err(e) = report.write_to_env_dir("indexes") {
+            panic!("{}", e);
+        }
+
+        Ok(())
+    }
+}
@@ -1,4 +1,11 @@
+pub mod audit;
+pub mod benchmarking;
 pub mod caching;
+pub mod denormalisation;
 pub mod enums;
+pub mod indexing;
 pub mod models;
 pub mod optimisations;
+pub mod partitioning;
+pub mod reporting;
+pub mod views;
@@ -1,5 +1,7 @@
 use chrono::{DateTime, Utc};
+use serde::Serialize;
 
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct AccountRowInsertion {
     pub user_id: i32,
     pub account_type: String,
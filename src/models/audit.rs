@@ -1,8 +1,48 @@
+use crate::audit::payload::AuditPayload;
 use crate::enums::audit_log_action::AuditLogAction;
-use chrono::{DateTime, Utc};
+use crate::enums::audit_log_subject_table::AuditLogSubjectTable;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sqlx::postgres::PgRow;
+use sqlx::types::Json;
+use sqlx::Row;
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct AuditLogs {
+    pub id: i32,
+    pub subject_table: AuditLogSubjectTable,
+    pub subject_id: i32,
     pub action: AuditLogAction,
-    pub text: String,
+    pub details: AuditPayload,
     pub timestamp: DateTime<Utc>,
 }
+
+impl AuditLogs {
+    /// Decodes a full `audit_logs` row.
+    pub fn from_row(row: &PgRow) -> Result<Self, sqlx::Error> {
+        let subject_table: String = row.try_get("subject_table")?;
+        let action: String = row.try_get("action")?;
+        let details: Option<Json<AuditPayload>> = row.try_get("details")?;
+        let created_at: NaiveDateTime = row.try_get("created_at")?;
+
+        Ok(Self {
+            id: row.try_get("id")?,
+            subject_table: AuditLogSubjectTable::from_string(&subject_table).ok_or_else(|| {
+                sqlx::Error::Decode(
+                    format!(
+                        "Error: unknown audit log subject table - <subject_table={}>",
+                        subject_table
+                    )
+                    .into(),
+                )
+            })?,
+            subject_id: row.try_get("subject_id")?,
+            action: AuditLogAction::from_string(&action).ok_or_else(|| {
+                sqlx::Error::Decode(
+                    format!("Error: unknown audit log action - <action={}>", action).into(),
+                )
+            })?,
+            details: details.map(|details| details.0).unwrap_or_default(),
+            timestamp: created_at.and_utc(),
+        })
+    }
+}
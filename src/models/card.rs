@@ -1,7 +1,11 @@
 use chrono::{DateTime, Utc};
+use serde::Serialize;
 
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct CardRowInsertion {
     pub account_id: i32,
+    /// Left out of audit payloads.
+    #[serde(skip)]
     pub card_number: String,
     pub card_type: String,
     pub expiration_date: DateTime<Utc>,
@@ -1,5 +1,7 @@
 use chrono::{DateTime, Utc};
+use serde::Serialize;
 
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct LoanRowInsertion {
     pub user_id: i32,
     pub amount: f64,
@@ -1,5 +1,7 @@
 use chrono::{DateTime, Utc};
+use serde::Serialize;
 
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct PaymentRowInsertion {
     pub account_id: i32,
     pub loan_id: i32,
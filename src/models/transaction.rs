@@ -1,5 +1,7 @@
 use chrono::{DateTime, Utc};
+use serde::Serialize;
 
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct TransactionRowInsertion {
     pub account_id: i32,
     pub transaction_type: String,
@@ -1,5 +1,7 @@
 use chrono::{DateTime, Utc};
+use serde::Serialize;
 
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct TransferRowInsertion {
     pub sender_account_id: i32,
     pub receiver_account_id: i32,
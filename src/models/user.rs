@@ -1,6 +1,8 @@
 use chrono::{DateTime, Utc};
+use serde::Serialize;
 use uuid::Uuid;
 
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct UserRowInsertion {
     pub public_id: Uuid,
     pub given_name: String,
@@ -1,6 +1,7 @@
+use crate::audit::payload::{insert_audit_log, AuditPayload};
+use crate::audit::triggers;
 use crate::enums::account_type::AccountType;
 use crate::enums::audit_log_action::AuditLogAction;
-use crate::enums::audit_log_subject_table::AuditLogSubjectTable;
 use crate::enums::card_status::CardStatus;
 use crate::enums::card_type::CardType;
 use crate::enums::loan_status::LoanStatus;
@@ -20,60 +21,126 @@ use fake::faker::creditcard::en::CreditCardNumber;
 use fake::faker::internet::en::{SafeEmail, Username};
 use fake::faker::name::{en::FirstName, en::LastName};
 use fake::faker::phone_number::en::PhoneNumber;
+use fake::rand::rngs::StdRng;
+use fake::rand::{Rng, SeedableRng};
 use fake::Fake;
-use rand::Rng;
+use serde::Serialize;
 use sqlx::{Pool, Postgres, Row};
+use std::sync::Mutex;
+use tokio::sync::OnceCell;
 use uuid::Uuid;
 
-const NUM_USERS: i32 = 100;
-const NUM_ACCOUNTS_PER_USER: i32 = 4;
+pub(crate) const NUM_USERS: i32 = 100;
+pub(crate) const NUM_ACCOUNTS_PER_USER: i32 = 4;
 const NUM_TRANSFERS_PER_ACCOUNT: i32 = 5;
 const NUM_TRANSACTIONS_PER_ACCOUNT: i32 = 2;
+const GENERATOR_ACTOR: &str = "generator";
 
-struct BankSystemManager {
+pub(crate) struct BankSystemManager {
     db: Pool<Postgres>,
+    num_users: i32,
+    /// Shared by the audit logs of every row this manager generates.
+    correlation_id: Uuid,
+    seed: u64,
+    rng: Mutex<StdRng>,
+    /// Whether the audit triggers are installed, checked on the first audited insert.
+    audited_by_triggers: OnceCell<bool>,
 }
 
 impl BankSystemManager {
-    fn new(db: Pool<Postgres>) -> Self {
-        Self { db }
+    pub(crate) fn new(db: Pool<Postgres>) -> Self {
+        Self::with_num_users(db, NUM_USERS)
+    }
+
+    /// Seeds from `BENCHMARK_SEED` when set, so a recorded baseline's data can be regenerated.
+    pub(crate) fn with_num_users(db: Pool<Postgres>, num_users: i32) -> Self {
+        let seed = match std::env::var("BENCHMARK_SEED") {
+            Ok(seed) => seed.parse().unwrap_or_else(|e| {
+                panic!(
+                    "Error: invalid BENCHMARK_SEED - <value={}> - <error={:?}>",
+                    seed, e
+                )
+            }),
+            Err(_) => rand::random(),
+        };
+        Self::with_seed(db, num_users, seed)
+    }
+
+    pub(crate) fn with_seed(db: Pool<Postgres>, num_users: i32, seed: u64) -> Self {
+        Self {
+            db,
+            num_users,
+            correlation_id: Uuid::new_v4(),
+            seed,
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+            audited_by_triggers: OnceCell::new(),
+        }
+    }
+
+    pub(crate) fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    fn with_rng<T>(&self, f: impl FnOnce(&mut StdRng) -> T) -> T {
+        f(&mut self.rng.lock().unwrap())
+    }
+
+    fn num_accounts(&self) -> i32 {
+        self.num_users * NUM_ACCOUNTS_PER_USER
     }
 
     fn random_date_past(&self, lower_limit: i64, upper_limit: i64) -> DateTime<Utc> {
         let now = Utc::now();
         let lower = now - Duration::weeks(lower_limit);
         let upper = now - Duration::weeks(upper_limit);
-        let random_seconds =
-            rand::random::<i64>() % (upper.signed_duration_since(lower).num_seconds());
+        let random_seconds = self.with_rng(|rng| rng.gen::<i64>())
+            % (upper.signed_duration_since(lower).num_seconds());
         lower + Duration::seconds(random_seconds)
     }
 
+    /// Payload for a row the generator created, tagged with this run's correlation id.
+    fn created_payload<T: Serialize>(
+        &self,
+        action: AuditLogAction,
+        row: &T,
+    ) -> Result<AuditPayload, String> {
+        let mut payload = AuditPayload::new(action, None, Some(row))?;
+        payload.actor = Some(GENERATOR_ACTOR.to_string());
+        payload.correlation_id = Some(self.correlation_id);
+        Ok(payload)
+    }
+
+    async fn audited_by_triggers(&self) -> Result<bool, sqlx::Error> {
+        self.audited_by_triggers
+            .get_or_try_init(|| triggers::is_installed(&self.db))
+            .await
+            .copied()
+    }
+
+    /// Writes the audit log for a generated row, unless the audit triggers already have.
     async fn insert_audit_log(
         &self,
-        subject_table: &str,
+        action: AuditLogAction,
         subject_id: i32,
-        action: &str,
-        details: String,
+        details: Result<AuditPayload, String>,
         created_at: DateTime<Utc>,
     ) {
-        if let Err(e) = sqlx::query(
-            "
-            INSERT INTO public.audit_logs 
-            (subject_table, subject_id, action, details, created_at)
-            VALUES ($1, $2, $3, $4, $5);
-            ",
-        )
-        .bind(subject_table)
-        .bind(subject_id)
-        .bind(action)
-        .bind(details)
-        .bind(created_at)
-        .execute(&self.db)
-        .await
-        {
+        let result = match self.audited_by_triggers().await {
+            Ok(true) => return,
+            Ok(false) => details,
+            Err(e) => Err(format!("{:?}", e)),
+        };
+        let result = match result {
+            Ok(details) => insert_audit_log(&self.db, action, subject_id, &details, created_at)
+                .await
+                .map(|_| ())
+                .map_err(|e| format!("{:?}", e)),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
             println!(
-                 "Error: failed to insert row into 'audit_logs' - <subject_table={}> - <subject_id={}> - <action={}> - <error={:?}>",
-                 subject_table, subject_id, action, e
+                 "Error: failed to insert row into 'audit_logs' - <subject_table={}> - <subject_id={}> - <action={}> - <error={}>",
+                 action.subject_table().to_string(), subject_id, action.to_string(), e
              );
         }
     }
@@ -81,21 +148,22 @@ impl BankSystemManager {
     async fn insert_users(&self) {
         let mut users_count = 1;
         loop {
-            if users_count > NUM_USERS {
+            if users_count > self.num_users {
                 break;
             }
 
             let created_at = self.random_date_past(10, 9);
 
-            let user = UserRowInsertion {
-                public_id: Uuid::new_v4(),
-                given_name: FirstName().fake(),
-                family_name: LastName().fake(),
-                username: Username().fake(),
-                email: SafeEmail().fake(),
-                phone: PhoneNumber().fake(),
+            let user = self.with_rng(|rng| UserRowInsertion {
+                public_id: uuid::Builder::from_random_bytes(rng.gen()).into_uuid(),
+                given_name: FirstName().fake_with_rng(rng),
+                family_name: LastName().fake_with_rng(rng),
+                username: Username().fake_with_rng(rng),
+                email: SafeEmail().fake_with_rng(rng),
+                phone: PhoneNumber().fake_with_rng(rng),
                 created_at,
-            };
+            });
+            let details = self.created_payload(AuditLogAction::UserCreated, &user);
             match sqlx::query(
                 "
                 INSERT INTO public.users 
@@ -118,10 +186,9 @@ impl BankSystemManager {
                     let user_id: i32 = row.get::<i32, _>("id");
 
                     self.insert_audit_log(
-                        AuditLogSubjectTable::Users.to_string(),
+                        AuditLogAction::UserCreated,
                         user_id,
-                        AuditLogAction::UserCreated.to_string(),
-                        format!("user id <{}>", user_id),
+                        details,
                         user.created_at,
                     )
                     .await;
@@ -143,7 +210,7 @@ impl BankSystemManager {
         let mut current_user_id = 1;
         let mut accounts_per_user = 0;
         loop {
-            if accounts_count > NUM_USERS * NUM_ACCOUNTS_PER_USER {
+            if accounts_count > self.num_accounts() {
                 break;
             }
 
@@ -165,12 +232,13 @@ impl BankSystemManager {
             let account = AccountRowInsertion {
                 user_id: current_user_id,
                 account_type: account_type.to_string(),
-                balance: format!("{:.2}", rand::rng().random_range(0..=1_000_000))
+                balance: format!("{:.2}", self.with_rng(|rng| rng.gen_range(0..=1_000_000)))
                     .parse()
                     .unwrap_or(0.00),
                 created_at,
                 num_active_cards,
             };
+            let details = self.created_payload(AuditLogAction::AccountCreated, &account);
             match sqlx::query(
                 "
                 INSERT INTO public.accounts
@@ -191,10 +259,9 @@ impl BankSystemManager {
                     let account_id: i32 = row.get::<i32, _>("id");
 
                     self.insert_audit_log(
-                        AuditLogSubjectTable::Accounts.to_string(),
+                        AuditLogAction::AccountCreated,
                         account_id,
-                        AuditLogAction::AccountCreated.to_string(),
-                        format!("account id <{}>", account_id),
+                        details,
                         created_at,
                     )
                     .await;
@@ -220,7 +287,7 @@ impl BankSystemManager {
         let mut current_account_id = 1;
         let mut is_business_account_debit_inserted = false;
         loop {
-            if current_account_id > NUM_USERS * NUM_ACCOUNTS_PER_USER {
+            if current_account_id > self.num_accounts() {
                 break;
             }
 
@@ -268,11 +335,12 @@ impl BankSystemManager {
 
             let card = CardRowInsertion {
                 account_id: current_account_id,
-                card_number: CreditCardNumber().fake(),
+                card_number: self.with_rng(|rng| CreditCardNumber().fake_with_rng(rng)),
                 card_type: card_type.to_string(),
                 expiration_date: created_at,
                 status: CardStatus::Active.to_string(),
             };
+            let details = self.created_payload(AuditLogAction::CardCreated, &card);
             match sqlx::query(
                 "
                 INSERT INTO public.cards
@@ -293,10 +361,9 @@ impl BankSystemManager {
                     let card_id: i32 = row.get::<i32, _>("id");
 
                     self.insert_audit_log(
-                        AuditLogSubjectTable::Cards.to_string(),
+                        AuditLogAction::CardCreated,
                         card_id,
-                        AuditLogAction::CardCreated.to_string(),
-                        format!("card id <{}>", card_id),
+                        details,
                         created_at,
                     )
                     .await;
@@ -322,7 +389,7 @@ impl BankSystemManager {
         let mut current_account_id = 1;
         let mut num_transfers_each_account = 0;
         loop {
-            if current_account_id > NUM_USERS * NUM_ACCOUNTS_PER_USER {
+            if current_account_id > self.num_accounts() {
                 break;
             }
 
@@ -349,19 +416,21 @@ impl BankSystemManager {
             }
 
             // Set receiver to another account
-            let receiver_account_id = (current_account_id + 200) % 400 + 1;
+            let receiver_account_id =
+                (current_account_id + self.num_accounts() / 2) % self.num_accounts() + 1;
 
             let created_at = self.random_date_past(6, 5);
 
             let transfer = TransferRowInsertion {
                 sender_account_id: current_account_id,
                 receiver_account_id,
-                amount: format!("{:.2}", rand::rng().random_range(1..=1_000))
+                amount: format!("{:.2}", self.with_rng(|rng| rng.gen_range(1..=1_000)))
                     .parse()
                     .unwrap_or(1.00),
                 status: TransferStatus::Completed.to_string(),
                 created_at,
             };
+            let details = self.created_payload(AuditLogAction::TransferCreated, &transfer);
             match sqlx::query(
                 "
                 INSERT INTO public.transfers
@@ -382,10 +451,9 @@ impl BankSystemManager {
                     let transfer_id: i32 = row.get::<i32, _>("id");
 
                     self.insert_audit_log(
-                        AuditLogSubjectTable::Transfers.to_string(),
+                        AuditLogAction::TransferCreated,
                         transfer_id,
-                        AuditLogAction::TransferCreated.to_string(),
-                        format!("transfers id <{}>", transfer_id),
+                        details,
                         created_at,
                     )
                     .await;
@@ -410,11 +478,11 @@ impl BankSystemManager {
         let mut current_account_id = 1;
         let mut num_transactions_each_account = 0;
         loop {
-            if current_account_id > NUM_USERS * NUM_ACCOUNTS_PER_USER {
+            if current_account_id > self.num_accounts() {
                 break;
             }
 
-            let account_type = match current_account_id % 4 {
+            let account_type = match current_account_id % NUM_ACCOUNTS_PER_USER {
                 1 => AccountType::Checking,
                 2 => AccountType::Savings,
                 3 => AccountType::Credit,
@@ -452,12 +520,13 @@ impl BankSystemManager {
             let transaction = TransactionRowInsertion {
                 account_id: current_account_id,
                 transaction_type: transaction_type.to_string(),
-                amount: format!("{:.2}", rand::rng().random_range(1..=100_000))
+                amount: format!("{:.2}", self.with_rng(|rng| rng.gen_range(1..=100_000)))
                     .parse()
                     .unwrap_or(1000.00),
                 status: TransactionStatus::Pending.to_string(),
                 created_at,
             };
+            let details = self.created_payload(AuditLogAction::TransactionCreated, &transaction);
             match sqlx::query(
                 "
                 INSERT INTO public.transactions
@@ -478,10 +547,9 @@ impl BankSystemManager {
                     let transaction_id: i32 = row.get::<i32, _>("id");
 
                     self.insert_audit_log(
-                        AuditLogSubjectTable::Transactions.to_string(),
+                        AuditLogAction::TransactionCreated,
                         transaction_id,
-                        AuditLogAction::TransactionCreated.to_string(),
-                        format!("transaction id <{}>", transaction_id),
+                        details,
                         created_at,
                     )
                     .await;
@@ -505,7 +573,7 @@ impl BankSystemManager {
     async fn insert_loans(&self) {
         let mut current_user_id = 1;
         loop {
-            if current_user_id > NUM_USERS {
+            if current_user_id > self.num_users {
                 break;
             }
 
@@ -515,12 +583,13 @@ impl BankSystemManager {
                 user_id: current_user_id,
                 term_months: 24,
                 interest_rate: 4.50,
-                amount: format!("{:.2}", rand::rng().random_range(1..=100_000))
+                amount: format!("{:.2}", self.with_rng(|rng| rng.gen_range(1..=100_000)))
                     .parse()
                     .unwrap_or(10000.00),
                 status: LoanStatus::Active.to_string(),
                 created_at,
             };
+            let details = self.created_payload(AuditLogAction::LoanCreated, &loan);
             match sqlx::query(
                 "
                 INSERT INTO public.loans
@@ -542,10 +611,9 @@ impl BankSystemManager {
                     let loan_id: i32 = row.get::<i32, _>("id");
 
                     self.insert_audit_log(
-                        AuditLogSubjectTable::Loans.to_string(),
+                        AuditLogAction::LoanCreated,
                         loan_id,
-                        AuditLogAction::LoanCreated.to_string(),
-                        format!("loan id <{}>", loan_id),
+                        details,
                         created_at,
                     )
                     .await;
@@ -566,21 +634,22 @@ impl BankSystemManager {
         let mut current_user_id = 1;
         let mut payments_per_loan_inserted = 0;
         loop {
-            if current_user_id > NUM_USERS {
+            if current_user_id > self.num_users {
                 break;
             }
 
             let created_at = self.random_date_past(6, 5);
 
             let payment = PaymentRowInsertion {
-                account_id: ((current_user_id - 1) * 4) + 1,
+                account_id: ((current_user_id - 1) * NUM_ACCOUNTS_PER_USER) + 1,
                 loan_id: current_user_id,
-                amount: format!("{:.2}", rand::rng().random_range(1..=1_000))
+                amount: format!("{:.2}", self.with_rng(|rng| rng.gen_range(1..=1_000)))
                     .parse()
                     .unwrap_or(50.00),
                 status: PaymentStatus::Completed.to_string(),
                 created_at,
             };
+            let details = self.created_payload(AuditLogAction::PaymentCreated, &payment);
             match sqlx::query(
                 "
                 INSERT INTO public.payments
@@ -601,10 +670,9 @@ impl BankSystemManager {
                     let payment_id: i32 = row.get::<i32, _>("id");
 
                     self.insert_audit_log(
-                        AuditLogSubjectTable::Payments.to_string(),
+                        AuditLogAction::PaymentCreated,
                         payment_id,
-                        AuditLogAction::PaymentCreated.to_string(),
-                        format!("loan id <{}>", payment_id),
+                        details,
                         created_at,
                     )
                     .await;
@@ -625,7 +693,7 @@ impl BankSystemManager {
         }
     }
 
-    async fn insert_data(&self) {
+    pub(crate) async fn insert_data(&self) {
         self.insert_users().await;
         self.insert_accounts().await;
         self.insert_cards().await;
@@ -634,15 +702,78 @@ impl BankSystemManager {
         self.insert_loans().await;
         self.insert_payments().await;
     }
+
+    /// Set based equivalent of `insert_data` for seeding sizes the row by row generator is too
+    /// slow for. Expects empty tables, as account and loan ids are assumed to start at 1.
+    pub(crate) async fn bulk_insert_data(&self) -> Result<(), sqlx::Error> {
+        let audited_by_triggers = self.audited_by_triggers().await?;
+        let mut tx = self.db.begin().await?;
+        // Seeds `random()` for the rest of the session, which the seed scripts all run in.
+        sqlx::query("SELECT setseed($1)")
+            .bind(self.seed as f64 / u64::MAX as f64 * 2.0 - 1.0)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(include_str!("../../db/seed/users.sql"))
+            .bind(self.num_users)
+            .execute(&mut *tx)
+            .await?;
+        for sql in [
+            include_str!("../../db/seed/accounts.sql"),
+            include_str!("../../db/seed/cards.sql"),
+            include_str!("../../db/seed/transfers.sql"),
+            include_str!("../../db/seed/transactions.sql"),
+            include_str!("../../db/seed/loans.sql"),
+            include_str!("../../db/seed/payments.sql"),
+        ] {
+            sqlx::raw_sql(sql).execute(&mut *tx).await?;
+        }
+        if !audited_by_triggers {
+            sqlx::raw_sql(include_str!("../../db/seed/audit_logs.sql"))
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod test {
 
     use sqlx::{PgPool, Row};
-    use std::time::Instant;
 
     use super::*;
+    use crate::benchmarking::{baseline, Benchmark, BenchmarkResult};
+    use crate::enums::materialized_view::MaterializedView;
+    use crate::enums::refresh_mode::RefreshMode;
+    use crate::reporting::Report;
+    use crate::views::refresh;
+    use crate::views::scheduler::{RefreshScheduler, SchedulerConfig};
+
+    const BENCHMARK_WARMUP: usize = 5;
+    const BENCHMARK_ITERATIONS: usize = 50;
+
+    /// Checks `raw_sql` and `mat_view` against the baselines and writes their report, named
+    /// after `view`, when the environment asks for either.
+    fn record_view_comparison(
+        view: &str,
+        raw_sql: BenchmarkResult,
+        mat_view: BenchmarkResult,
+        seed: u64,
+    ) {
+        if let Err(e) =
+            baseline::record_or_compare_from_env(&[&raw_sql, &mat_view], NUM_USERS as usize, seed)
+        {
+            panic!("{}", e);
+        }
+
+        let mut report = Report::new(&format!("{} raw SQL vs materialized view", view));
+        report.add_view_comparison(view, raw_sql, mat_view);
+        if let Err(e) = report.write_to_env_dir(view) {
+            panic!("{}", e);
+        }
+    }
 
     #[sqlx::test(fixtures("../../db/schema/users.sql", "../../db/schema/audit_logs.sql"))]
     async fn test_users_inserted(pool: PgPool) -> sqlx::Result<()> {
@@ -674,6 +805,37 @@ mod test {
         Ok(())
     }
 
+    #[sqlx::test(fixtures("../../db/schema/users.sql", "../../db/schema/audit_logs.sql"))]
+    async fn test_same_seed_generates_same_users(pool: PgPool) -> sqlx::Result<()> {
+        let usernames = || async {
+            sqlx::query("SELECT username FROM public.users ORDER BY id")
+                .fetch_all(&pool)
+                .await
+                .map(|rows| {
+                    rows.iter()
+                        .map(|row| row.get::<String, _>("username"))
+                        .collect::<Vec<_>>()
+                })
+        };
+
+        BankSystemManager::with_seed(pool.clone(), 10, 7)
+            .insert_users()
+            .await;
+        let first = usernames().await?;
+        sqlx::raw_sql("TRUNCATE public.users, public.audit_logs RESTART IDENTITY")
+            .execute(&pool)
+            .await?;
+        BankSystemManager::with_seed(pool.clone(), 10, 7)
+            .insert_users()
+            .await;
+        let second = usernames().await?;
+
+        assert_eq!(first.len(), 10);
+        assert_eq!(first, second);
+
+        Ok(())
+    }
+
     #[sqlx::test(fixtures(
         "../../db/schema/users.sql",
         "../../db/schema/accounts.sql",
@@ -890,6 +1052,23 @@ mod test {
             })
             .collect();
         assert_eq!(payment_insertions.len(), 300);
+        // Payment audit logs describe the payment itself, not its loan.
+        let mismatched: i64 = sqlx::query(
+            "
+            SELECT COUNT(*) AS count
+            FROM public.audit_logs a
+            JOIN public.payments p ON p.id = a.subject_id
+            WHERE a.action = 'payment created' AND (
+                (a.details->'after'->>'loan_id')::INT IS DISTINCT FROM p.loan_id
+                OR a.details->>'correlation_id' IS DISTINCT FROM $1
+            );
+            ",
+        )
+        .bind(bank_system_manager.correlation_id.to_string())
+        .fetch_one(&mut *conn)
+        .await?
+        .get("count");
+        assert_eq!(mismatched, 0);
 
         Ok(())
     }
@@ -955,6 +1134,96 @@ mod test {
         Ok(())
     }
 
+    #[sqlx::test(fixtures(
+        "../../db/schema/audit_logs.sql",
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/cards.sql",
+        "../../db/schema/transfers.sql",
+        "../../db/schema/transactions.sql",
+        "../../db/schema/loans.sql",
+        "../../db/schema/payments.sql",
+    ))]
+    async fn test_insertions_scale_with_num_users(pool: PgPool) -> sqlx::Result<()> {
+        let bank_system_manager = BankSystemManager::with_num_users(pool.clone(), 10);
+
+        bank_system_manager.insert_data().await;
+
+        let mut conn = pool.acquire().await?;
+
+        let accounts = sqlx::query("SELECT * FROM public.accounts")
+            .fetch_all(&mut *conn)
+            .await?;
+
+        let max_receiver: i32 =
+            sqlx::query("SELECT MAX(receiver_account_id) AS max_receiver FROM public.transfers")
+                .fetch_one(&mut *conn)
+                .await?
+                .get("max_receiver");
+
+        let payments_from_checking = sqlx::query(
+            "
+            SELECT payments.id
+            FROM public.payments
+            JOIN public.accounts ON payments.account_id = accounts.id
+            WHERE accounts.account_type = 'checking';
+            ",
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+
+        assert_eq!(accounts.len(), 40);
+        assert!(max_receiver <= 40);
+        assert_eq!(payments_from_checking.len(), 30);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/audit_logs.sql",
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/cards.sql",
+        "../../db/schema/transfers.sql",
+        "../../db/schema/transactions.sql",
+        "../../db/schema/loans.sql",
+        "../../db/schema/payments.sql",
+    ))]
+    async fn test_bulk_insertions_match_generator(pool: PgPool) -> sqlx::Result<()> {
+        let bank_system_manager = BankSystemManager::new(pool.clone());
+
+        bank_system_manager.bulk_insert_data().await?;
+
+        let counts = sqlx::query(
+            "
+            SELECT
+                (SELECT COUNT(*) FROM public.users) AS users,
+                (SELECT COUNT(*) FROM public.accounts) AS accounts,
+                (SELECT COUNT(*) FROM public.cards) AS cards,
+                (SELECT COUNT(*) FROM public.transfers) AS transfers,
+                (SELECT COUNT(*) FROM public.transactions) AS transactions,
+                (SELECT COUNT(*) FROM public.loans) AS loans,
+                (SELECT COUNT(*) FROM public.payments) AS payments,
+                (SELECT COUNT(*) FROM public.audit_logs) AS audit_logs,
+                (SELECT COUNT(*) FROM public.accounts WHERE account_type = 'business' AND id % 4 = 0) AS business_accounts;
+            ",
+        )
+        .fetch_one(&pool)
+        .await?;
+
+        assert_eq!(counts.get::<i64, _>("users"), 100);
+        assert_eq!(counts.get::<i64, _>("accounts"), 400);
+        assert_eq!(counts.get::<i64, _>("cards"), 400);
+        assert_eq!(counts.get::<i64, _>("transfers"), 1000);
+        assert_eq!(counts.get::<i64, _>("transactions"), 400);
+        assert_eq!(counts.get::<i64, _>("loans"), 100);
+        assert_eq!(counts.get::<i64, _>("payments"), 300);
+        assert_eq!(counts.get::<i64, _>("audit_logs"), 2700);
+        assert_eq!(counts.get::<i64, _>("business_accounts"), 100);
+
+        Ok(())
+    }
+
     #[sqlx::test(fixtures(
         "../../db/schema/audit_logs.sql",
         "../../db/schema/users.sql",
@@ -973,15 +1242,19 @@ mod test {
 
         bank_system_manager.insert_data().await;
 
-        let mut conn = pool.acquire().await?;
-
-        sqlx::query("REFRESH MATERIALIZED VIEW public.average_transaction_amount")
-            .execute(&mut *conn)
-            .await?;
+        refresh(
+            &pool,
+            MaterializedView::AverageTransactionAmount,
+            RefreshMode::Blocking,
+        )
+        .await?;
 
-        let raw_start = Instant::now();
-        let raw_sql = sqlx::query(
-            "
+        let benchmark = Benchmark::new(BENCHMARK_WARMUP, BENCHMARK_ITERATIONS);
+        let raw_sql = benchmark
+            .run_with_plan(
+                &pool,
+                "average_transaction_amount raw SQL",
+                "
             SELECT 
                 accounts.id AS account_id,
                 AVG(transactions.amount) AS average_transaction
@@ -991,36 +1264,36 @@ mod test {
                 transactions ON accounts.id = transactions.account_id
             GROUP BY accounts.id;
             ",
-        )
-        .fetch_all(&mut *conn)
-        .await?;
-        let raw_duration = raw_start.elapsed();
-
-        let mat_start = Instant::now();
-        let mat_view = sqlx::query("SELECT * FROM public.average_transaction_amount")
-            .fetch_all(&mut *conn)
+            )
+            .await?;
+        let mat_view = benchmark
+            .run_with_plan(
+                &pool,
+                "average_transaction_amount materialized view",
+                "SELECT * FROM public.average_transaction_amount",
+            )
             .await?;
-        let mat_duration = mat_start.elapsed();
 
-        assert_eq!(raw_sql.len(), mat_view.len());
-        println!("mat_view len = {:?}", mat_view.len());
+        assert_eq!(raw_sql.rows, mat_view.rows);
+        println!("mat_view len = {:?}", mat_view.rows);
 
-        let mut rows_printed = 0;
-        for row in mat_view.iter() {
-            if rows_printed == 5 {
-                break;
-            }
-            println!("{:?}", row);
-            rows_printed += 1;
+        println!("Raw SQL query: {:?}", raw_sql.stats);
+        if let Some(plan) = &raw_sql.plan {
+            println!("{}", plan.summary());
+        }
+        println!("Materialized view query: {:?}", mat_view.stats);
+        if let Some(plan) = &mat_view.plan {
+            println!("{}", plan.summary());
         }
-
-        println!("Time for raw SQL query: {:?}", raw_duration);
-        println!("Time for materialized view query: {:?}", mat_duration);
         println!(
-            "Percentage materialized view faster than raw SQL query: {:?}",
-            ((raw_duration.as_secs_f64() - mat_duration.as_secs_f64())
-                / raw_duration.as_secs_f64())
-                * 100.00
+            "Percentage materialized view median faster than raw SQL query: {:?}",
+            mat_view.percentage_faster_than(&raw_sql)
+        );
+        record_view_comparison(
+            "average_transaction_amount",
+            raw_sql,
+            mat_view,
+            bank_system_manager.seed(),
         );
 
         Ok(())
@@ -1044,15 +1317,19 @@ mod test {
 
         bank_system_manager.insert_data().await;
 
-        let mut conn = pool.acquire().await?;
-
-        sqlx::query("REFRESH MATERIALIZED VIEW public.loans_outstanding")
-            .execute(&mut *conn)
-            .await?;
+        refresh(
+            &pool,
+            MaterializedView::LoansOutstanding,
+            RefreshMode::Blocking,
+        )
+        .await?;
 
-        let raw_start = Instant::now();
-        let raw_sql = sqlx::query(
-            "
+        let benchmark = Benchmark::new(BENCHMARK_WARMUP, BENCHMARK_ITERATIONS);
+        let raw_sql = benchmark
+            .run_with_plan(
+                &pool,
+                "loans_outstanding raw SQL",
+                "
             SELECT 
                 users.id AS user_id,
                 SUM(loans.amount) AS sum_loans_outstanding
@@ -1063,36 +1340,36 @@ mod test {
             GROUP BY 
                 users.id;
             ",
-        )
-        .fetch_all(&mut *conn)
-        .await?;
-        let raw_duration = raw_start.elapsed();
-
-        let mat_start = Instant::now();
-        let mat_view = sqlx::query("SELECT * FROM public.loans_outstanding")
-            .fetch_all(&mut *conn)
+            )
+            .await?;
+        let mat_view = benchmark
+            .run_with_plan(
+                &pool,
+                "loans_outstanding materialized view",
+                "SELECT * FROM public.loans_outstanding",
+            )
             .await?;
-        let mat_duration = mat_start.elapsed();
 
-        assert_eq!(raw_sql.len(), mat_view.len());
-        println!("mat_view len = {:?}", mat_view.len());
+        assert_eq!(raw_sql.rows, mat_view.rows);
+        println!("mat_view len = {:?}", mat_view.rows);
 
-        let mut rows_printed = 0;
-        for row in mat_view.iter() {
-            if rows_printed == 5 {
-                break;
-            }
-            println!("{:?}", row);
-            rows_printed += 1;
+        println!("Raw SQL query: {:?}", raw_sql.stats);
+        if let Some(plan) = &raw_sql.plan {
+            println!("{}", plan.summary());
+        }
+        println!("Materialized view query: {:?}", mat_view.stats);
+        if let Some(plan) = &mat_view.plan {
+            println!("{}", plan.summary());
         }
-
-        println!("Time for raw SQL query: {:?}", raw_duration);
-        println!("Time for materialized view query: {:?}", mat_duration);
         println!(
-            "Percentage materialized view faster than raw SQL query: {:?}",
-            ((raw_duration.as_secs_f64() - mat_duration.as_secs_f64())
-                / raw_duration.as_secs_f64())
-                * 100.00
+            "Percentage materialized view median faster than raw SQL query: {:?}",
+            mat_view.percentage_faster_than(&raw_sql)
+        );
+        record_view_comparison(
+            "loans_outstanding",
+            raw_sql,
+            mat_view,
+            bank_system_manager.seed(),
         );
 
         Ok(())
@@ -1117,18 +1394,16 @@ mod test {
 
         bank_system_manager.insert_data().await;
 
-        let mut conn = pool.acquire().await?;
-
-        sqlx::query("REFRESH MATERIALIZED VIEW public.average_transaction_amount")
-            .execute(&mut *conn)
-            .await?;
-
-        sqlx::query("REFRESH MATERIALIZED VIEW public.suspicious_transactions")
-            .execute(&mut *conn)
+        RefreshScheduler::new(pool.clone(), SchedulerConfig::new(RefreshMode::Blocking))
+            .await?
+            .refresh_all()
             .await?;
 
-        let raw_start = Instant::now();
-        let raw_sql = sqlx::query(
+        let benchmark = Benchmark::new(BENCHMARK_WARMUP, BENCHMARK_ITERATIONS);
+        let raw_sql = benchmark
+            .run_with_plan(
+                &pool,
+                "suspicious_transactions raw SQL",
             "
             WITH transaction_counts AS (
                 SELECT 
@@ -1159,36 +1434,36 @@ mod test {
                 (tc.amount > (ata.average_transaction * 1.5) OR
                 (tc.amount < 10 AND tc.transaction_count_last_10_minutes > 5));
             ",
-        )
-        .fetch_all(&mut *conn)
-        .await?;
-        let raw_duration = raw_start.elapsed();
-
-        let mat_start = Instant::now();
-        let mat_view = sqlx::query("SELECT * FROM public.suspicious_transactions")
-            .fetch_all(&mut *conn)
+            )
+            .await?;
+        let mat_view = benchmark
+            .run_with_plan(
+                &pool,
+                "suspicious_transactions materialized view",
+                "SELECT * FROM public.suspicious_transactions",
+            )
             .await?;
-        let mat_duration = mat_start.elapsed();
 
-        assert_eq!(raw_sql.len(), mat_view.len());
-        println!("mat_view len = {:?}", mat_view.len());
+        assert_eq!(raw_sql.rows, mat_view.rows);
+        println!("mat_view len = {:?}", mat_view.rows);
 
-        let mut rows_printed = 0;
-        for row in mat_view.iter() {
-            if rows_printed == 5 {
-                break;
-            }
-            println!("{:?}", row);
-            rows_printed += 1;
+        println!("Raw SQL query: {:?}", raw_sql.stats);
+        if let Some(plan) = &raw_sql.plan {
+            println!("{}", plan.summary());
+        }
+        println!("Materialized view query: {:?}", mat_view.stats);
+        if let Some(plan) = &mat_view.plan {
+            println!("{}", plan.summary());
         }
-
-        println!("Time for raw SQL query: {:?}", raw_duration);
-        println!("Time for materialized view query: {:?}", mat_duration);
         println!(
-            "Percentage materialized view faster than raw SQL query: {:?}",
-            ((raw_duration.as_secs_f64() - mat_duration.as_secs_f64())
-                / raw_duration.as_secs_f64())
-                * 100.00
+            "Percentage materialized view median faster than raw SQL query: {:?}",
+            mat_view.percentage_faster_than(&raw_sql)
+        );
+        record_view_comparison(
+            "suspicious_transactions",
+            raw_sql,
+            mat_view,
+            bank_system_manager.seed(),
         );
 
         Ok(())
@@ -0,0 +1,342 @@
+use crate::benchmarking::explain::explain_estimate;
+use crate::benchmarking::{insert_throughput, Benchmark, BenchmarkResult};
+use crate::enums::partitioned_table::PartitionedTable;
+use sqlx::{Pool, Postgres, Row};
+use std::collections::BTreeSet;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartitionQueryComparison {
+    pub query: String,
+    pub unpartitioned: BenchmarkResult,
+    pub partitioned: BenchmarkResult,
+    /// Partitions left in the plan after pruning.
+    pub partitions_scanned: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartitionReport {
+    pub table: PartitionedTable,
+    /// Monthly partitions plus the default partition.
+    pub partitions: usize,
+    pub rows_migrated: u64,
+    pub migration: Duration,
+    /// The month the time bounded queries ran over, as `YYYY-MM-DD`.
+    pub month: Option<String>,
+    pub queries: Vec<PartitionQueryComparison>,
+    pub inserts_per_sec_unpartitioned: f64,
+    pub inserts_per_sec_partitioned: f64,
+}
+
+/// Copies tables into monthly range partitions by `created_at` and compares the copies with the
+/// originals.
+///
+/// The partitioned copies are dropped afterwards, the originals are left untouched.
+pub struct PartitionExperiment {
+    db: Pool<Postgres>,
+    benchmark: Benchmark,
+    insert_batches: usize,
+}
+
+impl PartitionExperiment {
+    pub fn new(db: Pool<Postgres>, benchmark: Benchmark, insert_batches: usize) -> Self {
+        Self {
+            db,
+            benchmark,
+            insert_batches,
+        }
+    }
+
+    pub async fn run(
+        &self,
+        tables: &[PartitionedTable],
+    ) -> Result<Vec<PartitionReport>, sqlx::Error> {
+        let mut reports = Vec::with_capacity(tables.len());
+        for table in tables {
+            let report = self.measure(*table).await;
+            self.execute(&format!(
+                "DROP TABLE IF EXISTS public.{} CASCADE",
+                table.partitioned_name()
+            ))
+            .await?;
+            reports.push(report?);
+        }
+
+        Ok(reports)
+    }
+
+    async fn measure(&self, table: PartitionedTable) -> Result<PartitionReport, sqlx::Error> {
+        let months = self.create_partitioned_copy(table).await?;
+
+        let start = Instant::now();
+        let rows_migrated = sqlx::query(&format!(
+            "INSERT INTO public.{} SELECT * FROM public.{}",
+            table.partitioned_name(),
+            table.to_string()
+        ))
+        .execute(&self.db)
+        .await?
+        .rows_affected();
+        let migration = start.elapsed();
+        self.execute(&format!(
+            "SELECT setval('public.{partitioned}_id_seq', COALESCE(MAX(id), 0) + 1, false) FROM public.{partitioned}",
+            partitioned = table.partitioned_name()
+        ))
+        .await?;
+        self.execute(&format!(
+            "ANALYZE public.{}; ANALYZE public.{};",
+            table.to_string(),
+            table.partitioned_name()
+        ))
+        .await?;
+
+        let mut queries = Vec::new();
+        let month = months.last().cloned();
+        if let Some((from, to)) = &month {
+            for (name, template) in table.time_bounded_queries() {
+                let sql = |name: &str| {
+                    template
+                        .replace("{table}", name)
+                        .replace("{from}", from)
+                        .replace("{to}", to)
+                };
+                let unpartitioned = self
+                    .benchmark
+                    .run(
+                        &self.db,
+                        &format!("{} {}", table.to_string(), name),
+                        &sql(table.to_string()),
+                    )
+                    .await?;
+                let partitioned_sql = sql(table.partitioned_name());
+                let partitioned = self
+                    .benchmark
+                    .run(
+                        &self.db,
+                        &format!("{} {}", table.partitioned_name(), name),
+                        &partitioned_sql,
+                    )
+                    .await?;
+                let plan = explain_estimate(&self.db, &partitioned_sql).await?;
+                let partition_prefix = format!("{}_", table.partitioned_name());
+                let partitions_scanned = plan
+                    .root
+                    .nodes()
+                    .iter()
+                    .filter_map(|node| node.relation_name.as_deref())
+                    .filter(|relation| relation.starts_with(&partition_prefix))
+                    .collect::<BTreeSet<_>>()
+                    .len();
+
+                queries.push(PartitionQueryComparison {
+                    query: name.to_string(),
+                    unpartitioned,
+                    partitioned,
+                    partitions_scanned,
+                });
+            }
+        }
+
+        let inserts_per_sec_unpartitioned =
+            insert_throughput(&self.db, table.to_string(), self.insert_batches).await?;
+        let inserts_per_sec_partitioned =
+            insert_throughput(&self.db, table.partitioned_name(), self.insert_batches).await?;
+
+        Ok(PartitionReport {
+            table,
+            partitions: months.len() + 1,
+            rows_migrated,
+            migration,
+            month: month.map(|(from, _)| from),
+            queries,
+            inserts_per_sec_unpartitioned,
+            inserts_per_sec_partitioned,
+        })
+    }
+
+    /// Creates the partitioned table with a partition per month the original has rows in, a
+    /// default partition for anything else, and the original's non-unique indexes. Returns the
+    /// `(from, to)` bounds of each monthly partition in order.
+    async fn create_partitioned_copy(
+        &self,
+        table: PartitionedTable,
+    ) -> Result<Vec<(String, String)>, sqlx::Error> {
+        let partitioned = table.partitioned_name();
+        self.execute(&format!(
+            "
+            DROP TABLE IF EXISTS public.{partitioned} CASCADE;
+            CREATE TABLE public.{partitioned}
+                (LIKE public.{table} INCLUDING DEFAULTS INCLUDING CONSTRAINTS)
+                PARTITION BY RANGE (created_at);
+            CREATE TABLE public.{partitioned}_default PARTITION OF public.{partitioned} DEFAULT;
+            -- Its own id sequence, so inserts into the copy leave the original's ids alone.
+            CREATE SEQUENCE public.{partitioned}_id_seq OWNED BY public.{partitioned}.id;
+            ALTER TABLE public.{partitioned}
+                ALTER COLUMN id SET DEFAULT nextval('public.{partitioned}_id_seq');
+            -- Stands in for the original's primary key, which has to include the partition key.
+            ALTER TABLE public.{partitioned} ADD PRIMARY KEY (id, created_at);
+            ",
+            partitioned = partitioned,
+            table = table.to_string()
+        ))
+        .await?;
+
+        let months: Vec<(String, String, String)> = sqlx::query(&format!(
+            "
+            SELECT
+                to_char(month, 'YYYY_MM') AS suffix,
+                to_char(month, 'YYYY-MM-DD') AS month_from,
+                to_char(month + INTERVAL '1 month', 'YYYY-MM-DD') AS month_to
+            FROM generate_series(
+                (SELECT date_trunc('month', MIN(created_at)) FROM public.{table}),
+                (SELECT date_trunc('month', MAX(created_at)) FROM public.{table}),
+                INTERVAL '1 month'
+            ) AS month
+            ORDER BY month;
+            ",
+            table = table.to_string()
+        ))
+        .fetch_all(&self.db)
+        .await?
+        .iter()
+        .map(|row| {
+            (
+                row.get("suffix"),
+                row.get("month_from"),
+                row.get("month_to"),
+            )
+        })
+        .collect();
+        for (suffix, from, to) in &months {
+            self.execute(&format!(
+                "CREATE TABLE public.{partitioned}_{suffix} PARTITION OF public.{partitioned} FOR VALUES FROM ('{from}') TO ('{to}')",
+                partitioned = partitioned,
+                suffix = suffix,
+                from = from,
+                to = to
+            ))
+            .await?;
+        }
+
+        // Unique indexes would have to include created_at, and the primary key is replaced above.
+        let indexes: Vec<String> = sqlx::query(
+            "
+            SELECT pg_get_indexdef(i.indexrelid) AS definition
+            FROM pg_index i
+            JOIN pg_class c ON c.oid = i.indrelid
+            JOIN pg_namespace n ON n.oid = c.relnamespace
+            WHERE n.nspname = 'public' AND c.relname = $1 AND NOT i.indisunique;
+            ",
+        )
+        .bind(table.to_string())
+        .fetch_all(&self.db)
+        .await?
+        .iter()
+        .map(|row| row.get("definition"))
+        .collect();
+        for definition in indexes {
+            if let Some((_, method)) = definition.split_once(" USING ") {
+                self.execute(&format!(
+                    "CREATE INDEX ON public.{} USING {}",
+                    partitioned, method
+                ))
+                .await?;
+            }
+        }
+
+        Ok(months.into_iter().map(|(_, from, to)| (from, to)).collect())
+    }
+
+    async fn execute(&self, sql: &str) -> Result<(), sqlx::Error> {
+        sqlx::raw_sql(sql).execute(&self.db).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::optimisations::BankSystemManager;
+    use crate::reporting::Report;
+    use sqlx::PgPool;
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/audit_logs.sql",
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/cards.sql",
+        "../../db/schema/transfers.sql",
+        "../../db/schema/transactions.sql",
+        "../../db/schema/loans.sql",
+        "../../db/schema/payments.sql",
+        "../../db/indices.sql",
+    ))]
+    async fn test_it_compares_partitioned_copies(pool: PgPool) -> sqlx::Result<()> {
+        BankSystemManager::with_num_users(pool.clone(), 500)
+            .bulk_insert_data()
+            .await?;
+        // Generated rows fall within a few weeks, so spread them over a year to give pruning
+        // something to do.
+        sqlx::raw_sql(
+            "
+            UPDATE public.transactions SET created_at = created_at - (id % 12) * INTERVAL '1 month';
+            UPDATE public.audit_logs SET created_at = created_at - (id % 12) * INTERVAL '1 month';
+            ",
+        )
+        .execute(&pool)
+        .await?;
+        let experiment = PartitionExperiment::new(pool.clone(), Benchmark::new(1, 5), 1);
+
+        let reports = experiment.run(&PartitionedTable::all()).await?;
+
+        assert_eq!(reports.len(), 2);
+        let mut report = Report::new("Partitioning");
+        for partition_report in reports {
+            let rows: i64 = sqlx::query(&format!(
+                "SELECT COUNT(*) AS count FROM public.{}",
+                partition_report.table.to_string()
+            ))
+            .fetch_one(&pool)
+            .await?
+            .get("count");
+            assert_eq!(partition_report.rows_migrated, rows as u64);
+            assert!(partition_report.partitions >= 13);
+            assert_eq!(
+                partition_report.queries.len(),
+                partition_report.table.time_bounded_queries().len()
+            );
+            println!(
+                "{} - {} partitions, migrated {} rows in {:?} - inserts/sec {:.0} unpartitioned, {:.0} partitioned",
+                partition_report.table.to_string(),
+                partition_report.partitions,
+                partition_report.rows_migrated,
+                partition_report.migration,
+                partition_report.inserts_per_sec_unpartitioned,
+                partition_report.inserts_per_sec_partitioned
+            );
+            for query in partition_report.queries {
+                assert_eq!(query.partitions_scanned, 1);
+                assert_eq!(query.unpartitioned.rows, query.partitioned.rows);
+                println!(
+                    "  {} - unpartitioned {:?}, partitioned {:?}, {} partition scanned",
+                    query.query,
+                    query.unpartitioned.stats.median,
+                    query.partitioned.stats.median,
+                    query.partitions_scanned
+                );
+                report.add_benchmark(query.unpartitioned);
+                report.add_benchmark(query.partitioned);
+            }
+            let remaining = sqlx::query("SELECT 1 FROM pg_tables WHERE tablename = $1")
+                .bind(partition_report.table.partitioned_name())
+                .fetch_optional(&pool)
+                .await?;
+            assert!(remaining.is_none());
+        }
+        if let Err(e) = report.write_to_env_dir("partitioning") {
+            panic!("{}", e);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,389 @@
+pub mod svg;
+
+use crate::benchmarking::BenchmarkResult;
+use crate::indexing::IndexReport;
+use chrono::{DateTime, Utc};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViewComparison {
+    pub view: String,
+    pub raw_sql: BenchmarkResult,
+    pub materialized_view: BenchmarkResult,
+}
+
+/// Collects experiment results and renders them as Markdown or a self-contained HTML page.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub title: String,
+    pub generated_at: DateTime<Utc>,
+    pub benchmarks: Vec<BenchmarkResult>,
+    pub view_comparisons: Vec<ViewComparison>,
+    pub index_reports: Vec<IndexReport>,
+}
+
+impl Report {
+    pub fn new(title: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            generated_at: Utc::now(),
+            benchmarks: Vec::new(),
+            view_comparisons: Vec::new(),
+            index_reports: Vec::new(),
+        }
+    }
+
+    pub fn add_benchmark(&mut self, result: BenchmarkResult) {
+        self.benchmarks.push(result);
+    }
+
+    /// Adds both results as benchmarks too, so their latencies and plans are rendered.
+    pub fn add_view_comparison(
+        &mut self,
+        view: &str,
+        raw_sql: BenchmarkResult,
+        materialized_view: BenchmarkResult,
+    ) {
+        self.benchmarks.push(raw_sql.clone());
+        self.benchmarks.push(materialized_view.clone());
+        self.view_comparisons.push(ViewComparison {
+            view: view.to_string(),
+            raw_sql,
+            materialized_view,
+        });
+    }
+
+    pub fn add_index_report(&mut self, report: IndexReport) {
+        self.index_reports.push(report);
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut md = format!(
+            "# {}\n\nGenerated {}\n",
+            self.title,
+            self.generated_at.to_rfc3339()
+        );
+
+        if !self.benchmarks.is_empty() {
+            md.push_str("\n## Benchmarks\n\n");
+            md.push_str("| Query | Rows | Iterations | Min (ms) | Median (ms) | p95 (ms) | p99 (ms) | Max (ms) | Std dev (ms) |\n");
+            md.push_str("|---|---:|---:|---:|---:|---:|---:|---:|---:|\n");
+            for result in &self.benchmarks {
+                md.push_str(&format!(
+                    "| {} | {} | {} | {:.3} | {:.3} | {:.3} | {:.3} | {:.3} | {:.3} |\n",
+                    escape_markdown(&result.name),
+                    result.rows,
+                    result.stats.iterations,
+                    ms(result.stats.min),
+                    ms(result.stats.median),
+                    ms(result.stats.p95),
+                    ms(result.stats.p99),
+                    ms(result.stats.max),
+                    ms(result.stats.stddev)
+                ));
+            }
+        }
+
+        if !self.view_comparisons.is_empty() {
+            md.push_str("\n## Raw SQL vs materialized view\n\n");
+            md.push_str("| View | Raw median (ms) | Materialized median (ms) | Faster by |\n");
+            md.push_str("|---|---:|---:|---:|\n");
+            for comparison in &self.view_comparisons {
+                md.push_str(&format!(
+                    "| {} | {:.3} | {:.3} | {:.1}% |\n",
+                    escape_markdown(&comparison.view),
+                    ms(comparison.raw_sql.stats.median),
+                    ms(comparison.materialized_view.stats.median),
+                    comparison
+                        .materialized_view
+                        .percentage_faster_than(&comparison.raw_sql)
+                ));
+            }
+        }
+
+        if !self.index_reports.is_empty() {
+            md.push_str("\n## Indexes\n\n");
+            md.push_str(
+                "| Index | Size (bytes) | Insert penalty | Best query | Best improvement |\n",
+            );
+            md.push_str("|---|---:|---:|---|---:|\n");
+            for report in &self.index_reports {
+                let best = report
+                    .queries
+                    .iter()
+                    .max_by(|a, b| a.improvement_pct.total_cmp(&b.improvement_pct));
+                md.push_str(&format!(
+                    "| {} | {} | {:.1}% | {} | {} |\n",
+                    escape_markdown(&report.index.name),
+                    report.size_bytes,
+                    report.insert_penalty_pct,
+                    best.map(|query| escape_markdown(&query.query))
+                        .unwrap_or_default(),
+                    best.map(|query| format!("{:.1}%", query.improvement_pct))
+                        .unwrap_or_default()
+                ));
+            }
+        }
+
+        let plans: Vec<&BenchmarkResult> = self
+            .benchmarks
+            .iter()
+            .filter(|result| result.plan.is_some())
+            .collect();
+        if !plans.is_empty() {
+            md.push_str("\n## Plans\n");
+            for result in plans {
+                if let Some(plan) = &result.plan {
+                    md.push_str(&format!(
+                        "\n### {}\n\n```\n{}\n```\n",
+                        escape_markdown(&result.name),
+                        plan.summary()
+                    ));
+                }
+            }
+        }
+
+        md
+    }
+
+    /// A single HTML file with inline CSS and SVG charts, so it can be shared as is.
+    pub fn to_html(&self) -> String {
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>{style}</style>\n</head>\n<body>\n<h1>{title}</h1>\n<p>Generated {generated}</p>\n",
+            title = escape_html(&self.title),
+            style = STYLE,
+            generated = self.generated_at.to_rfc3339()
+        );
+
+        if !self.view_comparisons.is_empty() {
+            html.push_str("<h2>Raw SQL vs materialized view</h2>\n");
+            let rows: Vec<(String, Duration, Duration)> = self
+                .view_comparisons
+                .iter()
+                .map(|comparison| {
+                    (
+                        comparison.view.clone(),
+                        comparison.raw_sql.stats.median,
+                        comparison.materialized_view.stats.median,
+                    )
+                })
+                .collect();
+            html.push_str(&svg::comparison_bars(&rows));
+            html.push('\n');
+        }
+
+        if !self.index_reports.is_empty() {
+            html.push_str("<h2>Indexes</h2>\n<table>\n<tr><th>Index</th><th>Size (bytes)</th><th>Insert penalty</th><th>Query</th><th>Improvement</th></tr>\n");
+            for report in &self.index_reports {
+                let rowspan = report.queries.len().max(1);
+                let index_cells = format!(
+                    "<td rowspan=\"{}\">{}</td><td rowspan=\"{}\">{}</td><td rowspan=\"{}\">{:.1}%</td>",
+                    rowspan,
+                    escape_html(&report.index.name),
+                    rowspan,
+                    report.size_bytes,
+                    rowspan,
+                    report.insert_penalty_pct
+                );
+                if report.queries.is_empty() {
+                    html.push_str(&format!("<tr>{}<td></td><td></td></tr>\n", index_cells));
+                }
+                for (i, query) in report.queries.iter().enumerate() {
+                    html.push_str(&format!(
+                        "<tr>{}<td>{}</td><td>{:.1}%</td></tr>\n",
+                        if i == 0 { index_cells.as_str() } else { "" },
+                        escape_html(&query.query),
+                        query.improvement_pct
+                    ));
+                }
+            }
+            html.push_str("</table>\n");
+        }
+
+        if !self.benchmarks.is_empty() {
+            html.push_str("<h2>Benchmarks</h2>\n");
+            for result in &self.benchmarks {
+                html.push_str(&format!(
+                    "<section>\n<h3>{}</h3>\n<p>{} rows, {} iterations - min {:.3} ms, median {:.3} ms, p95 {:.3} ms, p99 {:.3} ms, max {:.3} ms, std dev {:.3} ms</p>\n{}\n",
+                    escape_html(&result.name),
+                    result.rows,
+                    result.stats.iterations,
+                    ms(result.stats.min),
+                    ms(result.stats.median),
+                    ms(result.stats.p95),
+                    ms(result.stats.p99),
+                    ms(result.stats.max),
+                    ms(result.stats.stddev),
+                    svg::latency_histogram(&result.samples)
+                ));
+                if let Some(plan) = &result.plan {
+                    html.push_str(&format!("<pre>{}</pre>\n", escape_html(&plan.summary())));
+                }
+                html.push_str("</section>\n");
+            }
+        }
+
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+
+    /// Writes `<stem>.md` and `<stem>.html` into `dir`, returning their paths.
+    pub fn write(&self, dir: &Path, stem: &str) -> Result<(PathBuf, PathBuf), String> {
+        let markdown_path = dir.join(format!("{}.md", stem));
+        let html_path = dir.join(format!("{}.html", stem));
+        for (path, contents) in [
+            (&markdown_path, self.to_markdown()),
+            (&html_path, self.to_html()),
+        ] {
+            fs::create_dir_all(dir)
+                .and_then(|_| fs::write(path, contents))
+                .map_err(|e| {
+                    format!(
+                        "Error: failed to write report - <path={}> - <error={:?}>",
+                        path.display(),
+                        e
+                    )
+                })?;
+        }
+
+        Ok((markdown_path, html_path))
+    }
+
+    /// Writes the report into `BENCHMARK_REPORT_DIR`, if set.
+    pub fn write_to_env_dir(&self, stem: &str) -> Result<Option<(PathBuf, PathBuf)>, String> {
+        match std::env::var("BENCHMARK_REPORT_DIR") {
+            Ok(dir) => self.write(Path::new(&dir), stem).map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+const STYLE: &str = "body{font-family:sans-serif;max-width:960px;margin:2em auto;color:#222}table{border-collapse:collapse}th,td{border:1px solid #ccc;padding:4px 8px;text-align:left}pre{background:#f6f6f6;padding:8px;overflow-x:auto}section{margin-bottom:2em}";
+
+fn ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1_000.0
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn escape_markdown(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::benchmarking::explain::QueryPlan;
+    use crate::benchmarking::BenchmarkStats;
+    use crate::indexing::IndexDefinition;
+    use uuid::Uuid;
+
+    fn result(name: &str, samples_ms: &[u64]) -> BenchmarkResult {
+        let samples: Vec<Duration> = samples_ms
+            .iter()
+            .copied()
+            .map(Duration::from_millis)
+            .collect();
+        BenchmarkResult {
+            name: name.to_string(),
+            rows: 10,
+            stats: BenchmarkStats::from_samples(&samples),
+            samples,
+            plan: None,
+        }
+    }
+
+    fn report() -> Report {
+        let mut report = Report::new("Materialized views");
+        report.add_view_comparison(
+            "loans_outstanding",
+            result("loans_outstanding raw SQL", &[4, 4, 5]),
+            result("loans_outstanding materialized view", &[1, 1, 2]),
+        );
+        report
+    }
+
+    #[test]
+    fn test_markdown_summarises_comparisons() {
+        let markdown = report().to_markdown();
+
+        assert!(markdown.starts_with("# Materialized views\n"));
+        assert!(markdown.contains("| loans_outstanding raw SQL | 10 | 3 | 4.000 | 4.000 |"));
+        assert!(markdown.contains("| loans_outstanding | 4.000 | 1.000 | 75.0% |"));
+        assert!(!markdown.contains("## Indexes"));
+    }
+
+    #[test]
+    fn test_html_is_self_contained() {
+        let mut report = report();
+        report.title = String::from("<script>");
+
+        let html = report.to_html();
+
+        assert!(html.contains("<title>&lt;script&gt;</title>"));
+        assert_eq!(html.matches("<svg").count(), 3);
+        assert!(!html.contains("src=\"http"));
+        assert!(!html.contains("href=\"http"));
+    }
+
+    #[test]
+    fn test_html_lists_indexes_without_queries() {
+        let mut report = report();
+        report.add_index_report(IndexReport {
+            index: IndexDefinition {
+                name: String::from("idx_unused"),
+                table: String::from("loans"),
+                definition: String::from("CREATE INDEX idx_unused ON loans(status);"),
+            },
+            size_bytes: 8192,
+            queries: Vec::new(),
+            inserts_per_sec_with_index: 90.0,
+            inserts_per_sec_without_index: 100.0,
+            insert_penalty_pct: 10.0,
+        });
+
+        let html = report.to_html();
+
+        assert!(html.contains(
+            "<tr><td rowspan=\"1\">idx_unused</td><td rowspan=\"1\">8192</td><td rowspan=\"1\">10.0%</td><td></td><td></td></tr>"
+        ));
+    }
+
+    #[test]
+    fn test_markdown_escapes_plan_headings() {
+        let mut report = report();
+        let mut with_plan = result("a | b", &[1]);
+        with_plan.plan = Some(
+            serde_json::from_str::<QueryPlan>(
+                r#"{"Plan": {"Node Type": "Result", "Startup Cost": 0.0, "Total Cost": 0.01, "Plan Rows": 1}}"#,
+            )
+            .unwrap(),
+        );
+        report.add_benchmark(with_plan);
+
+        let markdown = report.to_markdown();
+
+        assert!(markdown.contains("\n### a \\| b\n"));
+    }
+
+    #[test]
+    fn test_it_writes_markdown_and_html() {
+        let dir = std::env::temp_dir().join(format!("report-{}", Uuid::new_v4()));
+
+        let written = report().write(&dir, "views");
+        let html = fs::read_to_string(dir.join("views.html"));
+        let _ = fs::remove_dir_all(&dir);
+
+        let (markdown_path, html_path) = written.unwrap();
+        assert_eq!(markdown_path, dir.join("views.md"));
+        assert_eq!(html_path, dir.join("views.html"));
+        assert!(html.unwrap().ends_with("</html>\n"));
+    }
+}
@@ -0,0 +1,155 @@
+use super::escape_html;
+use std::time::Duration;
+
+const WIDTH: f64 = 640.0;
+const HISTOGRAM_HEIGHT: f64 = 160.0;
+const HISTOGRAM_BINS: usize = 20;
+const BAR_HEIGHT: f64 = 18.0;
+const LABEL_WIDTH: f64 = 260.0;
+const MARGIN: f64 = 24.0;
+
+/// Counts of `samples` in `bins` equal-width buckets spanning the fastest to the slowest sample.
+pub fn histogram(samples: &[Duration], bins: usize) -> Vec<usize> {
+    if bins == 0 {
+        return Vec::new();
+    }
+    let mut counts = vec![0; bins];
+    let (Some(min), Some(max)) = (samples.iter().min(), samples.iter().max()) else {
+        return counts;
+    };
+    let span = (*max - *min).as_secs_f64();
+    for sample in samples {
+        let bin = if span == 0.0 {
+            0
+        } else {
+            (((*sample - *min).as_secs_f64() / span) * bins as f64) as usize
+        };
+        counts[bin.min(bins - 1)] += 1;
+    }
+    counts
+}
+
+/// Histogram of one benchmark's samples, with the fastest and slowest sample labelled.
+pub fn latency_histogram(samples: &[Duration]) -> String {
+    let counts = histogram(samples, HISTOGRAM_BINS);
+    let tallest = counts.iter().copied().max().unwrap_or(0).max(1) as f64;
+    let plot_height = HISTOGRAM_HEIGHT - MARGIN;
+    let bar_width = WIDTH / HISTOGRAM_BINS as f64;
+
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+        w = WIDTH,
+        h = HISTOGRAM_HEIGHT
+    );
+    for (i, count) in counts.iter().enumerate() {
+        let height = (*count as f64 / tallest) * plot_height;
+        svg.push_str(&format!(
+            r##"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="#4878d0"><title>{}</title></rect>"##,
+            i as f64 * bar_width + 1.0,
+            plot_height - height,
+            bar_width - 2.0,
+            height,
+            count
+        ));
+    }
+    let ms = |duration: Option<&Duration>| {
+        duration
+            .map(|duration| duration.as_secs_f64() * 1_000.0)
+            .unwrap_or(0.0)
+    };
+    svg.push_str(&format!(
+        r#"<text x="0" y="{y}" font-size="12">{:.3} ms</text><text x="{w}" y="{y}" font-size="12" text-anchor="end">{:.3} ms</text></svg>"#,
+        ms(samples.iter().min()),
+        ms(samples.iter().max()),
+        y = HISTOGRAM_HEIGHT - 6.0,
+        w = WIDTH
+    ));
+    svg
+}
+
+/// Horizontal bars, one pair per `(label, raw, materialized)` row, scaled to the slowest median.
+pub fn comparison_bars(rows: &[(String, Duration, Duration)]) -> String {
+    let slowest = rows
+        .iter()
+        .flat_map(|(_, raw, materialized)| [raw.as_secs_f64(), materialized.as_secs_f64()])
+        .fold(0.0, f64::max)
+        .max(f64::EPSILON);
+    let plot_width = WIDTH - LABEL_WIDTH - 90.0;
+    let row_height = BAR_HEIGHT * 2.0 + 12.0;
+    let height = rows.len() as f64 * row_height + MARGIN;
+
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+        w = WIDTH,
+        h = height
+    );
+    for (i, (label, raw, materialized)) in rows.iter().enumerate() {
+        let y = i as f64 * row_height;
+        svg.push_str(&format!(
+            r#"<text x="0" y="{:.1}" font-size="12">{}</text>"#,
+            y + BAR_HEIGHT + 4.0,
+            escape_html(label)
+        ));
+        for (offset, duration, colour) in
+            [(0.0, raw, "#d65f5f"), (BAR_HEIGHT, materialized, "#6acc64")]
+        {
+            let width = (duration.as_secs_f64() / slowest) * plot_width;
+            svg.push_str(&format!(
+                r#"<rect x="{x}" y="{:.1}" width="{:.1}" height="{:.1}" fill="{}"/><text x="{:.1}" y="{:.1}" font-size="11">{:.3} ms</text>"#,
+                y + offset,
+                width,
+                BAR_HEIGHT - 2.0,
+                colour,
+                LABEL_WIDTH + width + 4.0,
+                y + offset + BAR_HEIGHT - 5.0,
+                duration.as_secs_f64() * 1_000.0,
+                x = LABEL_WIDTH
+            ));
+        }
+    }
+    svg.push_str(&format!(
+        r##"<rect x="{x}" y="{y}" width="10" height="10" fill="#d65f5f"/><text x="{:.1}" y="{:.1}" font-size="11">raw SQL</text><rect x="{:.1}" y="{y}" width="10" height="10" fill="#6acc64"/><text x="{:.1}" y="{:.1}" font-size="11">materialized view</text></svg>"##,
+        LABEL_WIDTH + 14.0,
+        height - 5.0,
+        LABEL_WIDTH + 90.0,
+        LABEL_WIDTH + 104.0,
+        height - 5.0,
+        x = LABEL_WIDTH,
+        y = height - 14.0
+    ));
+    svg
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_histogram_spans_min_to_max() {
+        let samples: Vec<Duration> = [1, 1, 2, 5, 10]
+            .into_iter()
+            .map(Duration::from_millis)
+            .collect();
+
+        let counts = histogram(&samples, 3);
+
+        assert_eq!(counts, vec![3, 1, 1]);
+        assert_eq!(histogram(&[], 3), vec![0, 0, 0]);
+        assert_eq!(histogram(&[Duration::from_millis(4); 2], 3), vec![2, 0, 0]);
+        assert!(histogram(&samples, 0).is_empty());
+    }
+
+    #[test]
+    fn test_comparison_bars_escape_labels() {
+        let svg = comparison_bars(&[(
+            String::from("<view>"),
+            Duration::from_millis(4),
+            Duration::from_millis(1),
+        )]);
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("&lt;view&gt;"));
+        assert!(svg.contains("4.000 ms"));
+        assert!(svg.ends_with("</svg>"));
+    }
+}
@@ -0,0 +1,162 @@
+use super::refresh;
+use crate::benchmarking::BenchmarkStats;
+use crate::enums::materialized_view::MaterializedView;
+use crate::enums::refresh_mode::RefreshMode;
+use sqlx::{Pool, Postgres};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RefreshModeReport {
+    pub mode: RefreshMode,
+    pub refreshes: BenchmarkStats,
+    /// Latency of every read of the view made while the refreshes ran.
+    pub reads: BenchmarkStats,
+    pub read_errors: usize,
+}
+
+/// Refreshes a view repeatedly while `readers` clients read it, once per refresh mode.
+#[derive(Clone)]
+pub struct RefreshExperiment {
+    db: Pool<Postgres>,
+    view: MaterializedView,
+    readers: usize,
+    refreshes: usize,
+}
+
+impl RefreshExperiment {
+    pub fn new(
+        db: Pool<Postgres>,
+        view: MaterializedView,
+        readers: usize,
+        refreshes: usize,
+    ) -> Self {
+        Self {
+            db,
+            view,
+            readers,
+            refreshes,
+        }
+    }
+
+    pub async fn compare(&self) -> Result<Vec<RefreshModeReport>, sqlx::Error> {
+        let mut reports = Vec::with_capacity(RefreshMode::all().len());
+        for mode in RefreshMode::all() {
+            reports.push(self.run(mode).await?);
+        }
+
+        Ok(reports)
+    }
+
+    pub async fn run(&self, mode: RefreshMode) -> Result<RefreshModeReport, sqlx::Error> {
+        let done = Arc::new(AtomicBool::new(false));
+        let mut readers = JoinSet::new();
+        for _ in 0..self.readers {
+            let experiment = self.clone();
+            let done = done.clone();
+            readers.spawn(async move { experiment.reader(&done).await });
+        }
+        let refresher = async {
+            let mut durations = Vec::with_capacity(self.refreshes);
+            for _ in 0..self.refreshes {
+                match refresh(&self.db, self.view, mode).await {
+                    Ok(duration) => durations.push(duration),
+                    Err(e) => {
+                        done.store(true, Ordering::Relaxed);
+                        return Err(e);
+                    }
+                }
+            }
+            done.store(true, Ordering::Relaxed);
+            Ok(durations)
+        };
+
+        let (readers, refreshes) = tokio::join!(readers.join_all(), refresher);
+        let refreshes = refreshes?;
+
+        let mut reads = Vec::new();
+        let mut read_errors = 0;
+        for (latencies, errors) in readers {
+            reads.extend(latencies);
+            read_errors += errors;
+        }
+
+        Ok(RefreshModeReport {
+            mode,
+            refreshes: BenchmarkStats::from_samples(&refreshes),
+            reads: BenchmarkStats::from_samples(&reads),
+            read_errors,
+        })
+    }
+
+    async fn reader(&self, done: &AtomicBool) -> (Vec<Duration>, usize) {
+        let sql = format!("SELECT * FROM public.{}", self.view.to_string());
+        let mut latencies = Vec::new();
+        let mut errors = 0;
+        while !done.load(Ordering::Relaxed) {
+            let start = Instant::now();
+            match sqlx::query(&sql).fetch_all(&self.db).await {
+                Ok(_) => latencies.push(start.elapsed()),
+                Err(_) => errors += 1,
+            }
+        }
+        (latencies, errors)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::optimisations::BankSystemManager;
+    use sqlx::PgPool;
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/audit_logs.sql",
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/cards.sql",
+        "../../db/schema/transfers.sql",
+        "../../db/schema/transactions.sql",
+        "../../db/schema/loans.sql",
+        "../../db/schema/payments.sql",
+        "../../db/views/average_transaction_amount.sql",
+    ))]
+    async fn test_it_measures_readers_under_each_refresh_mode(pool: PgPool) -> sqlx::Result<()> {
+        let bank_system_manager = BankSystemManager::with_num_users(pool.clone(), 2_000);
+        bank_system_manager.bulk_insert_data().await?;
+        refresh(
+            &pool,
+            MaterializedView::AverageTransactionAmount,
+            RefreshMode::Blocking,
+        )
+        .await?;
+        let experiment = RefreshExperiment::new(
+            pool.clone(),
+            MaterializedView::AverageTransactionAmount,
+            3,
+            5,
+        );
+
+        let reports = experiment.compare().await?;
+
+        assert_eq!(reports.len(), 2);
+        for report in &reports {
+            assert_eq!(report.refreshes.iterations, 5);
+            assert!(report.reads.iterations > 0);
+            assert_eq!(report.read_errors, 0);
+            println!(
+                "{} - refresh median {:?} - read p50 {:?}, p99 {:?}, max {:?} over {} reads",
+                report.mode.to_string(),
+                report.refreshes.median,
+                report.reads.median,
+                report.reads.p99,
+                report.reads.max,
+                report.reads.iterations
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,465 @@
+use super::scheduler::ViewGraph;
+use super::{is_populated, refresh};
+use crate::enums::materialized_view::MaterializedView;
+use crate::enums::refresh_mode::RefreshMode;
+use crate::enums::stale_view_action::StaleViewAction;
+use crate::enums::view_read_source::ViewReadSource;
+use crate::indexing::view_definition;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sqlx::postgres::PgRow;
+use sqlx::{Pool, Postgres, Row};
+use std::collections::BTreeSet;
+use std::time::Duration;
+use tokio::sync::OnceCell;
+
+/// A view's last refresh, as recorded in `view_refreshes`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RefreshRecord {
+    pub view: MaterializedView,
+    pub refreshed_at: DateTime<Utc>,
+    /// Latest `created_at` of the view's source table when the refresh started, so every row
+    /// created up to then is in the view.
+    pub covered_until: Option<NaiveDateTime>,
+    pub duration: Duration,
+    /// How long ago the refresh happened, by the database clock.
+    pub age: Duration,
+}
+
+/// Latest `created_at` in the view's source table.
+pub async fn covered_until(
+    db: &Pool<Postgres>,
+    view: MaterializedView,
+) -> Result<Option<NaiveDateTime>, sqlx::Error> {
+    Ok(sqlx::query(&format!(
+        "SELECT MAX(created_at) AS covered_until FROM public.{}",
+        view.source_table()
+    ))
+    .fetch_one(db)
+    .await?
+    .get("covered_until"))
+}
+
+/// Records a refresh that has just finished, stamped with the database clock so ages computed by
+/// `last_refresh` do not depend on client clock skew. Returns the recorded `refreshed_at`.
+pub async fn record_refresh(
+    db: &Pool<Postgres>,
+    view: MaterializedView,
+    covered_until: Option<NaiveDateTime>,
+    duration: Duration,
+) -> Result<DateTime<Utc>, sqlx::Error> {
+    Ok(sqlx::query(
+        "
+        INSERT INTO public.view_refreshes (view_name, refreshed_at, covered_until, duration_ms)
+        VALUES ($1, NOW(), $2, $3)
+        ON CONFLICT (view_name) DO UPDATE
+        SET refreshed_at = EXCLUDED.refreshed_at,
+            covered_until = EXCLUDED.covered_until,
+            duration_ms = EXCLUDED.duration_ms
+        RETURNING refreshed_at;
+        ",
+    )
+    .bind(view.to_string())
+    .bind(covered_until)
+    .bind(duration.as_secs_f64() * 1_000.0)
+    .fetch_one(db)
+    .await?
+    .get("refreshed_at"))
+}
+
+/// Refreshes `view` and records the refresh in `view_refreshes`.
+pub async fn refresh_and_record(
+    db: &Pool<Postgres>,
+    view: MaterializedView,
+    mode: RefreshMode,
+) -> Result<RefreshRecord, sqlx::Error> {
+    // Read before refreshing, so rows created during the refresh are not claimed as covered.
+    let covered_until = covered_until(db, view).await?;
+    let duration = refresh(db, view, mode).await?;
+    let refreshed_at = record_refresh(db, view, covered_until, duration).await?;
+
+    Ok(RefreshRecord {
+        view,
+        refreshed_at,
+        covered_until,
+        duration,
+        age: Duration::ZERO,
+    })
+}
+
+pub async fn last_refresh(
+    db: &Pool<Postgres>,
+    view: MaterializedView,
+) -> Result<Option<RefreshRecord>, sqlx::Error> {
+    let row = sqlx::query(
+        "
+        SELECT refreshed_at, covered_until, duration_ms,
+            GREATEST(EXTRACT(EPOCH FROM NOW() - refreshed_at), 0)::FLOAT8 AS age_secs
+        FROM public.view_refreshes
+        WHERE view_name = $1;
+        ",
+    )
+    .bind(view.to_string())
+    .fetch_optional(db)
+    .await?;
+
+    Ok(row.map(|row| RefreshRecord {
+        view,
+        refreshed_at: row.get("refreshed_at"),
+        covered_until: row.get("covered_until"),
+        duration: Duration::from_secs_f64(row.get::<f64, _>("duration_ms") / 1_000.0),
+        age: Duration::from_secs_f64(row.get("age_secs")),
+    }))
+}
+
+pub struct FreshRead {
+    pub rows: Vec<PgRow>,
+    pub source: ViewReadSource,
+    /// The refresh the rows came from, if they came from the view.
+    pub refresh: Option<RefreshRecord>,
+}
+
+/// `view`'s defining query with every view it reads inlined as a CTE of the same name, which
+/// takes precedence over the materialized view, so only base tables are read.
+async fn base_table_sql(
+    db: &Pool<Postgres>,
+    graph: &ViewGraph,
+    view: MaterializedView,
+) -> Result<Option<String>, sqlx::Error> {
+    let definition = |view| async move {
+        Ok::<_, sqlx::Error>(
+            view_definition(db, view)
+                .await?
+                .map(|definition| definition.trim_end().trim_end_matches(';').to_string()),
+        )
+    };
+    let Some(sql) = definition(view).await? else {
+        return Ok(None);
+    };
+    let upstream = graph.upstream(view);
+    if upstream.is_empty() {
+        return Ok(Some(sql));
+    }
+
+    // Refresh order puts each view after the views it reads, as later CTEs may read earlier ones.
+    let mut ctes = Vec::with_capacity(upstream.len());
+    for source in graph.refresh_order() {
+        if upstream.contains(&source) {
+            let Some(source_sql) = definition(source).await? else {
+                return Ok(None);
+            };
+            ctes.push(format!("{} AS ({})", source.to_string(), source_sql));
+        }
+    }
+
+    Ok(Some(format!(
+        "WITH {} SELECT * FROM ({}) AS raw",
+        ctes.join(", "),
+        sql
+    )))
+}
+
+/// Reads materialized views only when their last recorded refresh is recent enough.
+pub struct FreshnessBoundedReader {
+    db: Pool<Postgres>,
+    max_staleness: Duration,
+    on_stale: StaleViewAction,
+    /// Discovered on the first read.
+    graph: OnceCell<ViewGraph>,
+}
+
+impl FreshnessBoundedReader {
+    pub fn new(db: Pool<Postgres>, max_staleness: Duration, on_stale: StaleViewAction) -> Self {
+        Self {
+            db,
+            max_staleness,
+            on_stale,
+            graph: OnceCell::new(),
+        }
+    }
+
+    /// Serves the view if it and every view it reads were refreshed within `max_staleness`,
+    /// otherwise refreshes the stale ones or runs its query against the base tables. A view with
+    /// no recorded refresh counts as stale.
+    pub async fn read(&self, view: MaterializedView) -> Result<FreshRead, sqlx::Error> {
+        let graph = self
+            .graph
+            .get_or_try_init(|| ViewGraph::discover(&self.db))
+            .await?;
+        let upstream = graph.upstream(view);
+        // `view` last, after the views it reads.
+        let chain: Vec<MaterializedView> = graph
+            .refresh_order()
+            .into_iter()
+            .filter(|candidate| *candidate == view || upstream.contains(candidate))
+            .collect();
+
+        let mut stale = BTreeSet::new();
+        let mut last = None;
+        for current in &chain {
+            let record = last_refresh(&self.db, *current).await?;
+            let fresh = match &record {
+                Some(record) => {
+                    record.age <= self.max_staleness && is_populated(&self.db, *current).await?
+                }
+                None => false,
+            };
+            if !fresh {
+                stale.insert(*current);
+            }
+            if *current == view {
+                last = record;
+            }
+        }
+        let view_sql = format!("SELECT * FROM public.{}", view.to_string());
+
+        if stale.is_empty() {
+            return Ok(FreshRead {
+                rows: sqlx::query(&view_sql).fetch_all(&self.db).await?,
+                source: ViewReadSource::MaterializedView,
+                refresh: last,
+            });
+        }
+
+        match self.on_stale {
+            StaleViewAction::Refresh => {
+                // Anything reading a refreshed view is refreshed after it, including `view`.
+                let mut refreshed = BTreeSet::new();
+                let mut record = None;
+                for current in chain {
+                    if stale.contains(&current)
+                        || current == view
+                        || !graph.upstream(current).is_disjoint(&refreshed)
+                    {
+                        record = Some(
+                            refresh_and_record(&self.db, current, RefreshMode::Concurrent).await?,
+                        );
+                        refreshed.insert(current);
+                    }
+                }
+                Ok(FreshRead {
+                    rows: sqlx::query(&view_sql).fetch_all(&self.db).await?,
+                    source: ViewReadSource::RefreshedView,
+                    refresh: record,
+                })
+            }
+            StaleViewAction::RawQuery => {
+                let raw_sql = base_table_sql(&self.db, graph, view)
+                    .await?
+                    .ok_or(sqlx::Error::RowNotFound)?;
+                Ok(FreshRead {
+                    rows: sqlx::query(&raw_sql).fetch_all(&self.db).await?,
+                    source: ViewReadSource::RawQuery,
+                    refresh: None,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::views::scheduler::{RefreshScheduler, SchedulerConfig};
+    use sqlx::PgPool;
+
+    async fn insert_active_loan(pool: &PgPool, user_id: i32) -> sqlx::Result<()> {
+        sqlx::query(
+            "
+            INSERT INTO public.loans (user_id, amount, interest_rate, term_months, status)
+            VALUES ($1, 100.00, 4.50, 24, 'active');
+            ",
+        )
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/users.sql",
+        "../../db/schema/loans.sql",
+        "../../db/schema/view_refreshes.sql",
+        "../../db/views/loans_outstanding.sql",
+    ))]
+    async fn test_it_serves_fresh_views_and_handles_stale_ones(pool: PgPool) -> sqlx::Result<()> {
+        sqlx::raw_sql(
+            "
+            INSERT INTO public.users (given_name, family_name, username, email, phone)
+            VALUES ('a', 'b', 'ab', 'ab@example.com', '1'), ('c', 'd', 'cd', 'cd@example.com', '2');
+            ",
+        )
+        .execute(&pool)
+        .await?;
+        insert_active_loan(&pool, 1).await?;
+        let view = MaterializedView::LoansOutstanding;
+        let refreshing = FreshnessBoundedReader::new(
+            pool.clone(),
+            Duration::from_secs(60),
+            StaleViewAction::Refresh,
+        );
+        let falling_back = FreshnessBoundedReader::new(
+            pool.clone(),
+            Duration::from_secs(60),
+            StaleViewAction::RawQuery,
+        );
+
+        // Never refreshed, so stale.
+        let read = refreshing.read(view).await?;
+        assert_eq!(read.source, ViewReadSource::RefreshedView);
+        assert_eq!(read.rows.len(), 1);
+        let record = last_refresh(&pool, view).await?.unwrap();
+        assert_eq!(record.covered_until, covered_until(&pool, view).await?);
+        assert!(record.age < Duration::from_secs(60));
+
+        insert_active_loan(&pool, 2).await?;
+        let read = refreshing.read(view).await?;
+        assert_eq!(read.source, ViewReadSource::MaterializedView);
+        assert_eq!(read.rows.len(), 1);
+
+        sqlx::query("UPDATE public.view_refreshes SET refreshed_at = NOW() - INTERVAL '1 hour'")
+            .execute(&pool)
+            .await?;
+        let read = falling_back.read(view).await?;
+        assert_eq!(read.source, ViewReadSource::RawQuery);
+        assert_eq!(read.rows.len(), 2);
+        assert!(last_refresh(&pool, view).await?.unwrap().age >= Duration::from_secs(3_600));
+
+        let read = refreshing.read(view).await?;
+        assert_eq!(read.source, ViewReadSource::RefreshedView);
+        assert_eq!(read.rows.len(), 2);
+
+        Ok(())
+    }
+
+    /// Refreshes both views over an account averaging 10.00, then adds a 16.00 transaction. That
+    /// is suspicious against the stale average but not against the fresh one of 11.50.
+    async fn make_average_stale(pool: &PgPool) -> sqlx::Result<()> {
+        sqlx::raw_sql(
+            "
+            INSERT INTO public.users (given_name, family_name, username, email, phone)
+            VALUES ('a', 'b', 'ab', 'ab@example.com', '1');
+            INSERT INTO public.accounts (user_id, account_type, balance) VALUES (1, 'checking', 100.00);
+            INSERT INTO public.transactions (account_id, transaction_type, amount, status)
+            VALUES (1, 'deposit', 10.00, 'completed'), (1, 'deposit', 10.00, 'completed'),
+                (1, 'deposit', 10.00, 'completed');
+            ",
+        )
+        .execute(pool)
+        .await?;
+        for view in [
+            MaterializedView::AverageTransactionAmount,
+            MaterializedView::SuspiciousTransactions,
+        ] {
+            refresh_and_record(pool, view, RefreshMode::Blocking).await?;
+        }
+        sqlx::raw_sql(
+            "
+            INSERT INTO public.transactions (account_id, transaction_type, amount, status)
+            VALUES (1, 'deposit', 16.00, 'completed');
+            UPDATE public.view_refreshes SET refreshed_at = NOW() - INTERVAL '1 hour'
+            WHERE view_name = 'average_transaction_amount';
+            ",
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/transactions.sql",
+        "../../db/schema/view_refreshes.sql",
+        "../../db/views/average_transaction_amount.sql",
+        "../../db/views/suspicious_transactions.sql",
+    ))]
+    async fn test_it_refreshes_stale_upstream_views_first(pool: PgPool) -> sqlx::Result<()> {
+        make_average_stale(&pool).await?;
+        let view = MaterializedView::SuspiciousTransactions;
+        let reader = FreshnessBoundedReader::new(
+            pool.clone(),
+            Duration::from_secs(60),
+            StaleViewAction::Refresh,
+        );
+
+        let read = reader.read(view).await?;
+
+        assert_eq!(read.source, ViewReadSource::RefreshedView);
+        assert!(read.rows.is_empty());
+        let upstream = last_refresh(&pool, MaterializedView::AverageTransactionAmount)
+            .await?
+            .unwrap();
+        assert!(upstream.age < Duration::from_secs(60));
+        assert_eq!(
+            reader.read(view).await?.source,
+            ViewReadSource::MaterializedView
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/transactions.sql",
+        "../../db/schema/view_refreshes.sql",
+        "../../db/views/average_transaction_amount.sql",
+        "../../db/views/suspicious_transactions.sql",
+    ))]
+    async fn test_it_falls_back_to_base_tables_when_only_upstream_is_stale(
+        pool: PgPool,
+    ) -> sqlx::Result<()> {
+        make_average_stale(&pool).await?;
+        let reader = FreshnessBoundedReader::new(
+            pool.clone(),
+            Duration::from_secs(60),
+            StaleViewAction::RawQuery,
+        );
+
+        let read = reader
+            .read(MaterializedView::SuspiciousTransactions)
+            .await?;
+
+        assert_eq!(read.source, ViewReadSource::RawQuery);
+        assert!(read.rows.is_empty());
+        let upstream = last_refresh(&pool, MaterializedView::AverageTransactionAmount)
+            .await?
+            .unwrap();
+        assert!(upstream.age >= Duration::from_secs(3_600));
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/users.sql",
+        "../../db/schema/loans.sql",
+        "../../db/schema/view_refreshes.sql",
+        "../../db/views/loans_outstanding.sql",
+    ))]
+    async fn test_scheduler_records_refreshes(pool: PgPool) -> sqlx::Result<()> {
+        let mut config = SchedulerConfig::new(RefreshMode::Blocking);
+        config.record_refreshes = true;
+        let mut scheduler = RefreshScheduler::new(pool.clone(), config).await?;
+
+        scheduler.refresh_all().await?;
+
+        let record = last_refresh(&pool, MaterializedView::LoansOutstanding)
+            .await?
+            .unwrap();
+        assert_eq!(record.covered_until, None);
+        let reader = FreshnessBoundedReader::new(
+            pool.clone(),
+            Duration::from_secs(60),
+            StaleViewAction::RawQuery,
+        );
+        assert_eq!(
+            reader
+                .read(MaterializedView::LoansOutstanding)
+                .await?
+                .source,
+            ViewReadSource::MaterializedView
+        );
+
+        Ok(())
+    }
+}
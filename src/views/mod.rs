@@ -0,0 +1,124 @@
+pub mod experiment;
+pub mod freshness;
+pub mod scheduler;
+pub mod summary;
+
+use crate::enums::materialized_view::MaterializedView;
+use crate::enums::refresh_mode::RefreshMode;
+use sqlx::{Pool, Postgres, Row};
+use std::time::{Duration, Instant};
+
+/// Refreshes `view` in the given mode, returning how long the refresh took.
+///
+/// A concurrent refresh falls back to blocking while the view is unpopulated, as Postgres refuses
+/// to refresh a view created `WITH NO DATA` concurrently.
+pub async fn refresh(
+    db: &Pool<Postgres>,
+    view: MaterializedView,
+    mode: RefreshMode,
+) -> Result<Duration, sqlx::Error> {
+    let concurrently = mode == RefreshMode::Concurrent && is_populated(db, view).await?;
+    let sql = format!(
+        "REFRESH MATERIALIZED VIEW {}public.{}",
+        if concurrently { "CONCURRENTLY " } else { "" },
+        view.to_string()
+    );
+
+    let start = Instant::now();
+    sqlx::raw_sql(&sql).execute(db).await?;
+
+    Ok(start.elapsed())
+}
+
+pub async fn is_populated(
+    db: &Pool<Postgres>,
+    view: MaterializedView,
+) -> Result<bool, sqlx::Error> {
+    Ok(sqlx::query(
+        "
+        SELECT ispopulated
+        FROM pg_matviews
+        WHERE schemaname = 'public' AND matviewname = $1;
+        ",
+    )
+    .bind(view.to_string())
+    .fetch_one(db)
+    .await?
+    .get("ispopulated"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sqlx::PgPool;
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/users.sql",
+        "../../db/schema/loans.sql",
+        "../../db/views/loans_outstanding.sql",
+    ))]
+    async fn test_it_refreshes_concurrently_and_blocking(pool: PgPool) -> sqlx::Result<()> {
+        sqlx::raw_sql(
+            "
+            INSERT INTO public.users (given_name, family_name, username, email, phone)
+            VALUES ('a', 'b', 'ab', 'ab@example.com', '1');
+            INSERT INTO public.loans (user_id, amount, interest_rate, term_months, status)
+            VALUES (1, 100.00, 4.50, 24, 'active');
+            ",
+        )
+        .execute(&pool)
+        .await?;
+
+        refresh(
+            &pool,
+            MaterializedView::LoansOutstanding,
+            RefreshMode::Concurrent,
+        )
+        .await?;
+        let rows = sqlx::query("SELECT * FROM public.loans_outstanding")
+            .fetch_all(&pool)
+            .await?;
+        assert_eq!(rows.len(), 1);
+
+        sqlx::raw_sql("UPDATE public.loans SET status = 'closed'")
+            .execute(&pool)
+            .await?;
+        refresh(
+            &pool,
+            MaterializedView::LoansOutstanding,
+            RefreshMode::Blocking,
+        )
+        .await?;
+        let rows = sqlx::query("SELECT * FROM public.loans_outstanding")
+            .fetch_all(&pool)
+            .await?;
+        assert!(rows.is_empty());
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/users.sql",
+        "../../db/schema/loans.sql",
+        "../../db/views/loans_outstanding.sql",
+    ))]
+    async fn test_concurrent_refresh_of_unpopulated_view_falls_back(
+        pool: PgPool,
+    ) -> sqlx::Result<()> {
+        sqlx::raw_sql("REFRESH MATERIALIZED VIEW public.loans_outstanding WITH NO DATA")
+            .execute(&pool)
+            .await?;
+        assert!(!is_populated(&pool, MaterializedView::LoansOutstanding).await?);
+
+        refresh(
+            &pool,
+            MaterializedView::LoansOutstanding,
+            RefreshMode::Concurrent,
+        )
+        .await?;
+
+        assert!(is_populated(&pool, MaterializedView::LoansOutstanding).await?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,524 @@
+use super::freshness::{covered_until, record_refresh};
+use super::refresh;
+use crate::enums::materialized_view::MaterializedView;
+use crate::enums::refresh_mode::RefreshMode;
+use chrono::{DateTime, Utc};
+use sqlx::{Pool, Postgres, Row};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::time::MissedTickBehavior;
+
+/// The relations each materialized view reads, as recorded in `pg_depend`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ViewGraph {
+    /// Tables each view reads directly.
+    pub tables: BTreeMap<MaterializedView, BTreeSet<String>>,
+    /// Materialized views each view reads directly.
+    pub views: BTreeMap<MaterializedView, BTreeSet<MaterializedView>>,
+}
+
+impl ViewGraph {
+    /// Reads the dependencies of every materialized view in the public schema.
+    pub async fn discover(db: &Pool<Postgres>) -> Result<Self, sqlx::Error> {
+        let mut graph = Self::default();
+        let matviews =
+            sqlx::query("SELECT matviewname FROM pg_matviews WHERE schemaname = 'public'")
+                .fetch_all(db)
+                .await?;
+        for row in matviews {
+            if let Some(view) = MaterializedView::from_string(row.get("matviewname")) {
+                graph.tables.entry(view).or_default();
+                graph.views.entry(view).or_default();
+            }
+        }
+
+        // A view's query is stored as a rewrite rule, which depends on every relation it reads.
+        let dependencies = sqlx::query(
+            "
+            SELECT DISTINCT dependent.relname AS view, source.relname AS source, source.relkind::text AS kind
+            FROM pg_depend d
+            JOIN pg_rewrite r ON r.oid = d.objid
+            JOIN pg_class dependent ON dependent.oid = r.ev_class
+            JOIN pg_class source ON source.oid = d.refobjid
+            JOIN pg_namespace n ON n.oid = dependent.relnamespace
+            WHERE d.classid = 'pg_rewrite'::regclass
+                AND d.refclassid = 'pg_class'::regclass
+                AND dependent.relkind = 'm'
+                AND n.nspname = 'public'
+                AND source.oid <> dependent.oid
+                AND source.relkind IN ('r', 'p', 'm');
+            ",
+        )
+        .fetch_all(db)
+        .await?;
+        for row in dependencies {
+            let Some(view) = MaterializedView::from_string(row.get("view")) else {
+                continue;
+            };
+            let source: String = row.get("source");
+            let kind: String = row.get("kind");
+            match MaterializedView::from_string(&source) {
+                Some(upstream) if kind == "m" => {
+                    graph.views.entry(view).or_default().insert(upstream);
+                }
+                _ => {
+                    graph.tables.entry(view).or_default().insert(source);
+                }
+            }
+        }
+
+        Ok(graph)
+    }
+
+    /// Every view after the views it reads from.
+    ///
+    /// Postgres cannot create a cycle between materialized views, so every view is included.
+    pub fn refresh_order(&self) -> Vec<MaterializedView> {
+        let mut remaining: BTreeMap<MaterializedView, BTreeSet<MaterializedView>> = self
+            .tables
+            .keys()
+            .chain(self.views.keys())
+            .map(|view| (*view, self.views.get(view).cloned().unwrap_or_default()))
+            .collect();
+        let mut order = Vec::with_capacity(remaining.len());
+        while !remaining.is_empty() {
+            let ready: Vec<MaterializedView> = remaining
+                .iter()
+                .filter(|(_, upstream)| upstream.iter().all(|view| order.contains(view)))
+                .map(|(view, _)| *view)
+                .collect();
+            if ready.is_empty() {
+                order.extend(remaining.keys());
+                break;
+            }
+            for view in ready {
+                remaining.remove(&view);
+                order.push(view);
+            }
+        }
+        order
+    }
+
+    /// Views that read `view`, directly or through another view.
+    pub fn dependents(&self, view: MaterializedView) -> BTreeSet<MaterializedView> {
+        let mut dependents = BTreeSet::new();
+        let mut pending = vec![view];
+        while let Some(current) = pending.pop() {
+            for (dependent, upstream) in &self.views {
+                if upstream.contains(&current) && dependents.insert(*dependent) {
+                    pending.push(*dependent);
+                }
+            }
+        }
+        dependents
+    }
+
+    /// Views that `view` reads, directly or through another view.
+    pub fn upstream(&self, view: MaterializedView) -> BTreeSet<MaterializedView> {
+        let mut upstream = BTreeSet::new();
+        let mut pending = vec![view];
+        while let Some(current) = pending.pop() {
+            for source in self.views.get(&current).into_iter().flatten() {
+                if upstream.insert(*source) {
+                    pending.push(*source);
+                }
+            }
+        }
+        upstream
+    }
+
+    /// Tables `view` reads, directly or through another view.
+    pub fn base_tables(&self, view: MaterializedView) -> BTreeSet<String> {
+        let mut tables = self.tables.get(&view).cloned().unwrap_or_default();
+        for upstream in self.views.get(&view).into_iter().flatten() {
+            tables.extend(self.base_tables(*upstream));
+        }
+        tables
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchedulerConfig {
+    pub mode: RefreshMode,
+    /// Refresh a view once this long has passed since its last refresh.
+    pub interval: Option<Duration>,
+    /// Refresh a view once this many rows of its base tables have been inserted, updated or
+    /// deleted since its last refresh.
+    pub change_threshold: Option<i64>,
+    /// Also record each refresh in `view_refreshes` for freshness bounded readers.
+    pub record_refreshes: bool,
+}
+
+impl SchedulerConfig {
+    pub fn new(mode: RefreshMode) -> Self {
+        Self {
+            mode,
+            interval: None,
+            change_threshold: None,
+            record_refreshes: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViewRefresh {
+    pub refreshed_at: DateTime<Utc>,
+    pub duration: Duration,
+    pub refreshes: u32,
+    /// Base table change counter when the refresh started.
+    pub changes: i64,
+}
+
+/// Refreshes materialized views in dependency order when they are due.
+///
+/// A view is due when the scheduler has not refreshed it yet, when `interval` has passed, or when
+/// its base tables have changed `change_threshold` times. Views that read a due view are
+/// refreshed after it.
+pub struct RefreshScheduler {
+    db: Pool<Postgres>,
+    config: SchedulerConfig,
+    graph: ViewGraph,
+    order: Vec<MaterializedView>,
+    refreshes: HashMap<MaterializedView, ViewRefresh>,
+}
+
+impl RefreshScheduler {
+    pub async fn new(db: Pool<Postgres>, config: SchedulerConfig) -> Result<Self, sqlx::Error> {
+        let graph = ViewGraph::discover(&db).await?;
+        let order = graph.refresh_order();
+
+        Ok(Self {
+            db,
+            config,
+            graph,
+            order,
+            refreshes: HashMap::new(),
+        })
+    }
+
+    pub fn graph(&self) -> &ViewGraph {
+        &self.graph
+    }
+
+    pub fn refresh_order(&self) -> &[MaterializedView] {
+        &self.order
+    }
+
+    pub fn last_refresh(&self, view: MaterializedView) -> Option<&ViewRefresh> {
+        self.refreshes.get(&view)
+    }
+
+    /// Views that would be refreshed now, in refresh order.
+    pub async fn due(&self) -> Result<Vec<MaterializedView>, sqlx::Error> {
+        let counts = self.change_counts().await?;
+        let now = Utc::now();
+
+        let mut due = BTreeSet::new();
+        for view in &self.order {
+            let is_due = match self.refreshes.get(view) {
+                None => true,
+                Some(last) => {
+                    let interval_passed = self.config.interval.is_some_and(|interval| {
+                        (now - last.refreshed_at)
+                            .to_std()
+                            .is_ok_and(|elapsed| elapsed >= interval)
+                    });
+                    let changed = self.config.change_threshold.is_some_and(|threshold| {
+                        self.changes(*view, &counts) - last.changes >= threshold
+                    });
+                    interval_passed || changed
+                }
+            };
+            if is_due {
+                due.insert(*view);
+                due.extend(self.graph.dependents(*view));
+            }
+        }
+
+        Ok(self
+            .order
+            .iter()
+            .filter(|view| due.contains(view))
+            .copied()
+            .collect())
+    }
+
+    /// Refreshes whichever views are due, returning them in the order they were refreshed.
+    pub async fn tick(&mut self) -> Result<Vec<MaterializedView>, sqlx::Error> {
+        let due = self.due().await?;
+        self.refresh_views(&due).await?;
+
+        Ok(due)
+    }
+
+    pub async fn refresh_all(&mut self) -> Result<(), sqlx::Error> {
+        let order = self.order.clone();
+        self.refresh_views(&order).await
+    }
+
+    /// Ticks every `poll` until `stop` is set. A tick that overruns `poll` delays the next one
+    /// rather than causing a burst of catch-up ticks.
+    pub async fn run(&mut self, poll: Duration, stop: &AtomicBool) -> Result<(), sqlx::Error> {
+        let mut interval = tokio::time::interval(poll);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            if stop.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+            self.tick().await?;
+        }
+    }
+
+    async fn refresh_views(&mut self, views: &[MaterializedView]) -> Result<(), sqlx::Error> {
+        if views.is_empty() {
+            return Ok(());
+        }
+        let counts = self.change_counts().await?;
+        for view in views {
+            let covered_until = if self.config.record_refreshes {
+                Some(covered_until(&self.db, *view).await?)
+            } else {
+                None
+            };
+            let refreshed_at = Utc::now();
+            let duration = refresh(&self.db, *view, self.config.mode).await?;
+            if let Some(covered_until) = covered_until {
+                record_refresh(&self.db, *view, covered_until, duration).await?;
+            }
+            let changes = self.changes(*view, &counts);
+            let refreshes = self
+                .refreshes
+                .get(view)
+                .map(|last| last.refreshes)
+                .unwrap_or(0)
+                + 1;
+            self.refreshes.insert(
+                *view,
+                ViewRefresh {
+                    refreshed_at,
+                    duration,
+                    refreshes,
+                    changes,
+                },
+            );
+        }
+
+        Ok(())
+    }
+
+    fn changes(&self, view: MaterializedView, counts: &HashMap<String, i64>) -> i64 {
+        self.graph
+            .base_tables(view)
+            .iter()
+            .filter_map(|table| counts.get(table))
+            .sum()
+    }
+
+    /// Rows inserted, updated or deleted per table since statistics were last reset.
+    async fn change_counts(&self) -> Result<HashMap<String, i64>, sqlx::Error> {
+        let rows = sqlx::query(
+            "
+            SELECT relname, n_tup_ins + n_tup_upd + n_tup_del AS changes
+            FROM pg_stat_user_tables
+            WHERE schemaname = 'public';
+            ",
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| (row.get("relname"), row.get("changes")))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sqlx::PgPool;
+
+    fn graph(edges: &[(MaterializedView, MaterializedView)]) -> ViewGraph {
+        let mut graph = ViewGraph::default();
+        for view in MaterializedView::all() {
+            graph.tables.entry(view).or_default();
+        }
+        for (view, upstream) in edges {
+            graph.views.entry(*view).or_default().insert(*upstream);
+        }
+        graph
+    }
+
+    #[test]
+    fn test_refresh_order_puts_upstream_views_first() {
+        let graph = graph(&[
+            (
+                MaterializedView::AverageTransactionAmount,
+                MaterializedView::SuspiciousTransactions,
+            ),
+            (
+                MaterializedView::SuspiciousTransactions,
+                MaterializedView::LoansOutstanding,
+            ),
+        ]);
+
+        assert_eq!(
+            graph.refresh_order(),
+            vec![
+                MaterializedView::LoansOutstanding,
+                MaterializedView::SuspiciousTransactions,
+                MaterializedView::AverageTransactionAmount,
+            ]
+        );
+        assert_eq!(
+            graph.dependents(MaterializedView::LoansOutstanding),
+            BTreeSet::from([
+                MaterializedView::SuspiciousTransactions,
+                MaterializedView::AverageTransactionAmount,
+            ])
+        );
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/transactions.sql",
+        "../../db/schema/loans.sql",
+        "../../db/views/average_transaction_amount.sql",
+        "../../db/views/loans_outstanding.sql",
+        "../../db/views/suspicious_transactions.sql",
+    ))]
+    async fn test_it_discovers_view_dependencies(pool: PgPool) -> sqlx::Result<()> {
+        let graph = ViewGraph::discover(&pool).await?;
+
+        assert_eq!(
+            graph.views[&MaterializedView::SuspiciousTransactions],
+            BTreeSet::from([MaterializedView::AverageTransactionAmount])
+        );
+        assert_eq!(
+            graph.tables[&MaterializedView::AverageTransactionAmount],
+            BTreeSet::from([String::from("accounts"), String::from("transactions")])
+        );
+        assert_eq!(
+            graph.base_tables(MaterializedView::SuspiciousTransactions),
+            BTreeSet::from([String::from("accounts"), String::from("transactions")])
+        );
+        let order = graph.refresh_order();
+        assert_eq!(order.len(), 3);
+        let position = |view| order.iter().position(|v| *v == view);
+        assert!(
+            position(MaterializedView::AverageTransactionAmount)
+                < position(MaterializedView::SuspiciousTransactions)
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/transactions.sql",
+        "../../db/schema/loans.sql",
+        "../../db/views/average_transaction_amount.sql",
+        "../../db/views/loans_outstanding.sql",
+        "../../db/views/suspicious_transactions.sql",
+    ))]
+    async fn test_it_refreshes_views_whose_tables_changed(pool: PgPool) -> sqlx::Result<()> {
+        let mut config = SchedulerConfig::new(RefreshMode::Concurrent);
+        config.change_threshold = Some(5);
+        let mut scheduler = RefreshScheduler::new(pool.clone(), config).await?;
+
+        assert_eq!(scheduler.tick().await?.len(), 3);
+        assert!(scheduler.tick().await?.is_empty());
+
+        let mut conn = pool.acquire().await?;
+        sqlx::raw_sql(
+            "
+            INSERT INTO public.users (given_name, family_name, username, email, phone)
+            VALUES ('a', 'b', 'ab', 'ab@example.com', '1');
+            INSERT INTO public.accounts (user_id, account_type) VALUES (1, 'checking');
+            INSERT INTO public.transactions (account_id, transaction_type, amount, status)
+            SELECT 1, 'deposit', n, 'completed' FROM generate_series(1, 4) AS n;
+            ",
+        )
+        .execute(&mut *conn)
+        .await?;
+        // Statistics are flushed lazily, so flush this backend's counters before the scheduler reads them.
+        sqlx::query("SELECT pg_stat_force_next_flush()")
+            .execute(&mut *conn)
+            .await?;
+
+        assert_eq!(
+            scheduler.tick().await?,
+            vec![
+                MaterializedView::AverageTransactionAmount,
+                MaterializedView::SuspiciousTransactions,
+            ]
+        );
+        let last = scheduler
+            .last_refresh(MaterializedView::SuspiciousTransactions)
+            .unwrap();
+        assert_eq!(last.refreshes, 2);
+        assert_eq!(last.changes, 5);
+        assert_eq!(
+            scheduler
+                .last_refresh(MaterializedView::LoansOutstanding)
+                .unwrap()
+                .refreshes,
+            1
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/users.sql",
+        "../../db/schema/loans.sql",
+        "../../db/views/loans_outstanding.sql",
+    ))]
+    async fn test_it_refreshes_views_after_the_interval(pool: PgPool) -> sqlx::Result<()> {
+        let mut config = SchedulerConfig::new(RefreshMode::Blocking);
+        config.interval = Some(Duration::from_millis(50));
+        let mut scheduler = RefreshScheduler::new(pool.clone(), config).await?;
+
+        assert_eq!(
+            scheduler.tick().await?,
+            vec![MaterializedView::LoansOutstanding]
+        );
+        assert!(scheduler.tick().await?.is_empty());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(
+            scheduler.tick().await?,
+            vec![MaterializedView::LoansOutstanding]
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/users.sql",
+        "../../db/schema/loans.sql",
+        "../../db/views/loans_outstanding.sql",
+    ))]
+    async fn test_it_runs_until_stopped(pool: PgPool) -> sqlx::Result<()> {
+        let mut config = SchedulerConfig::new(RefreshMode::Blocking);
+        config.interval = Some(Duration::from_millis(50));
+        let mut scheduler = RefreshScheduler::new(pool.clone(), config).await?;
+        let stop = AtomicBool::new(false);
+
+        let (result, _) = tokio::join!(scheduler.run(Duration::from_millis(20), &stop), async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            stop.store(true, Ordering::Relaxed);
+        });
+
+        result?;
+        let last = scheduler
+            .last_refresh(MaterializedView::LoansOutstanding)
+            .unwrap();
+        assert!(last.refreshes >= 2);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,372 @@
+use crate::benchmarking::{insert_throughput, Benchmark, BenchmarkResult};
+use crate::enums::summary_table::SummaryTable;
+use crate::indexing::view_definition;
+use sqlx::{Executor, Pool, Postgres, Row};
+use std::time::{Duration, Instant};
+
+pub async fn is_installed(db: &Pool<Postgres>, summary: SummaryTable) -> Result<bool, sqlx::Error> {
+    Ok(
+        sqlx::query("SELECT 1 FROM pg_tables WHERE schemaname = 'public' AND tablename = $1")
+            .bind(summary.to_string())
+            .fetch_optional(db)
+            .await?
+            .is_some(),
+    )
+}
+
+/// Creates and backfills the summary table and installs its trigger.
+pub async fn install(db: &Pool<Postgres>, summary: SummaryTable) -> Result<(), sqlx::Error> {
+    sqlx::raw_sql(summary.definition()).execute(db).await?;
+    Ok(())
+}
+
+pub async fn uninstall(db: &Pool<Postgres>, summary: SummaryTable) -> Result<(), sqlx::Error> {
+    let view = summary.view().to_string();
+    sqlx::raw_sql(&format!(
+        "
+        DROP TRIGGER IF EXISTS {trigger} ON public.{source};
+        DROP FUNCTION IF EXISTS maintain_{view}_summary();
+        DROP FUNCTION IF EXISTS apply_{view}_delta(INT, DECIMAL, INT);
+        DROP TABLE IF EXISTS public.{table};
+        ",
+        trigger = summary.trigger(),
+        source = summary.source_table(),
+        view = view,
+        table = summary.to_string()
+    ))
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Rows of `sql` that are missing from, or differ from, the rows of `raw_sql`, and vice versa.
+///
+/// Both queries must return the summary's key and value columns.
+pub async fn stale_rows<'c, E>(
+    executor: E,
+    summary: SummaryTable,
+    sql: &str,
+    raw_sql: &str,
+) -> Result<i64, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let (key, value) = summary.columns();
+    let query = format!(
+        "
+        WITH raw AS (
+            SELECT {key}, ROUND({value}, 6) AS value FROM ({raw_sql}) raw
+        ), candidate AS (
+            SELECT {key}, ROUND({value}, 6) AS value FROM ({sql}) candidate
+        )
+        SELECT COUNT(*) AS stale FROM (
+            (SELECT * FROM raw EXCEPT SELECT * FROM candidate)
+            UNION ALL
+            (SELECT * FROM candidate EXCEPT SELECT * FROM raw)
+        ) stale;
+        ",
+        key = key,
+        value = value,
+        raw_sql = raw_sql.trim().trim_end_matches(';'),
+        sql = sql.trim().trim_end_matches(';')
+    );
+
+    Ok(sqlx::query(&query).fetch_one(executor).await?.get("stale"))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SummaryReport {
+    pub summary: SummaryTable,
+    pub raw_sql: BenchmarkResult,
+    pub materialized_view: BenchmarkResult,
+    pub summary_table: BenchmarkResult,
+    pub inserts_per_sec_without_trigger: f64,
+    pub inserts_per_sec_with_trigger: f64,
+    /// How much slower inserts into the source table are with the trigger, as a percentage.
+    pub write_penalty_pct: f64,
+    /// Rows that disagree with the raw query straight after a batch of updates.
+    pub stale_rows_materialized_view: i64,
+    pub stale_rows_summary_table: i64,
+    /// How long a refresh takes to bring the materialized view back in line after those updates.
+    pub refresh: Duration,
+}
+
+/// Compares trigger maintained summary tables against the materialized views they replace and
+/// the views' raw queries.
+pub struct SummaryExperiment {
+    db: Pool<Postgres>,
+    benchmark: Benchmark,
+    insert_batches: usize,
+}
+
+impl SummaryExperiment {
+    pub fn new(db: Pool<Postgres>, benchmark: Benchmark, insert_batches: usize) -> Self {
+        Self {
+            db,
+            benchmark,
+            insert_batches,
+        }
+    }
+
+    /// Measures each summary whose materialized view exists, leaving summaries installed only if
+    /// they already were.
+    pub async fn run(&self, summaries: &[SummaryTable]) -> Result<Vec<SummaryReport>, sqlx::Error> {
+        let mut reports = Vec::with_capacity(summaries.len());
+        for summary in summaries {
+            if let Some(raw_sql) = view_definition(&self.db, summary.view()).await? {
+                reports.push(self.measure(*summary, &raw_sql).await?);
+            }
+        }
+
+        Ok(reports)
+    }
+
+    async fn measure(
+        &self,
+        summary: SummaryTable,
+        raw_sql: &str,
+    ) -> Result<SummaryReport, sqlx::Error> {
+        let installed = is_installed(&self.db, summary).await?;
+        if !installed {
+            install(&self.db, summary).await?;
+        }
+        let report = self.measure_installed(summary, raw_sql).await;
+        if !installed {
+            uninstall(&self.db, summary).await?;
+        }
+
+        report
+    }
+
+    async fn measure_installed(
+        &self,
+        summary: SummaryTable,
+        raw_sql: &str,
+    ) -> Result<SummaryReport, sqlx::Error> {
+        let view = summary.view().to_string();
+        let view_sql = format!("SELECT * FROM public.{}", view);
+        sqlx::raw_sql(&format!(
+            "REFRESH MATERIALIZED VIEW public.{}; ANALYZE public.{};",
+            view,
+            summary.to_string()
+        ))
+        .execute(&self.db)
+        .await?;
+
+        let raw = self
+            .benchmark
+            .run(&self.db, &format!("{} raw SQL", view), raw_sql)
+            .await?;
+        let materialized_view = self
+            .benchmark
+            .run(&self.db, &format!("{} materialized view", view), &view_sql)
+            .await?;
+        let summary_table = self
+            .benchmark
+            .run(&self.db, summary.to_string(), summary.sql())
+            .await?;
+
+        let (stale_rows_materialized_view, stale_rows_summary_table, refresh) =
+            self.staleness(summary, raw_sql, &view_sql).await?;
+
+        let inserts_per_sec_with_trigger =
+            insert_throughput(&self.db, summary.source_table(), self.insert_batches).await?;
+        self.set_trigger_enabled(summary, false).await?;
+        let inserts_per_sec_without_trigger =
+            insert_throughput(&self.db, summary.source_table(), self.insert_batches).await;
+        self.set_trigger_enabled(summary, true).await?;
+        let inserts_per_sec_without_trigger = inserts_per_sec_without_trigger?;
+
+        Ok(SummaryReport {
+            summary,
+            raw_sql: raw,
+            materialized_view,
+            summary_table,
+            inserts_per_sec_without_trigger,
+            inserts_per_sec_with_trigger,
+            write_penalty_pct: if inserts_per_sec_without_trigger == 0.0 {
+                0.0
+            } else {
+                ((inserts_per_sec_without_trigger - inserts_per_sec_with_trigger)
+                    / inserts_per_sec_without_trigger)
+                    * 100.00
+            },
+            stale_rows_materialized_view,
+            stale_rows_summary_table,
+            refresh,
+        })
+    }
+
+    /// Raises the amount of every tenth source row in a rolled back transaction and compares each
+    /// alternative with the raw query before refreshing the view.
+    async fn staleness(
+        &self,
+        summary: SummaryTable,
+        raw_sql: &str,
+        view_sql: &str,
+    ) -> Result<(i64, i64, Duration), sqlx::Error> {
+        let mut tx = self.db.begin().await?;
+        sqlx::query(&format!(
+            "UPDATE public.{} SET amount = amount + 1 WHERE id % 10 = 0",
+            summary.source_table()
+        ))
+        .execute(&mut *tx)
+        .await?;
+
+        let stale_view = stale_rows(&mut *tx, summary, view_sql, raw_sql).await?;
+        let stale_summary = stale_rows(&mut *tx, summary, summary.sql(), raw_sql).await?;
+        let start = Instant::now();
+        sqlx::raw_sql(&format!(
+            "REFRESH MATERIALIZED VIEW public.{}",
+            summary.view().to_string()
+        ))
+        .execute(&mut *tx)
+        .await?;
+        let refresh = start.elapsed();
+        tx.rollback().await?;
+
+        Ok((stale_view, stale_summary, refresh))
+    }
+
+    async fn set_trigger_enabled(
+        &self,
+        summary: SummaryTable,
+        enabled: bool,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::raw_sql(&format!(
+            "ALTER TABLE public.{} {} TRIGGER {}",
+            summary.source_table(),
+            if enabled { "ENABLE" } else { "DISABLE" },
+            summary.trigger()
+        ))
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::optimisations::BankSystemManager;
+    use crate::reporting::Report;
+    use sqlx::PgPool;
+
+    async fn assert_summaries_match_raw_queries(pool: &PgPool) -> sqlx::Result<()> {
+        for summary in SummaryTable::all() {
+            let raw_sql = view_definition(pool, summary.view()).await?.unwrap();
+            assert_eq!(
+                stale_rows(pool, summary, summary.sql(), &raw_sql).await?,
+                0,
+                "{} differs from its view's query",
+                summary.to_string()
+            );
+        }
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/transactions.sql",
+        "../../db/schema/loans.sql",
+        "../../db/views/average_transaction_amount.sql",
+        "../../db/views/loans_outstanding.sql",
+        "../../db/summaries/average_transaction_amount.sql",
+        "../../db/summaries/loans_outstanding.sql",
+    ))]
+    async fn test_triggers_keep_summaries_in_line_with_raw_queries(
+        pool: PgPool,
+    ) -> sqlx::Result<()> {
+        sqlx::raw_sql(
+            "
+            INSERT INTO public.users (given_name, family_name, username, email, phone)
+            VALUES ('a', 'b', 'ab', 'ab@example.com', '1'), ('c', 'd', 'cd', 'cd@example.com', '2');
+            INSERT INTO public.accounts (user_id, account_type) VALUES (1, 'checking'), (2, 'savings');
+            INSERT INTO public.transactions (account_id, transaction_type, amount, status)
+            VALUES (1, 'deposit', 10.00, 'completed'), (1, 'withdrawal', 25.50, 'pending'),
+                (2, 'deposit', 7.25, 'failed');
+            INSERT INTO public.loans (user_id, amount, interest_rate, term_months, status)
+            VALUES (1, 100.00, 4.50, 24, 'active'), (1, 50.00, 4.50, 12, 'approved'),
+                (2, 75.00, 3.00, 36, 'active');
+            ",
+        )
+        .execute(&pool)
+        .await?;
+        assert_summaries_match_raw_queries(&pool).await?;
+
+        sqlx::raw_sql(
+            "
+            UPDATE public.transactions SET amount = 30.00 WHERE id = 1;
+            UPDATE public.transactions SET account_id = 2 WHERE id = 2;
+            DELETE FROM public.transactions WHERE id = 3;
+            UPDATE public.loans SET status = 'active' WHERE id = 2;
+            UPDATE public.loans SET status = 'closed' WHERE id = 1;
+            ",
+        )
+        .execute(&pool)
+        .await?;
+        assert_summaries_match_raw_queries(&pool).await?;
+
+        sqlx::raw_sql("DELETE FROM public.users WHERE id = 2")
+            .execute(&pool)
+            .await?;
+        assert_summaries_match_raw_queries(&pool).await?;
+        let remaining = sqlx::query("SELECT * FROM public.loans_outstanding_summary")
+            .fetch_all(&pool)
+            .await?;
+        assert_eq!(remaining.len(), 1);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        "../../db/schema/audit_logs.sql",
+        "../../db/schema/users.sql",
+        "../../db/schema/accounts.sql",
+        "../../db/schema/cards.sql",
+        "../../db/schema/transfers.sql",
+        "../../db/schema/transactions.sql",
+        "../../db/schema/loans.sql",
+        "../../db/schema/payments.sql",
+        "../../db/views/average_transaction_amount.sql",
+        "../../db/views/loans_outstanding.sql",
+    ))]
+    async fn test_it_compares_summaries_with_materialized_views(pool: PgPool) -> sqlx::Result<()> {
+        BankSystemManager::with_num_users(pool.clone(), 200)
+            .bulk_insert_data()
+            .await?;
+        let experiment = SummaryExperiment::new(pool.clone(), Benchmark::new(1, 5), 1);
+
+        let reports = experiment.run(&SummaryTable::all()).await?;
+
+        assert_eq!(reports.len(), 2);
+        let mut report = Report::new("Summary tables");
+        for summary_report in reports {
+            assert_eq!(summary_report.stale_rows_summary_table, 0);
+            assert!(summary_report.stale_rows_materialized_view > 0);
+            assert!(!is_installed(&pool, summary_report.summary).await?);
+            println!(
+                "{} - raw {:?}, materialized {:?}, summary {:?} - write penalty {:.1}% - {} stale view rows, refresh {:?}",
+                summary_report.summary.to_string(),
+                summary_report.raw_sql.stats.median,
+                summary_report.materialized_view.stats.median,
+                summary_report.summary_table.stats.median,
+                summary_report.write_penalty_pct,
+                summary_report.stale_rows_materialized_view,
+                summary_report.refresh
+            );
+            report.add_view_comparison(
+                summary_report.summary.view().to_string(),
+                summary_report.raw_sql,
+                summary_report.materialized_view,
+            );
+            report.add_benchmark(summary_report.summary_table);
+        }
+        if let Err(e) = report.write_to_env_dir("summaries") {
+            panic!("{}", e);
+        }
+
+        Ok(())
+    }
+}